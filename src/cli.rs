@@ -0,0 +1,1642 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+
+use crate::geom_convert::geometry_type_name;
+use crate::{
+    convert_contents, convert_file_streaming, convert_glob, infer_schema, input_path, is_gzip_path,
+    is_url, read_input_file, ConversionError, ConversionOptions, ConversionSummary, Encoding,
+    ErrorPolicy, FeatureCollectionToCsvWriter, FeatureCollectionToShpWriter,
+    FeatureCollectionToShpWriterConfig, FieldSchema, GeoJsonFormat, InexactNumberPolicy,
+    LongTextPolicy, SchemaSample, ShapefileToGeoJsonConverter, UnencodablePolicy,
+};
+
+/// What the binary was asked to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Convert the GeoJSON into a shapefile at `output_path`.
+    Convert { output_path: String },
+    /// Convert every file matching the input glob pattern into a shapefile in `output_dir`.
+    ConvertGlob { output_dir: String },
+    /// Print the inferred dbf schema without writing any files.
+    Schema { json: bool },
+    /// Convert a shapefile back into GeoJSON at `output_path`.
+    ToGeoJson {
+        output_path: String,
+        format: GeoJsonFormat,
+    },
+}
+
+/// Version of the binary, printed by `--version`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The ways to run the binary, at the top of `--help`.
+const USAGE: &str = "geojson_to_shp [OPTIONS] <INPUT> <OUTPUT>
+       geojson_to_shp [OPTIONS] <GLOB_PATTERN> <OUTPUT_DIR>
+       geojson_to_shp [OPTIONS] schema <INPUT> [--json]
+       geojson_to_shp to-geojson <SHP> <OUTPUT> [--pretty | --lines]";
+
+/// The exit codes, listed at the end of `--help`.
+const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  invalid arguments
+  2  input file not found
+  3  input is not valid GeoJSON
+  4  features could not be converted, or were skipped under --skip-invalid
+  5  output files could not be written";
+
+/// The command line, as clap parses it. `Cli::new` turns it into a `Command` and the
+/// `ConversionOptions` the library runs with.
+#[derive(Debug, Parser)]
+#[command(
+    name = "geojson_to_shp",
+    version = VERSION,
+    about = "Converts a GeoJSON FeatureCollection into a shapefile.",
+    override_usage = USAGE,
+    after_help = EXIT_CODES,
+    subcommand_negates_reqs = true
+)]
+struct CliArgs {
+    /// GeoJSON file, http(s) URL, GeoJSON content, or a glob pattern of files
+    #[arg(required = true)]
+    input: Option<String>,
+    /// Output file, or the directory a glob pattern's files are written to
+    #[arg(required = true)]
+    output: Option<String>,
+    #[command(subcommand)]
+    subcommand: Option<CliSubcommand>,
+    #[command(flatten)]
+    input_args: InputArgs,
+    #[command(flatten)]
+    output_args: OutputArgs,
+    #[command(flatten)]
+    attribute_args: AttributeArgs,
+    #[command(flatten)]
+    geometry_args: GeometryArgs,
+    #[command(flatten)]
+    error_args: ErrorArgs,
+    /// Report how many features --geometry-type leaves out
+    #[arg(long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliSubcommand {
+    /// Print the dbf schema inferred from the input, without writing any files
+    Schema {
+        /// GeoJSON file, http(s) URL or GeoJSON content
+        input: String,
+        /// Print the schema as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Convert a shapefile back into GeoJSON
+    #[command(name = "to-geojson")]
+    ToGeoJson {
+        /// The .shp to read
+        input: String,
+        /// The GeoJSON file to write
+        output: String,
+        /// Indent the GeoJSON
+        #[arg(long, conflicts_with = "lines")]
+        pretty: bool,
+        /// Write newline-delimited GeoJSON, a feature per line
+        #[arg(long)]
+        lines: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Input")]
+struct InputArgs {
+    /// Read the input as a file path
+    #[arg(long, global = true, conflicts_with = "inline")]
+    file: bool,
+    /// Read the input as GeoJSON content
+    #[arg(long, global = true)]
+    inline: bool,
+    /// Convert the input file a feature at a time
+    #[arg(long, global = true)]
+    stream: bool,
+    /// Read positions as [latitude, longitude]
+    #[arg(long, global = true)]
+    lat_lon: bool,
+    /// Convert only the features whose property has this value; repeat to require several
+    #[arg(long, global = true, value_name = "FIELD=VALUE", value_parser = property_condition)]
+    filter: Vec<(String, String)>,
+    /// Seconds the download of a URL input may take
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = timeout, hide = cfg!(not(feature = "http")))]
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Output")]
+struct OutputArgs {
+    /// Kind of file to write, instead of the output extension's
+    #[arg(long, global = true, value_name = "shp|csv|gpkg", value_parser = output_format)]
+    format: Option<OutputFormat>,
+    /// Replace output files that already exist
+    #[arg(long, global = true)]
+    force: bool,
+    /// Create the output directory when missing
+    #[arg(long, global = true)]
+    mkdirs: bool,
+    /// Write only the .shp and .dbf, without the .shx
+    #[arg(long, global = true)]
+    no_index: bool,
+    /// Write no .prj naming the coordinate reference system
+    #[arg(long, global = true)]
+    no_prj: bool,
+    /// Write no .cpg naming the code page of the dbf
+    #[arg(long, global = true)]
+    no_cpg: bool,
+    /// Start a new shapefile at the format's size limit
+    #[arg(long, global = true)]
+    split_at_limit: bool,
+    /// Write a .wkt file with the WKT of every record
+    #[arg(long, global = true)]
+    wkt_sidecar: bool,
+    /// Leave the files of a failed conversion in place
+    #[arg(long, global = true)]
+    keep_partial: bool,
+    /// Write a .shp.xml metadata sidecar
+    #[arg(long, global = true, hide = cfg!(not(feature = "esri-metadata")))]
+    esri_metadata: bool,
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Attributes")]
+struct AttributeArgs {
+    /// Write members found outside of `properties`
+    #[arg(long, global = true)]
+    include_foreign_members: bool,
+    /// Property every feature must carry, written first
+    #[arg(long, global = true, value_name = "NAME")]
+    feature_id_field: Option<String>,
+    /// Also require the id property to be unique
+    #[arg(long, global = true)]
+    require_unique_id: bool,
+    /// Features the schema is inferred from: a number, or all
+    #[arg(long, global = true, value_name = "N|all", value_parser = schema_sample)]
+    schema_sample: Option<SchemaSample>,
+    /// Fail on properties missing from the schema sample
+    #[arg(long, global = true)]
+    strict_schema: bool,
+    /// Width of every Character field, from 1 to 254
+    #[arg(long, global = true, value_name = "WIDTH", value_parser = field_width)]
+    field_width: Option<u8>,
+    /// Character field widths per property, separated by commas
+    #[arg(
+        long,
+        global = true,
+        value_name = "FIELD=WIDTH",
+        value_delimiter = ',',
+        value_parser = field_width_pair
+    )]
+    field_width_map: Vec<(String, u8)>,
+    /// What to do with values past 254 bytes
+    #[arg(long, global = true, value_name = "POLICY")]
+    long_text: Option<LongText>,
+    /// Fields a value is split over under --long-text split
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    long_text_fields: Option<u8>,
+    /// Numbers an f64 cannot hold: nearest, error or a sentinel number
+    #[arg(
+        long,
+        global = true,
+        value_name = "POLICY",
+        value_parser = inexact_numbers,
+        allow_negative_numbers = true
+    )]
+    inexact_numbers: Option<InexactNumberPolicy>,
+    /// Code page of the dbf, such as latin1
+    #[arg(long, global = true, value_name = "LABEL", value_parser = encoding)]
+    encoding: Option<&'static Encoding>,
+    /// What to do with characters the encoding lacks
+    #[arg(long, global = true, value_name = "POLICY")]
+    unencodable: Option<Unencodable>,
+    /// Transliterate text to ASCII
+    #[arg(long, global = true)]
+    transliterate: bool,
+    /// Trim whitespace around text
+    #[arg(long, global = true)]
+    trim_strings: bool,
+    /// Collapse runs of whitespace inside text
+    #[arg(long, global = true)]
+    collapse_whitespace: bool,
+    /// Skip features identical to one already written
+    #[arg(long, global = true)]
+    dedupe: bool,
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Geometry")]
+struct GeometryArgs {
+    /// Round coordinates to N decimal places
+    #[arg(long, global = true, value_name = "N")]
+    coordinate_precision: Option<u8>,
+    /// Simplify lines and rings with Douglas-Peucker
+    #[arg(long, global = true, value_name = "TOLERANCE", value_parser = tolerance)]
+    simplify: Option<f64>,
+    /// Write only the features of this type, such as Polygon
+    #[arg(long, global = true, value_name = "TYPE", value_parser = geometry_type)]
+    geometry_type: Option<&'static str>,
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Errors")]
+struct ErrorArgs {
+    /// Skip features that cannot be converted
+    #[arg(long, global = true)]
+    skip_invalid: bool,
+    /// Report every feature that cannot be converted
+    #[arg(long, global = true)]
+    keep_going: bool,
+    /// How failures and warnings are written to stderr
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text")]
+    error_format: ErrorFormat,
+}
+
+/// `--long-text`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LongText {
+    Truncate,
+    Split,
+    Drop,
+    Error,
+}
+
+/// `--unencodable`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Unencodable {
+    Replace,
+    Error,
+}
+
+/// The kind of file the convert command writes, chosen with `--format` or the output extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// `shp`: a shapefile, the .shp, .shx and .dbf.
+    #[default]
+    Shapefile,
+    /// `csv`: a CSV file with a WKT geometry column (see `FeatureCollectionToCsvWriter`).
+    Csv,
+    /// `gpkg`: a GeoPackage (see `FeatureCollectionToGpkgWriter`).
+    #[cfg(feature = "gpkg")]
+    GeoPackage,
+}
+
+impl OutputFormat {
+    fn from_name(name: &str) -> Result<Option<Self>, &'static str> {
+        match name.to_lowercase().as_str() {
+            "shp" => Ok(Some(OutputFormat::Shapefile)),
+            "csv" => Ok(Some(OutputFormat::Csv)),
+            #[cfg(feature = "gpkg")]
+            "gpkg" => Ok(Some(OutputFormat::GeoPackage)),
+            #[cfg(not(feature = "gpkg"))]
+            "gpkg" => {
+                Err("GeoPackage output requires geojson_to_shp to be built with the gpkg feature")
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// How the binary reports failures (and warnings) on stderr, chosen with `--error-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ErrorFormat {
+    /// `text`: a message for people to read.
+    #[default]
+    #[value(help = "Messages for people to read")]
+    Text,
+    /// `json`: a single JSON object, built with `ConversionError::to_json` (and `flag_hint`
+    /// under `hint`) on failure and `ConversionSummary::to_json` on success with warnings,
+    /// for scripts to parse.
+    #[value(help = "A JSON object for scripts to parse")]
+    Json,
+}
+
+impl ErrorFormat {
+    /// The format asked for by `args`, for reporting arguments `Cli::new` rejects.
+    /// The last `--error-format` wins, given as `--error-format json` or `--error-format=json`.
+    pub fn from_args(args: &[String]) -> Self {
+        let format = args.iter().enumerate().rev().find_map(|(index, arg)| {
+            match arg.strip_prefix("--error-format") {
+                Some("") => args.get(index + 1).map(String::as_str),
+                Some(value) => value.strip_prefix('='),
+                None => None,
+            }
+        });
+        match format {
+            Some("json") => ErrorFormat::Json,
+            _ => ErrorFormat::Text,
+        }
+    }
+}
+
+/// The flags that deal with `err`, for errors whose message names the `ConversionOptions`
+/// field to set instead, which someone running the binary cannot.
+pub fn flag_hint(err: &ConversionError) -> Option<&'static str> {
+    match err {
+        ConversionError::Input { source, .. } | ConversionError::Feature { source, .. } => {
+            flag_hint(source)
+        }
+        ConversionError::OutputExists { .. } => Some("pass --force to replace the files"),
+        ConversionError::MissingOutputDirectory { .. } => {
+            Some("pass --mkdirs to create the directory")
+        }
+        ConversionError::TextTooLong { .. } => {
+            Some("pass --long-text split to keep long values in several fields")
+        }
+        ConversionError::RecordTooLong { .. } => Some(
+            "narrow the fields with --field-width or --field-width-map, or leave long text out with --long-text drop",
+        ),
+        _ => None,
+    }
+}
+
+/// The geometry types `--geometry-type` selects; features of any other type, or without
+/// geometry, are left out.
+const GEOMETRY_TYPES: [&str; 6] = [
+    "Point",
+    "MultiPoint",
+    "LineString",
+    "MultiLineString",
+    "Polygon",
+    "MultiPolygon",
+];
+
+/// Whether the input argument is a file or GeoJSON content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    /// A URL when it starts with `http://` or `https://` (see `is_url`), content when it
+    /// starts with `{` or `[`, a file otherwise (see `input_path`).
+    Detect,
+    /// `--file`
+    File,
+    /// `--inline`
+    Inline,
+}
+
+/// Size from which an input file is converted with `convert_file_streaming`, as reading it
+/// whole risks running out of memory.
+const STREAM_THRESHOLD: u64 = 512 * 1024 * 1024;
+
+/// `STREAM_THRESHOLD` for a gzipped input file, as GeoJSON compresses about tenfold.
+const GZIP_STREAM_THRESHOLD: u64 = STREAM_THRESHOLD / 8;
+
+pub struct Cli {
+    command: Command,
+    input: String,
+    input_kind: InputKind,
+    /// `--stream`
+    stream: bool,
+    /// `--split-at-limit`
+    split_at_limit: bool,
+    format: OutputFormat,
+    error_format: ErrorFormat,
+    /// The flags `--verbose` reports the features left out by.
+    left_out_by: Option<String>,
+    options: ConversionOptions,
+}
+
+impl Cli {
+    /// Parses the command line, `args` starting with the name of the binary.
+    ///
+    /// `--help` and `--version` come back as errors of kind `ErrorKind::DisplayHelp` and
+    /// `ErrorKind::DisplayVersion`, which `clap::Error::exit` prints to stdout.
+    pub fn new<I: Iterator<Item = String>>(args: I) -> Result<Cli, clap::Error> {
+        CliArgs::try_parse_from(args)?
+            .into_cli()
+            .map_err(|message| CliArgs::command().error(ErrorKind::ValueValidation, message))
+    }
+
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// The GeoJSON (or shapefile) file, content or glob pattern the command reads.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// The file the input argument names, `None` when it is GeoJSON content.
+    fn input_file(&self) -> Option<&Path> {
+        match self.input_kind {
+            InputKind::Detect => input_path(&self.input),
+            InputKind::File => Some(Path::new(&self.input)),
+            InputKind::Inline => None,
+        }
+    }
+
+    /// The URL the input argument names, downloaded by `read_input`.
+    #[cfg(feature = "http")]
+    fn input_url(&self) -> Option<&str> {
+        (self.input_kind == InputKind::Detect && is_url(&self.input)).then_some(&self.input)
+    }
+
+    fn read_input(&self) -> Result<String, ConversionError> {
+        #[cfg(feature = "http")]
+        if let Some(url) = self.input_url() {
+            return crate::http::fetch(url, &self.options.http);
+        }
+        match self.input_file() {
+            Some(path) => read_input_file(path),
+            None => Ok(self.input.clone()),
+        }
+    }
+
+    pub fn to_writer(self) -> Result<FeatureCollectionToShpWriter, ConversionError> {
+        let output_path = match &self.command {
+            Command::Convert { output_path } => output_path,
+            _ => {
+                return Err(ConversionError::Usage(
+                    "Only the convert command creates a writer",
+                ))
+            }
+        };
+        let contents = self.read_input()?;
+        let path = self.input_file().map(Path::to_path_buf);
+        FeatureCollectionToShpWriter::with_options(contents, output_path, self.options)
+            .map_err(|err| err.in_file(path.as_deref()))
+    }
+
+    /// Whether to convert the input file a feature at a time: under `--stream`, or when it is
+    /// larger than `STREAM_THRESHOLD` (`GZIP_STREAM_THRESHOLD` when gzipped).
+    fn streams(&self) -> Option<&Path> {
+        let path = self.input_file()?;
+        let threshold = if is_gzip_path(path) {
+            GZIP_STREAM_THRESHOLD
+        } else {
+            STREAM_THRESHOLD
+        };
+        let is_large = || std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > threshold);
+        (self.stream || is_large()).then_some(path)
+    }
+
+    /// Whether `--split-at-limit` was passed, to convert with `convert_in_parts`.
+    pub fn split_at_limit(&self) -> bool {
+        self.split_at_limit
+    }
+
+    /// Converts into as many shapefiles as it takes to stay under the size limit of the
+    /// format, `{output}`, `{output}_001`, `{output}_002`… Returns each with its summary.
+    pub fn convert_in_parts(self) -> Result<Vec<(String, ConversionSummary)>, ConversionError> {
+        match &self.command {
+            Command::Convert { output_path } => {
+                let contents = self.read_input()?;
+                let mut parts = FeatureCollectionToShpWriterConfig::new(&contents, output_path)
+                    .map_err(|err| err.in_file(self.input_file()))?
+                    .options(self.options.clone())
+                    .write_chunked()?;
+                for (_, summary) in parts.iter_mut() {
+                    self.report_left_out(summary);
+                }
+                Ok(parts)
+            }
+            _ => Err(ConversionError::Usage(
+                "Only the convert command converts to a shapefile",
+            )),
+        }
+    }
+
+    /// The kind of file the convert command writes.
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// The options the command line sets, which the conversion is run with.
+    pub fn options(&self) -> &ConversionOptions {
+        &self.options
+    }
+
+    /// How failures and warnings are reported.
+    pub fn error_format(&self) -> ErrorFormat {
+        self.error_format
+    }
+
+    /// Where the command writes to: the output file, or the directory for a glob pattern.
+    pub fn output(&self) -> Option<&str> {
+        match &self.command {
+            Command::Convert { output_path } | Command::ToGeoJson { output_path, .. } => {
+                Some(output_path)
+            }
+            Command::ConvertGlob { output_dir } => Some(output_dir),
+            Command::Schema { .. } => None,
+        }
+    }
+
+    /// Runs the conversion requested on the command line.
+    ///
+    /// Only shapefiles are streamed; CSV files and GeoPackages are written from the whole input.
+    pub fn convert(self) -> Result<ConversionSummary, ConversionError> {
+        let mut summary = self.write_output()?;
+        self.report_left_out(&mut summary);
+        Ok(summary)
+    }
+
+    /// Under `--verbose`, warns of the features `--geometry-type` left out.
+    fn report_left_out(&self, summary: &mut ConversionSummary) {
+        if let Some(flags) = &self.left_out_by {
+            if summary.features_filtered > 0 {
+                summary.warn(format!(
+                    "Left out {} features ({})",
+                    summary.features_filtered, flags
+                ));
+            }
+        }
+    }
+
+    fn write_output(&self) -> Result<ConversionSummary, ConversionError> {
+        let in_file = |err: ConversionError| err.in_file(self.input_file());
+        match &self.command {
+            Command::Convert { output_path } => match (self.format, self.streams()) {
+                (OutputFormat::Shapefile, Some(path)) => {
+                    convert_file_streaming(path, output_path, self.options.clone())
+                }
+                (OutputFormat::Shapefile, None) => convert_contents(
+                    self.read_input()?,
+                    self.input_file(),
+                    output_path,
+                    self.options.clone(),
+                ),
+                (OutputFormat::Csv, _) => {
+                    let mut writer = FeatureCollectionToCsvWriter::with_options(
+                        self.read_input()?,
+                        output_path,
+                        self.options.clone(),
+                    )
+                    .map_err(in_file)?;
+                    if let Err(err) = writer.write() {
+                        writer.finish()?;
+                        return Err(err);
+                    }
+                    writer.finish()
+                }
+                #[cfg(feature = "gpkg")]
+                (OutputFormat::GeoPackage, _) => {
+                    let mut writer = crate::FeatureCollectionToGpkgWriter::with_options(
+                        self.read_input()?,
+                        output_path,
+                        self.options.clone(),
+                    )
+                    .map_err(in_file)?;
+                    if let Err(err) = writer.write() {
+                        writer.finish()?;
+                        return Err(err);
+                    }
+                    writer.finish()
+                }
+            },
+            _ => Err(ConversionError::Usage(
+                "Only the convert command converts to a shapefile",
+            )),
+        }
+    }
+
+    /// Converts every file matching the input pattern, returning each file with its summary.
+    pub fn convert_glob(&self) -> Result<Vec<(PathBuf, ConversionSummary)>, ConversionError> {
+        match &self.command {
+            Command::ConvertGlob { output_dir } => {
+                let mut converted = convert_glob(&self.input, output_dir, &self.options)?;
+                for (_, summary) in converted.iter_mut() {
+                    self.report_left_out(summary);
+                }
+                Ok(converted)
+            }
+            _ => Err(ConversionError::Usage(
+                "Only a glob pattern input converts several files",
+            )),
+        }
+    }
+
+    /// Converts the shapefile back into GeoJSON, returning the number of features written.
+    pub fn to_geojson(&self) -> Result<usize, ConversionError> {
+        match &self.command {
+            Command::ToGeoJson {
+                output_path,
+                format,
+            } => {
+                let converter = ShapefileToGeoJsonConverter::new(&self.input)?;
+                converter.write_to_path(output_path, *format)?;
+                Ok(converter.features().len())
+            }
+            _ => Err(ConversionError::Usage(
+                "Only the to-geojson command converts to GeoJSON",
+            )),
+        }
+    }
+
+    /// Renders the inferred schema as a table, or as JSON when `--json` was passed.
+    pub fn schema_report(&self) -> Result<String, ConversionError> {
+        let contents = self.read_input()?;
+        let schema =
+            infer_schema(&contents, &self.options).map_err(|err| err.in_file(self.input_file()))?;
+        match self.command {
+            Command::Schema { json: true } => Ok(serde_json::to_string_pretty(&schema)?),
+            _ => Ok(schema_table(&schema)),
+        }
+    }
+}
+
+impl CliArgs {
+    /// The `Cli` the arguments ask for, after the checks clap cannot make on its own.
+    fn into_cli(self) -> Result<Cli, &'static str> {
+        let Self {
+            input,
+            output,
+            subcommand,
+            input_args,
+            output_args,
+            attribute_args,
+            geometry_args,
+            error_args,
+            verbose,
+        } = self;
+        let input_kind = match (input_args.file, input_args.inline) {
+            (true, _) => InputKind::File,
+            (_, true) => InputKind::Inline,
+            _ => InputKind::Detect,
+        };
+        if cfg!(not(feature = "http")) && input_args.timeout.is_some() {
+            return Err("--timeout requires geojson_to_shp to be built with the http feature");
+        }
+        if cfg!(not(feature = "esri-metadata")) && output_args.esri_metadata {
+            return Err(
+                "--esri-metadata requires geojson_to_shp to be built with the esri-metadata feature",
+            );
+        }
+
+        let mut options = ConversionOptions {
+            lat_lon_order: input_args.lat_lon,
+            overwrite: output_args.force,
+            create_dirs: output_args.mkdirs,
+            skip_index: output_args.no_index,
+            write_prj: !output_args.no_prj,
+            write_cpg: !output_args.no_cpg,
+            write_wkt_sidecar: output_args.wkt_sidecar,
+            keep_partial: output_args.keep_partial,
+            include_foreign_members: attribute_args.include_foreign_members,
+            feature_id_field: attribute_args.feature_id_field,
+            require_unique_id: attribute_args.require_unique_id,
+            schema_sample: attribute_args.schema_sample.unwrap_or_default(),
+            strict_schema: attribute_args.strict_schema,
+            default_string_width: attribute_args.field_width,
+            string_widths: attribute_args.field_width_map.into_iter().collect(),
+            inexact_numbers: attribute_args.inexact_numbers.unwrap_or_default(),
+            encoding: attribute_args.encoding,
+            transliterate: attribute_args.transliterate,
+            trim_strings: attribute_args.trim_strings,
+            collapse_whitespace: attribute_args.collapse_whitespace,
+            dedupe: attribute_args.dedupe,
+            coordinate_precision: geometry_args.coordinate_precision,
+            simplify: geometry_args.simplify,
+            keep_going: error_args.keep_going,
+            ..Default::default()
+        };
+        #[cfg(feature = "http")]
+        if let Some(timeout) = input_args.timeout {
+            options.http.timeout = timeout;
+        }
+        #[cfg(feature = "esri-metadata")]
+        {
+            options.esri_metadata = output_args.esri_metadata;
+        }
+        if error_args.skip_invalid {
+            options.on_error = ErrorPolicy::Skip;
+        }
+        if let Some(Unencodable::Error) = attribute_args.unencodable {
+            options.unencodable = UnencodablePolicy::Error;
+        }
+        options.long_text = match attribute_args.long_text {
+            None => LongTextPolicy::default(),
+            Some(LongText::Truncate) => LongTextPolicy::Truncate,
+            Some(LongText::Split) => LongTextPolicy::Split {
+                max_fields: attribute_args
+                    .long_text_fields
+                    .map_or(LongTextPolicy::DEFAULT_SPLIT_FIELDS, usize::from),
+            },
+            Some(LongText::Drop) => LongTextPolicy::Drop,
+            Some(LongText::Error) => LongTextPolicy::Error,
+        };
+        if let Some(wanted) = geometry_args.geometry_type {
+            options = options.filter(move |feature| {
+                feature
+                    .geometry
+                    .as_ref()
+                    .map(|geometry| geometry_type_name(&geometry.value))
+                    == Some(wanted)
+            });
+        }
+        // The features either leaves out are counted together.
+        let left_out_by = match geometry_args.geometry_type {
+            Some(wanted) if verbose && input_args.filter.is_empty() => {
+                Some(format!("--geometry-type {}", wanted))
+            }
+            Some(wanted) if verbose => Some(format!("--geometry-type {}, --filter", wanted)),
+            _ => None,
+        };
+        // Every condition has to hold, as with --geometry-type.
+        for (property, value) in input_args.filter {
+            options = options.property_equals(property, value);
+        }
+
+        let cli = |command, input| Cli {
+            command,
+            input,
+            input_kind,
+            stream: input_args.stream,
+            split_at_limit: output_args.split_at_limit,
+            format: OutputFormat::default(),
+            error_format: error_args.error_format,
+            left_out_by: left_out_by.clone(),
+            options,
+        };
+        match subcommand {
+            Some(CliSubcommand::Schema { input, json }) => {
+                check_url_input(&input, input_kind)?;
+                return Ok(cli(Command::Schema { json }, input));
+            }
+            Some(CliSubcommand::ToGeoJson {
+                input,
+                output,
+                pretty,
+                lines,
+            }) => {
+                let format = match (pretty, lines) {
+                    (true, _) => GeoJsonFormat::Pretty,
+                    (_, true) => GeoJsonFormat::NewlineDelimited,
+                    _ => GeoJsonFormat::Compact,
+                };
+                let command = Command::ToGeoJson {
+                    output_path: output,
+                    format,
+                };
+                return Ok(cli(command, input));
+            }
+            None => {}
+        }
+
+        // clap requires both without a subcommand.
+        let (input, output) = (input.unwrap_or_default(), output.unwrap_or_default());
+        check_url_input(&input, input_kind)?;
+        let (output_path, implied_format) = output_base(output)?;
+        let format = match (output_args.format, implied_format) {
+            (Some(format), Some(implied)) if format != implied => {
+                return Err("--format does not match the extension of the output location")
+            }
+            (format, implied) => format.or(implied).unwrap_or_default(),
+        };
+        if output_args.split_at_limit && format != OutputFormat::Shapefile {
+            return Err("--split-at-limit only applies to shapefiles");
+        }
+
+        let command = if input_kind != InputKind::Inline && is_glob_pattern(&input) {
+            if format != OutputFormat::Shapefile {
+                return Err("Only shapefiles are written for a glob pattern input");
+            }
+            Command::ConvertGlob {
+                output_dir: output_path,
+            }
+        } else {
+            let input_file = match input_kind {
+                InputKind::Detect if is_url(&input) => url_file_name(&input).map(Path::new),
+                InputKind::Detect => input_path(&input),
+                InputKind::File => Some(Path::new(&input)),
+                InputKind::Inline => None,
+            };
+            Command::Convert {
+                output_path: output_in_directory(output_path, input_file)?,
+            }
+        };
+        Ok(Cli {
+            format,
+            ..cli(command, input)
+        })
+    }
+}
+
+/// `--filter FIELD=VALUE`
+fn property_condition(condition: &str) -> Result<(String, String), String> {
+    match condition.split_once('=') {
+        Some((property, value)) if !property.is_empty() => {
+            Ok((property.to_string(), value.to_string()))
+        }
+        _ => Err("expected a FIELD=VALUE condition".to_string()),
+    }
+}
+
+/// `--timeout SECONDS`
+fn timeout(seconds: &str) -> Result<Duration, String> {
+    seconds
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| "expected a positive number of seconds".to_string())
+}
+
+/// `--format shp|csv|gpkg`
+fn output_format(name: &str) -> Result<OutputFormat, String> {
+    match OutputFormat::from_name(name) {
+        Ok(Some(format)) => Ok(format),
+        Ok(None) => Err("expected one of: shp, csv, gpkg".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// `--schema-sample N|all`
+fn schema_sample(sample: &str) -> Result<SchemaSample, String> {
+    match sample {
+        "all" => Ok(SchemaSample::All),
+        count => count
+            .parse()
+            .map(SchemaSample::First)
+            .map_err(|_| "expected a number or \"all\"".to_string()),
+    }
+}
+
+/// A dbf Character field width, which has to be between 1 and 254.
+fn field_width(width: &str) -> Result<u8, String> {
+    width
+        .parse()
+        .ok()
+        .filter(|width| (1..=254).contains(width))
+        .ok_or_else(|| "expected a width between 1 and 254".to_string())
+}
+
+/// `--field-width-map FIELD=WIDTH`
+fn field_width_pair(pair: &str) -> Result<(String, u8), String> {
+    match pair.split_once('=') {
+        Some((field, width)) if !field.is_empty() => Ok((field.to_string(), field_width(width)?)),
+        _ => Err("expected FIELD=WIDTH".to_string()),
+    }
+}
+
+/// `--inexact-numbers nearest|error|SENTINEL`
+fn inexact_numbers(policy: &str) -> Result<InexactNumberPolicy, String> {
+    match policy {
+        "nearest" => Ok(InexactNumberPolicy::Nearest),
+        "error" => Ok(InexactNumberPolicy::Error),
+        sentinel => sentinel
+            .parse()
+            .map(InexactNumberPolicy::Sentinel)
+            .map_err(|_| "expected nearest, error or a sentinel number".to_string()),
+    }
+}
+
+/// `--encoding LABEL`
+fn encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| "expected an encoding label, such as latin1 or windows-1252".to_string())
+}
+
+/// `--simplify TOLERANCE`
+fn tolerance(tolerance: &str) -> Result<f64, String> {
+    tolerance
+        .parse()
+        .ok()
+        .filter(|tolerance| *tolerance >= 0.0)
+        .ok_or_else(|| "expected a non-negative tolerance".to_string())
+}
+
+/// `--geometry-type TYPE`, one of `GEOMETRY_TYPES` in any case.
+fn geometry_type(name: &str) -> Result<&'static str, String> {
+    GEOMETRY_TYPES
+        .into_iter()
+        .find(|geometry_type| geometry_type.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("expected one of: {}", GEOMETRY_TYPES.join(", ")))
+}
+
+/// Whether `input` is a glob pattern (such as `data/*.geojson`) rather than a single file.
+fn is_glob_pattern(input: &str) -> bool {
+    input_path(input).is_some() && input.contains(['*', '?', '['])
+}
+
+/// Rejects a URL input when the binary was built without the `http` feature, rather than
+/// failing to parse the URL as GeoJSON.
+fn check_url_input(input: &str, input_kind: InputKind) -> Result<(), &'static str> {
+    if cfg!(not(feature = "http")) && input_kind == InputKind::Detect && is_url(input) {
+        return Err("Reading a URL requires geojson_to_shp to be built with the http feature");
+    }
+    Ok(())
+}
+
+/// The last segment of the path of `url`, such as `parcels.geojson`, to name the output after.
+fn url_file_name(url: &str) -> Option<&str> {
+    let (_, rest) = url.split(['?', '#']).next()?.split_once("://")?;
+    let (_, path) = rest.split_once('/')?;
+    path.rsplit('/').next().filter(|name| !name.is_empty())
+}
+
+/// The output location for an `output` that is an existing directory (with or without a
+/// trailing separator): the directory joined with the name of the input file, so that
+/// `parcels.geojson` (or `parcels.geojson.gz`) is written to `{output}/parcels.shp`. Other
+/// outputs are left as they are.
+fn output_in_directory(output: String, input: Option<&Path>) -> Result<String, &'static str> {
+    if !Path::new(&output).is_dir() {
+        return Ok(output);
+    }
+    let stem = input.and_then(|input| match input.file_stem() {
+        Some(stem) if is_gzip_path(input) => Path::new(stem).file_stem(),
+        stem => stem,
+    });
+    match stem {
+        Some(stem) => Ok(Path::new(&output).join(stem).to_string_lossy().into_owned()),
+        None => Err("The output location is a directory, and inline GeoJSON has no file name to name the output after. Pass an output file name inside the directory."),
+    }
+}
+
+/// The output location without the `.shp`, `.csv` or `.gpkg` extension the writer adds
+/// itself, so that `output.shp` does not become `output.shp.shp`, with the format the
+/// extension names. An output ending in `.geojson` or `.json` is taken for swapped arguments.
+fn output_base(path: String) -> Result<(String, Option<OutputFormat>), &'static str> {
+    let extension = match Path::new(&path).extension() {
+        Some(extension) => extension.to_string_lossy().into_owned(),
+        None => return Ok((path, None)),
+    };
+    if let Some(format) = OutputFormat::from_name(&extension)? {
+        let base = path[..path.len() - extension.len() - 1].to_string();
+        return Ok((base, Some(format)));
+    }
+    match extension.to_lowercase().as_str() {
+        "geojson" | "json" => Err("The output location ends in .geojson or .json; pass the GeoJSON input first and the output location second. For example:
+  ./geojson_to_shp input.geojson output"),
+        _ => Ok((path, None)),
+    }
+}
+
+fn schema_table(schema: &FieldSchema) -> String {
+    let mut table = format!(
+        "{:<24} {:<11} {:<10} {:>5} {:>8}\n",
+        "property", "field", "type", "width", "decimals"
+    );
+    for field in schema.fields.iter() {
+        table.push_str(&format!(
+            "{:<24} {:<11} {:<10} {:>5} {:>8}\n",
+            field.property,
+            field.name,
+            format!("{:?}", field.field_type),
+            field.width,
+            field.decimals
+        ));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn parses_flags_alongside_positional_arguments() {
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--include-foreign-members",
+            "input.geojson",
+            "--trim-strings",
+            "output",
+            "--long-text",
+            "split",
+            "--schema-sample",
+            "100",
+        ]))
+        .unwrap();
+        assert_eq!(cli.input, "input.geojson");
+        assert_eq!(
+            cli.command,
+            Command::Convert {
+                output_path: "output".to_string()
+            }
+        );
+        assert!(cli.options.include_foreign_members);
+        assert!(cli.options.trim_strings);
+        assert!(!cli.options.collapse_whitespace);
+        assert_eq!(cli.options.schema_sample, SchemaSample::First(100));
+        assert_eq!(
+            cli.options.long_text,
+            LongTextPolicy::Split {
+                max_fields: LongTextPolicy::DEFAULT_SPLIT_FIELDS
+            }
+        );
+    }
+
+    #[test]
+    fn parses_field_widths() {
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--field-width-map",
+            "address=200,name=40",
+            "input.geojson",
+            "output",
+            "--field-width",
+            "80",
+        ]))
+        .unwrap();
+        assert_eq!(cli.input, "input.geojson");
+        assert_eq!(cli.options.default_string_width, Some(80));
+        assert_eq!(cli.options.string_widths.get("address"), Some(&200));
+        assert_eq!(cli.options.string_widths.get("name"), Some(&40));
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "--field-width-map",
+            "input.geojson"
+        ]))
+        .is_err());
+        for width in ["0", "255"] {
+            assert!(Cli::new(args(&["geojson_to_shp", "--field-width", width])).is_err());
+            let pair = format!("name={}", width);
+            assert!(Cli::new(args(&["geojson_to_shp", "--field-width-map", &pair])).is_err());
+        }
+        let inline = r#"{"type": "FeatureCollection", "features": [], "query": "a=1"}"#;
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--field-width-map",
+            "name=40",
+            inline,
+            "output",
+        ]))
+        .unwrap();
+        assert_eq!(cli.input, inline);
+        assert_eq!(cli.options.string_widths.len(), 1);
+    }
+
+    #[test]
+    fn prints_help_with_the_exit_codes() {
+        // Arguments after --help are not checked.
+        let err = Cli::new(args(&["geojson_to_shp", "input.geojson", "-h", "--nope"]))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::DisplayHelp);
+        let err = Cli::new(args(&["geojson_to_shp", "--help"])).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::DisplayHelp);
+        let help = err.to_string();
+        for text in [
+            "Usage:",
+            "Output:",
+            "Attributes:",
+            "Geometry:",
+            "--field-width-map",
+            "--no-prj",
+            "--error-format",
+            "schema",
+            "to-geojson",
+        ] {
+            assert!(help.contains(text), "{} is missing from --help", text);
+        }
+        for code in [
+            ConversionError::EXIT_USAGE,
+            ConversionError::EXIT_INPUT_NOT_FOUND,
+            ConversionError::EXIT_PARSE,
+            ConversionError::EXIT_CONVERSION,
+            ConversionError::EXIT_IO,
+        ] {
+            assert!(help.contains(&format!("\n  {}  ", code)));
+        }
+        let err = Cli::new(args(&["geojson_to_shp", "--version"]))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::DisplayVersion);
+        assert!(err.to_string().contains(VERSION));
+    }
+
+    #[test]
+    fn parses_the_error_format() {
+        let argv = args(&[
+            "geojson_to_shp",
+            "--error-format",
+            "json",
+            "in.geojson",
+            "out",
+        ]);
+        let argv: Vec<String> = argv.collect();
+        assert_eq!(ErrorFormat::from_args(&argv), ErrorFormat::Json);
+        let cli = Cli::new(argv.into_iter()).unwrap();
+        assert_eq!(cli.error_format(), ErrorFormat::Json);
+        assert_eq!(cli.output(), Some("out"));
+        let cli = Cli::new(args(&["geojson_to_shp", "in.geojson", "out"])).unwrap();
+        assert_eq!(cli.error_format(), ErrorFormat::Text);
+        let argv: Vec<String> =
+            args(&["geojson_to_shp", "--error-format=json", "--nope"]).collect();
+        assert_eq!(ErrorFormat::from_args(&argv), ErrorFormat::Json);
+        let argv: Vec<String> = args(&[
+            "geojson_to_shp",
+            "--error-format=json",
+            "--error-format",
+            "text",
+        ])
+        .collect();
+        assert_eq!(ErrorFormat::from_args(&argv), ErrorFormat::Text);
+        let argv: Vec<String> = args(&["geojson_to_shp", "--error-formats=json"]).collect();
+        assert_eq!(ErrorFormat::from_args(&argv), ErrorFormat::Text);
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "--error-format",
+            "xml",
+            "in",
+            "out"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        let err = Cli::new(args(&[
+            "geojson_to_shp",
+            "--nope",
+            "input.geojson",
+            "output",
+        ]))
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+        assert!(err.to_string().contains("--nope"));
+    }
+
+    #[test]
+    fn parses_and_runs_the_schema_subcommand() {
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "schema",
+            "./fixtures/points.geojson",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::Schema { json: false });
+
+        let report = cli.schema_report().unwrap();
+        assert!(report.starts_with("property"));
+        assert!(report.contains("category"));
+        assert!(cli.to_writer().is_err());
+    }
+
+    #[test]
+    fn returns_errors_for_malformed_documents() {
+        let documents = [
+            "42",
+            r#"{"type": "Point", "coordinates": [1.0]}"#,
+            r#"{"type": "LineString", "coordinates": [[0.0, 0.0]]}"#,
+            r#"{"type": "Polygon", "coordinates": []}"#,
+            r#"{"type": "Polygon", "coordinates": [[]]}"#,
+        ];
+        for (index, document) in documents.iter().enumerate() {
+            let output = crate::tests::temp_output(&format!("malformed_{}", index));
+            let input = format!("{}.geojson", output);
+            std::fs::write(&input, document).unwrap();
+            let cli = Cli::new(args(&["geojson_to_shp", &input, &output])).unwrap();
+            let result = cli.to_writer().and_then(|mut writer| writer.write());
+            assert!(result.is_err(), "{} was accepted", document);
+        }
+    }
+
+    #[test]
+    fn reads_the_input_as_told() {
+        let cli = Cli::new(args(&["geojson_to_shp", "data.geojsn", "output"])).unwrap();
+        assert!(matches!(
+            cli.to_writer(),
+            Err(ConversionError::InputNotFound { path }) if path == Path::new("data.geojsn")
+        ));
+
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--inline",
+            "data.geojsn",
+            "output",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            cli.to_writer(),
+            Err(ConversionError::Parse { path: None, .. })
+        ));
+
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--file",
+            "{braces}.json",
+            "output",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            cli.to_writer(),
+            Err(ConversionError::InputNotFound { .. })
+        ));
+
+        assert!(Cli::new(args(&["geojson_to_shp", "--file", "--inline", "a", "b"])).is_err());
+    }
+
+    #[test]
+    fn tells_glob_patterns_from_files() {
+        assert!(is_glob_pattern("data/*.geojson"));
+        assert!(!is_glob_pattern("data/points.geojson"));
+        assert!(!is_glob_pattern(
+            "http://example.com/wfs?typeNames=roads&outputFormat=json"
+        ));
+        assert!(!is_glob_pattern(
+            r#"[{"type": "Point", "coordinates": [1, 2]}]"#
+        ));
+    }
+
+    #[test]
+    fn reads_the_input_from_a_url() {
+        let url = "http://example.com/data/parcels.geojson?limit=10";
+        assert_eq!(url_file_name(url), Some("parcels.geojson"));
+        assert_eq!(url_file_name("http://example.com/"), None);
+        assert_eq!(url_file_name("http://example.com"), None);
+        let cli = Cli::new(args(&["geojson_to_shp", url, "output"]));
+        #[cfg(not(feature = "http"))]
+        assert!(cli.is_err());
+        #[cfg(feature = "http")]
+        {
+            let cli = cli.unwrap();
+            assert_eq!(cli.input_url(), Some(url));
+            assert_eq!(cli.input_file(), None);
+            let https = "https://example.com/data/parcels.geojson";
+            let cli = Cli::new(args(&["geojson_to_shp", https, "output"])).unwrap();
+            assert_eq!(cli.input_url(), Some(https));
+
+            let cli = Cli::new(args(&["geojson_to_shp", "--timeout", "2.5", url, "out"])).unwrap();
+            assert_eq!(cli.options.http.timeout, Duration::from_millis(2500));
+            assert!(Cli::new(args(&["geojson_to_shp", "--timeout", "0", url, "out"])).is_err());
+
+            // Downloads the GeoJSON from a local server.
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/points.geojson", listener.local_addr().unwrap());
+            let body = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+            std::thread::spawn(move || {
+                use std::io::{BufRead, BufReader, Write};
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/geo+json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            });
+            let output = crate::tests::temp_output("cli_url");
+            let cli = Cli::new(args(&["geojson_to_shp", "--force", &url, &output])).unwrap();
+            assert!(cli.convert().unwrap().features_written > 0);
+            assert!(Path::new(&format!("{}.shp", output)).exists());
+        }
+    }
+
+    #[test]
+    fn writes_only_the_features_of_the_geometry_type() {
+        let input = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}, "properties": {"name": "b"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "c"}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "d"}}
+        ]}"#;
+        let output = crate::tests::temp_output("geometry_type");
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--geometry-type",
+            "point",
+            "--force",
+            input,
+            &output,
+        ]))
+        .unwrap();
+        assert_eq!(cli.convert().unwrap().features_written, 2);
+        let shapes = shapefile::read_shapes(format!("{}.shp", output)).unwrap();
+        assert!(shapes
+            .iter()
+            .all(|shape| shape.shapetype() == shapefile::ShapeType::Point));
+
+        let geometry_type = |name: &str| {
+            Cli::new(args(&[
+                "geojson_to_shp",
+                "--geometry-type",
+                name,
+                "in.json",
+                "out",
+            ]))
+        };
+        assert!(geometry_type("MultiPolygon").is_ok());
+        assert!(geometry_type("GeometryCollection").is_err());
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "in.json",
+            "out",
+            "--geometry-type"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn reports_the_features_left_out_once_when_streaming() {
+        let input = format!("{}.geojson", crate::tests::temp_output("left_out_input"));
+        std::fs::write(
+            &input,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}},
+                {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}, "properties": {"name": "b"}},
+                {"type": "Feature", "geometry": null, "properties": {"name": "c"}}
+            ]}"#,
+        )
+        .unwrap();
+        let output = crate::tests::temp_output("left_out");
+        let convert = |extra: &[&str]| {
+            let mut argv = vec!["geojson_to_shp", "--geometry-type", "point", "--force"];
+            argv.extend(extra);
+            argv.extend([input.as_str(), output.as_str()]);
+            Cli::new(args(&argv)).unwrap().convert().unwrap()
+        };
+        for extra in [&["--stream", "--verbose"][..], &["--verbose"][..]] {
+            let summary = convert(extra);
+            assert_eq!(summary.features_written, 1);
+            assert_eq!(summary.features_filtered, 2);
+            assert_eq!(
+                summary.warnings,
+                ["Left out 2 features (--geometry-type Point)"]
+            );
+        }
+        assert!(convert(&["--stream"]).warnings.is_empty());
+    }
+
+    #[test]
+    fn writes_only_the_features_matching_the_filters() {
+        let input = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"state": "NY", "lanes": 2}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"state": "NY", "lanes": 4}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5.0, 6.0]}, "properties": {"state": "VT", "lanes": 2}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}, "properties": {"state": "NY", "lanes": 2}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [7.0, 8.0]}, "properties": {"state": "2"}}
+        ]}"#;
+        let output = crate::tests::temp_output("property_filter");
+        let written = |filters: &[&str]| {
+            let arguments: Vec<String> = ["geojson_to_shp", "--force"]
+                .iter()
+                .chain(filters)
+                .chain(&[input, output.as_str()])
+                .map(|arg| arg.to_string())
+                .collect();
+            let cli = Cli::new(arguments.into_iter()).unwrap();
+            cli.convert().unwrap().features_written
+        };
+        assert_eq!(written(&["--filter", "state=VT"]), 1);
+        // A number matches the text it is written as, and every condition has to hold.
+        assert_eq!(
+            written(&[
+                "--filter",
+                "state=NY",
+                "--filter",
+                "lanes=2",
+                "--geometry-type",
+                "Point"
+            ]),
+            1
+        );
+        assert_eq!(
+            written(&["--filter", "lanes=2", "--geometry-type", "Point"]),
+            2
+        );
+        assert_eq!(written(&["--filter", "state="]), 0);
+
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "--filter",
+            "state",
+            "in.json",
+            "out"
+        ]))
+        .is_err());
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "--filter",
+            "=NY",
+            "in.json",
+            "out"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn hints_at_the_flags_for_errors_naming_options() {
+        let output = crate::tests::temp_output("flag_hint");
+        let convert = |force: &str| {
+            let arguments = [
+                "geojson_to_shp",
+                force,
+                "./fixtures/points.geojson",
+                &output,
+            ];
+            Cli::new(args(&arguments).filter(|arg| !arg.is_empty()))
+                .unwrap()
+                .convert()
+        };
+        convert("--force").unwrap();
+        let err = convert("").unwrap_err();
+        assert!(err.to_string().contains("`overwrite` option"));
+        assert!(!err.to_string().contains("--force"));
+        assert_eq!(flag_hint(&err), Some("pass --force to replace the files"));
+        let err = ConversionError::TextTooLong {
+            property: "notes".to_string(),
+            len: 300,
+            limit: 254,
+        }
+        .at_feature(3);
+        assert!(flag_hint(&err).unwrap().contains("--long-text split"));
+        assert_eq!(flag_hint(&ConversionError::MissingGeometry), None);
+    }
+
+    #[test]
+    fn strips_the_shp_extension_from_the_output() {
+        let output = |path: &str| match Cli::new(args(&["geojson_to_shp", "in.geojson", path])) {
+            Ok(cli) => match cli.command {
+                Command::Convert { output_path } => Ok(output_path),
+                command => panic!("unexpected command {:?}", command),
+            },
+            Err(err) => Err(err),
+        };
+        assert_eq!(output("out").unwrap(), "out");
+        assert_eq!(output("out.shp").unwrap(), "out");
+        assert_eq!(output("out.SHP").unwrap(), "out");
+        assert_eq!(output("data/out.Shp").unwrap(), "data/out");
+        assert_eq!(output("out.v2").unwrap(), "out.v2");
+        assert_eq!(output("out.shp.bak").unwrap(), "out.shp.bak");
+        assert!(output("out.geojson").is_err());
+        assert!(output("out.GeoJSON").is_err());
+        assert!(output("out.json").is_err());
+    }
+
+    #[test]
+    fn names_the_output_after_the_input_in_a_directory() {
+        let expected = crate::tests::temp_output("parcels");
+        let dir = Path::new(&expected).parent().unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        let output = |input: &str, output: &str| {
+            Cli::new(args(&["geojson_to_shp", input, output]))
+                .map(|cli| cli.command)
+                .ok()
+        };
+        for output_dir in [dir.clone(), format!("{}/", dir)] {
+            assert_eq!(
+                output("data/parcels.geojson", &output_dir),
+                Some(Command::Convert {
+                    output_path: expected.clone()
+                })
+            );
+        }
+        assert!(output(r#"{"type": "FeatureCollection", "features": []}"#, &dir).is_none());
+        assert_eq!(
+            output("data/parcels.geojson.gz", &dir),
+            Some(Command::Convert {
+                output_path: expected.clone()
+            })
+        );
+        assert_eq!(
+            output("data/parcels.geojson", &format!("{}/roads", dir)),
+            Some(Command::Convert {
+                output_path: format!("{}/roads", dir)
+            })
+        );
+    }
+
+    #[test]
+    fn splits_the_output_at_the_size_limit() {
+        let output = crate::tests::temp_output("split_at_limit");
+        let mut cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "--split-at-limit",
+            "--force",
+            "./fixtures/points.geojson",
+            &output,
+        ]))
+        .unwrap();
+        assert!(cli.split_at_limit());
+        // Room for about two of the point records in each .shp.
+        cli.options.max_file_size = Some(100 + 2 * 28);
+        let parts = cli.convert_in_parts().unwrap();
+        assert!(parts.len() > 1);
+        assert_eq!(parts[0].0, output);
+        assert_eq!(parts[1].0, format!("{}_001", output));
+        for (part, _) in parts.iter() {
+            assert!(std::fs::metadata(format!("{}.shp", part)).unwrap().len() <= 156);
+        }
+
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "--split-at-limit",
+            "in.json",
+            "out.csv"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn picks_the_output_format_from_the_flag_or_extension() {
+        let cli = |arguments: &[&str]| {
+            let arguments = ["geojson_to_shp", "in.geojson"].iter().chain(arguments);
+            Cli::new(
+                arguments
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )
+        };
+        assert_eq!(cli(&["out"]).unwrap().format(), OutputFormat::Shapefile);
+        let csv = cli(&["out.CSV"]).unwrap();
+        assert_eq!(csv.format(), OutputFormat::Csv);
+        assert_eq!(
+            csv.command,
+            Command::Convert {
+                output_path: "out".to_string()
+            }
+        );
+        assert_eq!(
+            cli(&["--format", "csv", "out"]).unwrap().format(),
+            OutputFormat::Csv
+        );
+        assert!(cli(&["--format", "csv", "out.shp"]).is_err());
+        assert!(cli(&["--format", "kml", "out"]).is_err());
+        #[cfg(not(feature = "gpkg"))]
+        assert!(cli(&["out.gpkg"]).is_err());
+    }
+
+    #[cfg(feature = "gpkg")]
+    #[test]
+    fn writes_a_geopackage_for_a_gpkg_output() {
+        let output = crate::tests::temp_output("cli_geopackage");
+        let convert = |arguments: &[&str]| {
+            let arguments = ["geojson_to_shp", "./fixtures/lines.geojson"]
+                .iter()
+                .chain(arguments);
+            Cli::new(
+                arguments
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )
+            .unwrap()
+            .convert()
+            .unwrap()
+        };
+        let summary = convert(&[&format!("{}.gpkg", output)]);
+        assert!(summary.features_written > 0);
+        let connection = rusqlite::Connection::open(format!("{}.gpkg", output)).unwrap();
+        let rows: usize = connection
+            .query_row("SELECT COUNT(*) FROM cli_geopackage", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, summary.features_written);
+        drop(connection);
+
+        let summary = convert(&["--format", "gpkg", "--force", &output]);
+        assert_eq!(rows, summary.features_written);
+    }
+
+    #[test]
+    fn treats_a_glob_input_as_a_batch() {
+        let cli = Cli::new(args(&["geojson_to_shp", "data/*.geojson", "output"])).unwrap();
+        assert_eq!(
+            cli.command,
+            Command::ConvertGlob {
+                output_dir: "output".to_string()
+            }
+        );
+        assert!(matches!(cli.convert(), Err(ConversionError::Usage(_))));
+    }
+
+    #[test]
+    fn parses_the_to_geojson_subcommand() {
+        let cli = Cli::new(args(&[
+            "geojson_to_shp",
+            "to-geojson",
+            "input.shp",
+            "output.geojson",
+            "--lines",
+        ]))
+        .unwrap();
+        assert_eq!(cli.input, "input.shp");
+        assert_eq!(
+            cli.command,
+            Command::ToGeoJson {
+                output_path: "output.geojson".to_string(),
+                format: GeoJsonFormat::NewlineDelimited,
+            }
+        );
+
+        assert!(Cli::new(args(&[
+            "geojson_to_shp",
+            "to-geojson",
+            "input.shp",
+            "output.geojson",
+            "--lines",
+            "--pretty",
+        ]))
+        .is_err());
+    }
+}
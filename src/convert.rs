@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+
+use encoding_rs::Encoding;
+use geojson::Feature;
+use serde_json::{Map, Value as JsonValue};
+use shapefile::dbase::{FieldValue, Record};
+use shapefile::Shape;
+
+use crate::encoding::unencodable_char;
+use crate::geom_convert::geometry_to_shp;
+use crate::schema::{exact_f64, parse_number, text_chunks, CHARACTER_MAX_WIDTH};
+use crate::text::prepare_text_values;
+use crate::{
+    ConversionError, ConversionOptions, ConversionSummary, DbfType, FieldSchema,
+    InexactNumberPolicy, LongTextPolicy, UnencodablePolicy,
+};
+
+/// Converts a single feature into a shape and its dbf record.
+///
+/// A feature without geometry becomes a `Shape::NullShape`. This is the conversion used by
+/// `FeatureCollectionToShpWriter`, for callers who manage their own shapefile writers.
+pub fn feature_to_shape_record(
+    feature: &Feature,
+    schema: &FieldSchema,
+) -> Result<(Shape, Record), ConversionError> {
+    let mut summary = ConversionSummary::default();
+    let options = ConversionOptions::default();
+    convert_feature(feature, schema, &options, None, &mut summary)
+}
+
+pub(crate) fn convert_feature(
+    feature: &Feature,
+    schema: &FieldSchema,
+    options: &ConversionOptions,
+    sampled_properties: Option<&HashSet<String>>,
+    summary: &mut ConversionSummary,
+) -> Result<(Shape, Record), ConversionError> {
+    let shape = match &feature.geometry {
+        Some(geometry) => geometry_to_shp(&geometry.value, options)?,
+        None => Shape::NullShape,
+    };
+    let mut attributes = feature_attributes(feature, options, summary);
+    summary.transliterated_values += prepare_text_values(&mut attributes, options);
+    if let Some(sampled_properties) = sampled_properties {
+        for property in attributes.keys() {
+            if sampled_properties.contains(property) {
+                continue;
+            }
+            if options.strict_schema {
+                return Err(ConversionError::UnexpectedProperty {
+                    property: property.clone(),
+                });
+            }
+            summary.warn(format!(
+                "Property \"{}\" was not in the schema sample and was dropped",
+                property
+            ));
+        }
+    }
+    check_long_text(&attributes, schema, options, summary)?;
+    check_numbers(&mut attributes, schema, options)?;
+    let record = schema.record(&attributes)?;
+    if let (Some(encoding), UnencodablePolicy::Error) = (options.encoding, options.unencodable) {
+        check_encodable(&record, schema, encoding)?;
+    }
+    Ok((shape, record))
+}
+
+/// Errors on the first Character value `encoding` cannot represent.
+fn check_encodable(
+    record: &Record,
+    schema: &FieldSchema,
+    encoding: &'static Encoding,
+) -> Result<(), ConversionError> {
+    for field in schema.fields.iter() {
+        let text = match record.get(&field.name) {
+            Some(FieldValue::Character(Some(text))) => text,
+            _ => continue,
+        };
+        if let Some(character) = unencodable_char(text, encoding) {
+            return Err(ConversionError::UnencodableCharacter {
+                property: field.property.clone(),
+                character,
+                encoding: encoding.name(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Warns about String values that do not fit in the field(s) the schema has for them, or
+/// errors on values longer than a field can hold under `LongTextPolicy::Error`.
+fn check_long_text(
+    attributes: &Map<String, JsonValue>,
+    schema: &FieldSchema,
+    options: &ConversionOptions,
+    summary: &mut ConversionSummary,
+) -> Result<(), ConversionError> {
+    for (property, value) in attributes {
+        let text = match value {
+            JsonValue::String(text) => text,
+            _ => continue,
+        };
+        if options.long_text == LongTextPolicy::Error && text.len() > CHARACTER_MAX_WIDTH {
+            return Err(ConversionError::TextTooLong {
+                property: property.clone(),
+                len: text.len(),
+                limit: CHARACTER_MAX_WIDTH,
+            });
+        }
+        let fields: Vec<_> = schema
+            .fields
+            .iter()
+            .filter(|field| &field.property == property && field.field_type == DbfType::Character)
+            .collect();
+        let truncated = match fields[..] {
+            [] => {
+                if options.long_text == LongTextPolicy::Drop && text.len() > CHARACTER_MAX_WIDTH {
+                    summary.warn(format!(
+                        "Property \"{}\" has values longer than {} bytes and was left out",
+                        property, CHARACTER_MAX_WIDTH
+                    ));
+                }
+                false
+            }
+            [field] if field.part.is_none() => text.len() > field.width as usize,
+            ref parts => text_chunks(text).count() > parts.len(),
+        };
+        if truncated {
+            summary.warn(format!(
+                "Property \"{}\" has values too long for its field(s); they were truncated",
+                property
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Applies `ConversionOptions::inexact_numbers` to the values of Numeric fields that an `f64`
+/// cannot hold exactly, replacing them with the sentinel or erroring.
+fn check_numbers(
+    attributes: &mut Map<String, JsonValue>,
+    schema: &FieldSchema,
+    options: &ConversionOptions,
+) -> Result<(), ConversionError> {
+    if options.inexact_numbers == InexactNumberPolicy::Nearest {
+        return Ok(());
+    }
+    for field in schema.fields.iter() {
+        if field.field_type != DbfType::Numeric {
+            continue;
+        }
+        let value = match attributes.get_mut(&field.property) {
+            Some(value) => value,
+            None => continue,
+        };
+        let number = match value {
+            JsonValue::Number(number) => Some(number.clone()),
+            JsonValue::String(text) => parse_number(text),
+            _ => None,
+        };
+        match number {
+            Some(number) if exact_f64(&number).is_none() => match options.inexact_numbers {
+                InexactNumberPolicy::Sentinel(sentinel) => *value = JsonValue::from(sentinel),
+                _ => {
+                    return Err(ConversionError::InexactNumber {
+                        property: field.property.clone(),
+                        value: number.to_string(),
+                    })
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the feature's properties, merged with its foreign members when requested.
+///
+/// A feature without a `properties` member has no attributes. Properties take precedence
+/// over foreign members with the same name.
+pub(crate) fn feature_attributes(
+    feature: &Feature,
+    options: &ConversionOptions,
+    summary: &mut ConversionSummary,
+) -> Map<String, JsonValue> {
+    let foreign_members = match (&feature.foreign_members, options.include_foreign_members) {
+        (Some(members), true) => members,
+        _ => return feature.properties.clone().unwrap_or_default(),
+    };
+
+    let mut attributes = feature.properties.clone().unwrap_or_default();
+    for (name, value) in foreign_members {
+        if attributes.contains_key(name) {
+            summary.warn(format!(
+                "Foreign member \"{}\" has the same name as a property; keeping the property",
+                name
+            ));
+        } else {
+            attributes.insert(name.clone(), value.clone());
+        }
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_feature_to_shape_and_record() {
+        let feature: Feature = r#"{
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": [1.0, 2.0]},
+            "properties": {"name": "a", "ranking": 3}
+        }"#
+        .parse::<geojson::GeoJson>()
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let schema = FieldSchema::infer(feature.properties.iter()).unwrap();
+
+        let (shape, record) = feature_to_shape_record(&feature, &schema).unwrap();
+        assert!(matches!(shape, Shape::Point(p) if p.x == 1.0 && p.y == 2.0));
+        assert_eq!(
+            record.get("name"),
+            Some(&FieldValue::Character(Some("a".to_string())))
+        );
+        assert_eq!(record.get("ranking"), Some(&FieldValue::Numeric(Some(3.0))));
+    }
+}
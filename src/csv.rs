@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use geojson::FeatureCollection;
+use serde_json::{Map, Value as JsonValue};
+use shapefile::Shape;
+
+use crate::convert::feature_attributes;
+use crate::geom_convert::geometry_to_shp;
+use crate::output::{ensure_directory, OutputFiles};
+use crate::text::prepare_text_values;
+use crate::wkt::shape_to_wkt;
+use crate::{
+    apply_filter, parse_feature_collection, ConversionError, ConversionOptions, ConversionSummary,
+    ErrorPolicy, NullGeometryBehavior, SkippedFeature,
+};
+
+/// Name of the column holding the WKT of every feature's geometry.
+const GEOMETRY_COLUMN: &str = "geometry";
+
+/// Writes a FeatureCollection as a CSV file instead of a shapefile, one row per feature.
+///
+/// The first column, `geometry`, holds the WKT of the geometry (empty for a Null Shape),
+/// followed by a column per property under its own name, in order of appearance. Values are
+/// written as UTF-8 text and quoted as in RFC 4180 where needed; arrays and objects are
+/// written as JSON.
+pub struct FeatureCollectionToCsvWriter {
+    feature_collection: FeatureCollection,
+    options: ConversionOptions,
+    summary: ConversionSummary,
+    /// Set by `write()`, as writing the features again would repeat every row.
+    written: bool,
+    dest: BufWriter<File>,
+    /// Declared last so that it is dropped after the file is closed.
+    outputs: OutputFiles,
+}
+
+impl FeatureCollectionToCsvWriter {
+    /// Creates `{filepath}.csv` for `contents`, which is read as by
+    /// `FeatureCollectionToShpWriter::new`.
+    pub fn new(contents: String, filepath: &str) -> Result<Self, ConversionError> {
+        Self::with_options(contents, filepath, ConversionOptions::default())
+    }
+
+    /// Same as `new`. Of the shapefile options, the ones that shape the dbf (field widths
+    /// and types, long text, encoding) do not apply.
+    pub fn with_options(
+        contents: String,
+        filepath: &str,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let mut feature_collection = parse_feature_collection(&contents)?;
+        let features_filtered = apply_filter(&mut feature_collection, &options);
+        let path = format!("{}.csv", filepath);
+        let mut outputs = OutputFiles::new(options.overwrite);
+        outputs.check(std::slice::from_ref(&path))?;
+        ensure_directory(Path::new(&path), options.create_dirs)?;
+        let dest = BufWriter::new(outputs.create(path)?);
+        Ok(Self {
+            feature_collection,
+            options,
+            summary: ConversionSummary {
+                features_filtered,
+                ..ConversionSummary::default()
+            },
+            written: false,
+            dest,
+            outputs,
+        })
+    }
+
+    pub fn summary(&self) -> &ConversionSummary {
+        &self.summary
+    }
+
+    /// Writes the header and a row per feature, stopping at the first one that cannot be
+    /// converted unless `ErrorPolicy::Skip` is set. Like `FeatureCollectionToShpWriter::write`,
+    /// it can only be called once, and removes the CSV on failure unless `keep_partial` is set.
+    pub fn write(&mut self) -> Result<(), ConversionError> {
+        if self.written {
+            return Err(ConversionError::AlreadyWritten);
+        }
+        self.written = true;
+        let result = self.write_rows();
+        if result.is_err() && !self.options.keep_partial {
+            self.outputs.discard();
+        }
+        result
+    }
+
+    /// Flushes the CSV and moves it into place, returning the summary of the features
+    /// written. Dropping the writer without calling `finish()` removes the CSV instead.
+    pub fn finish(self) -> Result<ConversionSummary, ConversionError> {
+        let Self {
+            dest,
+            mut summary,
+            mut outputs,
+            ..
+        } = self;
+        if let Err(err) = dest.into_inner() {
+            outputs.discard();
+            return Err(err.into_error().into());
+        }
+        summary.files = outputs.commit()?;
+        Ok(summary)
+    }
+
+    fn write_rows(&mut self) -> Result<(), ConversionError> {
+        let attributes: Vec<Map<String, JsonValue>> = self
+            .feature_collection
+            .features
+            .iter()
+            .map(|feature| {
+                let mut attributes = feature_attributes(feature, &self.options, &mut self.summary);
+                self.summary.transliterated_values +=
+                    prepare_text_values(&mut attributes, &self.options);
+                attributes
+            })
+            .collect();
+        let columns = columns(&attributes);
+
+        let header: Vec<String> = std::iter::once(GEOMETRY_COLUMN)
+            .chain(columns.iter().map(String::as_str))
+            .map(field)
+            .collect();
+        writeln!(self.dest, "{}", header.join(","))?;
+
+        for (index, (feature, attributes)) in self
+            .feature_collection
+            .features
+            .iter()
+            .zip(attributes.iter())
+            .enumerate()
+        {
+            self.options.check_cancelled()?;
+            let shape = match &feature.geometry {
+                Some(geometry) => geometry_to_shp(&geometry.value, &self.options),
+                None => match self.options.null_geometry_behavior {
+                    NullGeometryBehavior::Skip => continue,
+                    NullGeometryBehavior::WriteNullShape => Ok(Shape::NullShape),
+                    NullGeometryBehavior::Error => Err(ConversionError::MissingGeometry),
+                },
+            };
+            let shape = match shape {
+                Ok(shape) => shape,
+                Err(error) if self.options.on_error == ErrorPolicy::Skip => {
+                    self.summary.skipped_features.push(SkippedFeature {
+                        index,
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+                Err(error) => return Err(error.at_feature(index)),
+            };
+            let geometry = match shape {
+                Shape::NullShape => String::new(),
+                shape => shape_to_wkt(&shape),
+            };
+            let row: Vec<String> = std::iter::once(field(&geometry))
+                .chain(
+                    columns
+                        .iter()
+                        .map(|name| field(&text_value(attributes.get(name)))),
+                )
+                .collect();
+            writeln!(self.dest, "{}", row.join(","))?;
+            self.summary.features_written += 1;
+        }
+        Ok(())
+    }
+}
+
+/// The name of every property, in order of appearance. A property named `geometry` is left
+/// out, as the geometry column takes its name.
+fn columns(attributes: &[Map<String, JsonValue>]) -> Vec<String> {
+    let mut seen: HashSet<&str> = HashSet::from([GEOMETRY_COLUMN]);
+    let mut columns = Vec::new();
+    for name in attributes.iter().flat_map(|attributes| attributes.keys()) {
+        if seen.insert(name) {
+            columns.push(name.clone());
+        }
+    }
+    columns
+}
+
+fn text_value(value: Option<&JsonValue>) -> String {
+    match value {
+        None | Some(JsonValue::Null) => String::new(),
+        Some(JsonValue::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Quotes `value` when it holds a comma, a quote or a line break, doubling its quotes.
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_features_as_csv_rows() {
+        let output = crate::tests::temp_output("csv");
+        let output = output.as_str();
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.5, 2.0]}, "properties": {"name": "Zürich, \"ZH\"", "population": 421878}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}, "properties": {"name": "line", "tags": ["a", "b"], "geometry": "dropped"}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "nowhere"}}
+        ]"#;
+        let mut writer = FeatureCollectionToCsvWriter::new(contents.to_string(), output).unwrap();
+        writer.write().unwrap();
+        assert!(matches!(
+            writer.write(),
+            Err(ConversionError::AlreadyWritten)
+        ));
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.features_written, 2);
+
+        let csv = std::fs::read_to_string(format!("{}.csv", output)).unwrap();
+        assert_eq!(
+            csv,
+            "geometry,name,population,tags\n\
+             POINT (1.5 2),\"Zürich, \"\"ZH\"\"\",421878,\n\
+             \"LINESTRING (0 0, 1 1)\",line,,\"[\"\"a\"\",\"\"b\"\"]\"\n"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use encoding_rs::{EncoderResult, Encoding};
+
+use crate::{DbfType, FieldSchema};
+
+/// A .dbf destination that transcodes Character fields from UTF-8 into `encoding`.
+///
+/// dbase only writes Character values as UTF-8, one field at a time. Knowing the record
+/// layout, the writes that land on a Character field are re-encoded and padded back to
+/// the field width, so every other byte (and every offset) is left as dbase wrote it.
+pub(crate) struct EncodedDbf<W> {
+    dest: W,
+    encoding: Option<&'static Encoding>,
+    header_len: u64,
+    record_len: u64,
+    /// Offset within a record and width of every Character field.
+    character_fields: Vec<(u64, usize)>,
+    position: u64,
+}
+
+impl<W: Write + Seek> EncodedDbf<W> {
+    /// Wraps `dest`; with no `encoding`, the bytes are written untouched.
+    pub(crate) fn new(dest: W, schema: &FieldSchema, encoding: Option<&'static Encoding>) -> Self {
+        let mut character_fields = Vec::new();
+        // Every record starts with its deletion flag.
+        let mut offset = 1;
+        for field in schema.fields.iter() {
+            if field.field_type == DbfType::Character {
+                character_fields.push((offset, field.width as usize));
+            }
+            offset += field.width as u64;
+        }
+        Self {
+            dest,
+            encoding,
+            header_len: schema.header_len(),
+            record_len: schema.record_len(),
+            character_fields,
+            position: 0,
+        }
+    }
+
+    fn is_character_field(&self, len: usize) -> bool {
+        if self.position < self.header_len {
+            return false;
+        }
+        let offset = (self.position - self.header_len) % self.record_len;
+        self.character_fields.contains(&(offset, len))
+    }
+}
+
+impl<W: Write + Seek> Write for EncodedDbf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.encoding {
+            Some(encoding) if self.is_character_field(buf.len()) => {
+                let text = String::from_utf8_lossy(buf);
+                let mut encoded = encode_lossy(text.trim_end_matches(' '), encoding);
+                encoded.resize(buf.len(), b' ');
+                self.dest.write_all(&encoded)?;
+            }
+            _ => self.dest.write_all(buf)?,
+        }
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.dest.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for EncodedDbf<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.dest.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+/// Encodes `text`, replacing the characters `encoding` cannot represent with `?`.
+pub(crate) fn encode_lossy(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    let mut encoder = encoding.new_encoder();
+    let mut encoded = Vec::with_capacity(text.len());
+    let mut input = text;
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(input, &mut encoded, true);
+        input = &input[read..];
+        match result {
+            EncoderResult::InputEmpty => return encoded,
+            EncoderResult::OutputFull => encoded.reserve(input.len() + 8),
+            EncoderResult::Unmappable(_) => encoded.push(b'?'),
+        }
+    }
+}
+
+/// The first character of `text` that `encoding` cannot represent.
+pub(crate) fn unencodable_char(text: &str, encoding: &'static Encoding) -> Option<char> {
+    text.chars().find(|c| {
+        let mut buf = [0; 4];
+        let (_, _, had_errors) = encoding.encode(c.encode_utf8(&mut buf));
+        had_errors
+    })
+}
+
+/// The `.cpg` contents naming `encoding`: the bare code page number for the Windows code
+/// pages (`1252`), as ArcGIS and QGIS expect, and the encoding name otherwise.
+pub(crate) fn code_page(encoding: &'static Encoding) -> &'static str {
+    let name = encoding.name();
+    name.strip_prefix("windows-").unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn encodes_and_replaces_characters() {
+        let latin1 = Encoding::for_label(b"latin1").unwrap();
+        assert_eq!(encode_lossy("Zürich", latin1), b"Z\xfcrich");
+        assert_eq!(encode_lossy("Łódź", latin1), b"?\xf3d?");
+        assert_eq!(unencodable_char("Łódź", latin1), Some('Ł'));
+        assert_eq!(unencodable_char("Zürich", latin1), None);
+        assert_eq!(code_page(latin1), "1252");
+        assert_eq!(code_page(encoding_rs::SHIFT_JIS), "Shift_JIS");
+    }
+
+    #[test]
+    fn transcodes_only_character_fields() {
+        let schema = FieldSchema::infer([serde_json::json!({"city": "Zürich", "rank": 1})
+            .as_object()
+            .unwrap()])
+        .unwrap();
+        let mut dbf = Cursor::new(Vec::new());
+        let mut dest = EncodedDbf::new(&mut dbf, &schema, Some(encoding_rs::WINDOWS_1252));
+        let header = vec![0; dest.header_len as usize];
+        dest.write_all(&header).unwrap();
+        dest.write_all(b" ").unwrap();
+        let city = &schema.fields[0];
+        let mut value = "Zürich".as_bytes().to_vec();
+        value.resize(city.width as usize, b' ');
+        dest.write_all(&value).unwrap();
+
+        let record = &dbf.get_ref()[header.len()..];
+        assert_eq!(&record[..8], b" Z\xfcrich ");
+        assert_eq!(record.len(), 1 + city.width as usize);
+    }
+}
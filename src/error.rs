@@ -0,0 +1,487 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as JsonValue;
+use shapefile::ShapeType;
+use thiserror::Error;
+
+use crate::DbfType;
+
+/// Errors raised while converting GeoJSON into a shapefile.
+#[derive(Debug, Error)]
+pub enum ConversionError {
+    #[error("latitude {latitude} is outside of the valid range [-90, 90]")]
+    LatitudeOutOfRange { latitude: f64 },
+    #[error("unsupported geometry type: {geometry_type}")]
+    UnsupportedGeometry { geometry_type: String },
+    #[error("invalid geometry: {reason}")]
+    InvalidGeometry { reason: &'static str },
+    #[error("property \"{property}\": unsupported value {value}, only numbers and strings are supported")]
+    UnsupportedPropertyType { property: String, value: String },
+    #[error("property \"{property}\": expected {}, got {value}", expected_kind(*.expected))]
+    PropertyTypeMismatch {
+        property: String,
+        expected: DbfType,
+        value: String,
+    },
+    #[error("invalid dbf field name \"{name}\": {reason}")]
+    InvalidFieldName { name: String, reason: &'static str },
+    #[error("dbf field name \"{name}\" is used by more than one field")]
+    DuplicateFieldName { name: String },
+    #[error("the features have {count} properties, but a dbf holds at most {limit} fields")]
+    TooManyFields { count: usize, limit: usize },
+    #[error("dbf records would take {len} bytes, over the {limit} bytes dBASE allows; the widest fields are {}. Narrow them with the `default_string_width` or `string_widths` options, or leave long text out with `long_text: LongTextPolicy::Drop`", .widest.join(", "))]
+    RecordTooLong {
+        len: u64,
+        limit: u64,
+        widest: Vec<String>,
+    },
+    #[error("a dbf holds at most {limit} records")]
+    TooManyRecords { limit: u64 },
+    #[error("property \"{property}\" was not seen in the features the schema was inferred from")]
+    UnexpectedProperty { property: String },
+    #[error("property \"{property}\": {character:?} cannot be written in {encoding}")]
+    UnencodableCharacter {
+        property: String,
+        character: char,
+        encoding: &'static str,
+    },
+    #[error("property \"{property}\" holds a value of {len} bytes, longer than the {limit} a dbf Character field holds; set `long_text` to `LongTextPolicy::Split` to keep it in several fields")]
+    TextTooLong {
+        property: String,
+        len: usize,
+        limit: usize,
+    },
+    #[error("property \"{property}\": {value} cannot be written exactly as a dbf number")]
+    InexactNumber { property: String, value: String },
+    #[error("features {indices:?} have no \"{field}\" id property")]
+    MissingFeatureId { field: String, indices: Vec<usize> },
+    #[error("features {indices:?} share a \"{field}\" id with an earlier feature")]
+    DuplicateFeatureId { field: String, indices: Vec<usize> },
+    #[error("a {actual} shape cannot be written to a {expected} shapefile")]
+    MixedShapeTypes {
+        expected: ShapeType,
+        actual: ShapeType,
+    },
+    #[error("the features were already written; a writer can only write() once")]
+    AlreadyWritten,
+    #[error("the conversion was cancelled")]
+    Cancelled,
+    #[error("feature has no geometry")]
+    MissingGeometry,
+    #[error("the shapefile would grow past {limit} bytes; write it in chunks with write_chunked")]
+    FileSizeLimit { limit: u64 },
+    #[error("cannot create {}: the directory {} does not exist; set the `create_dirs` option to create it", .path.display(), .directory.display())]
+    MissingOutputDirectory { directory: PathBuf, path: PathBuf },
+    #[error("refusing to overwrite {}; set the `overwrite` option to replace them", .paths.join(", "))]
+    OutputExists { paths: Vec<String> },
+    /// The input is not valid GeoJSON. `context` holds its first 200 characters, and
+    /// `location` the (one-based) line and column of a JSON syntax error.
+    #[error("could not parse the GeoJSON{}: {}\n  the input starts with: {context}", in_file(.path), describe_parse_error(.source, *.location))]
+    Parse {
+        path: Option<PathBuf>,
+        location: Option<(usize, usize)>,
+        context: String,
+        source: Box<geojson::Error>,
+    },
+    #[cfg(feature = "topojson")]
+    #[error("could not decode the TopoJSON: {0}")]
+    TopoJson(#[from] topojson::Error),
+    #[cfg(feature = "http")]
+    #[error("could not download {url}: {reason}")]
+    Http { url: String, reason: String },
+    #[cfg(feature = "gpkg")]
+    #[error("could not write the GeoPackage: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("could not write the shapefile: {0}")]
+    Shapefile(#[from] shapefile::Error),
+    #[error("could not serialize JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("input file {} does not exist (GeoJSON content must start with `{{` or `[`)", .path.display())]
+    InputNotFound { path: PathBuf },
+    #[error("{0}")]
+    Usage(&'static str),
+    #[error("invalid glob pattern: {0}")]
+    Glob(#[from] glob::PatternError),
+    /// Another error, raised while converting the file at `path`.
+    #[error("{}: {source}", .path.display())]
+    Input {
+        path: PathBuf,
+        source: Box<ConversionError>,
+    },
+    /// Another error, raised while converting the feature at `index` (zero-based).
+    #[error("feature {index}, {source}")]
+    Feature {
+        index: usize,
+        source: Box<ConversionError>,
+    },
+    /// Every feature that failed, collected under `ConversionOptions::keep_going`.
+    #[error("{} features could not be converted:{}", .0.len(), list_errors(.0))]
+    Features(Vec<FeatureError>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// A feature that could not be converted, and why.
+#[derive(Debug, Error)]
+#[error("feature {index}, {error}")]
+pub struct FeatureError {
+    /// Zero-based index of the feature in the (filtered) collection.
+    pub index: usize,
+    pub error: ConversionError,
+}
+
+impl ConversionError {
+    /// Exit code of the binary for invalid arguments or usage.
+    pub const EXIT_USAGE: i32 = 1;
+    /// Exit code for an input file that does not exist.
+    pub const EXIT_INPUT_NOT_FOUND: i32 = 2;
+    /// Exit code for input that is not valid GeoJSON (or TopoJSON).
+    pub const EXIT_PARSE: i32 = 3;
+    /// Exit code for features or a schema that cannot be converted into a shapefile, and for
+    /// a conversion that skipped features that failed.
+    pub const EXIT_CONVERSION: i32 = 4;
+    /// Exit code for output (or other) files that cannot be read or written.
+    pub const EXIT_IO: i32 = 5;
+
+    /// The exit code the binary reports the error with, one of the `EXIT_*` constants listed
+    /// in `--help`, so that scripts can tell a bad invocation from a bad input file.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConversionError::Usage(_) | ConversionError::Glob(_) => Self::EXIT_USAGE,
+            ConversionError::InputNotFound { .. } => Self::EXIT_INPUT_NOT_FOUND,
+            ConversionError::OutputExists { .. }
+            | ConversionError::MissingOutputDirectory { .. }
+            | ConversionError::Shapefile(_)
+            | ConversionError::Json(_)
+            | ConversionError::Io(_) => Self::EXIT_IO,
+            #[cfg(feature = "gpkg")]
+            ConversionError::Sqlite(_) => Self::EXIT_IO,
+            #[cfg(feature = "http")]
+            ConversionError::Http { .. } => Self::EXIT_IO,
+            ConversionError::Parse { .. } => Self::EXIT_PARSE,
+            #[cfg(feature = "topojson")]
+            ConversionError::TopoJson(_) => Self::EXIT_PARSE,
+            ConversionError::Input { source, .. } | ConversionError::Feature { source, .. } => {
+                source.exit_code()
+            }
+            _ => Self::EXIT_CONVERSION,
+        }
+    }
+
+    pub(crate) fn at_feature(self, index: usize) -> Self {
+        ConversionError::Feature {
+            index,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error with its feature indices shifted by `offset`, for an error raised while
+    /// writing the features from the `offset`th on.
+    pub(crate) fn offset_feature_index(self, offset: usize) -> Self {
+        match self {
+            ConversionError::Feature { index, source } => ConversionError::Feature {
+                index: index + offset,
+                source,
+            },
+            ConversionError::Features(errors) => ConversionError::Features(
+                errors
+                    .into_iter()
+                    .map(|error| FeatureError {
+                        index: error.index + offset,
+                        error: error.error,
+                    })
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    /// Builds a `Parse` error, keeping the start of `contents` to show where it went wrong.
+    pub(crate) fn parse(source: geojson::Error, contents: &str) -> Self {
+        let context = match contents.char_indices().nth(PARSE_CONTEXT_LEN) {
+            Some((end, _)) => format!("{}…", &contents[..end]),
+            None => contents.to_string(),
+        };
+        let location = match &source {
+            geojson::Error::MalformedJson(err) if err.line() > 0 => {
+                Some((err.line(), err.column()))
+            }
+            _ => None,
+        };
+        ConversionError::Parse {
+            path: None,
+            location,
+            context,
+            source: Box::new(source),
+        }
+    }
+
+    /// Records the file the GeoJSON was read from on a `Parse` error.
+    pub(crate) fn in_file(mut self, file: Option<&Path>) -> Self {
+        if let ConversionError::Parse { path, .. } = &mut self {
+            *path = file.map(Path::to_path_buf);
+        }
+        self
+    }
+
+    /// The index of the feature the error was raised for, if it concerns a single feature.
+    pub fn feature_index(&self) -> Option<usize> {
+        match self {
+            ConversionError::Feature { index, .. } => Some(*index),
+            ConversionError::Input { source, .. } => source.feature_index(),
+            _ => None,
+        }
+    }
+
+    /// A name for the kind of error, such as `unsupported_geometry`, that stays the same
+    /// when its message is reworded. The file or feature it was raised for is looked through.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConversionError::LatitudeOutOfRange { .. } => "latitude_out_of_range",
+            ConversionError::UnsupportedGeometry { .. } => "unsupported_geometry",
+            ConversionError::InvalidGeometry { .. } => "invalid_geometry",
+            ConversionError::UnsupportedPropertyType { .. } => "unsupported_property_type",
+            ConversionError::PropertyTypeMismatch { .. } => "property_type_mismatch",
+            ConversionError::InvalidFieldName { .. } => "invalid_field_name",
+            ConversionError::DuplicateFieldName { .. } => "duplicate_field_name",
+            ConversionError::TooManyFields { .. } => "too_many_fields",
+            ConversionError::RecordTooLong { .. } => "record_too_long",
+            ConversionError::TooManyRecords { .. } => "too_many_records",
+            ConversionError::UnexpectedProperty { .. } => "unexpected_property",
+            ConversionError::UnencodableCharacter { .. } => "unencodable_character",
+            ConversionError::TextTooLong { .. } => "text_too_long",
+            ConversionError::InexactNumber { .. } => "inexact_number",
+            ConversionError::MissingFeatureId { .. } => "missing_feature_id",
+            ConversionError::DuplicateFeatureId { .. } => "duplicate_feature_id",
+            ConversionError::MixedShapeTypes { .. } => "mixed_shape_types",
+            ConversionError::AlreadyWritten => "already_written",
+            ConversionError::Cancelled => "cancelled",
+            ConversionError::MissingGeometry => "missing_geometry",
+            ConversionError::FileSizeLimit { .. } => "file_size_limit",
+            ConversionError::MissingOutputDirectory { .. } => "missing_output_directory",
+            ConversionError::OutputExists { .. } => "output_exists",
+            ConversionError::Parse { .. } => "parse",
+            #[cfg(feature = "topojson")]
+            ConversionError::TopoJson(_) => "topojson",
+            #[cfg(feature = "gpkg")]
+            ConversionError::Sqlite(_) => "sqlite",
+            #[cfg(feature = "http")]
+            ConversionError::Http { .. } => "http",
+            ConversionError::Shapefile(_) => "shapefile",
+            ConversionError::Json(_) => "json",
+            ConversionError::InputNotFound { .. } => "input_not_found",
+            ConversionError::Usage(_) => "usage",
+            ConversionError::Glob(_) => "glob",
+            ConversionError::Input { source, .. } | ConversionError::Feature { source, .. } => {
+                source.kind()
+            }
+            ConversionError::Features(_) => "features",
+            ConversionError::Io(_) => "io",
+        }
+    }
+
+    /// The property the error concerns, if any.
+    pub fn property(&self) -> Option<&str> {
+        match self {
+            ConversionError::UnsupportedPropertyType { property, .. }
+            | ConversionError::PropertyTypeMismatch { property, .. }
+            | ConversionError::UnexpectedProperty { property }
+            | ConversionError::UnencodableCharacter { property, .. }
+            | ConversionError::TextTooLong { property, .. }
+            | ConversionError::InexactNumber { property, .. } => Some(property),
+            ConversionError::MissingFeatureId { field, .. }
+            | ConversionError::DuplicateFeatureId { field, .. } => Some(field),
+            ConversionError::Input { source, .. } | ConversionError::Feature { source, .. } => {
+                source.property()
+            }
+            _ => None,
+        }
+    }
+
+    /// The file the error concerns, if it names one: the input that was read, or the output
+    /// that could not be created.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConversionError::Input { path, .. }
+            | ConversionError::InputNotFound { path }
+            | ConversionError::MissingOutputDirectory { path, .. } => Some(path),
+            ConversionError::Parse { path, .. } => path.as_deref(),
+            ConversionError::Feature { source, .. } => source.path(),
+            _ => None,
+        }
+    }
+
+    /// The error as a JSON object, for `--error-format json`: its `kind`, `message`,
+    /// `feature_index`, `property` and `path`, `null` where they do not apply.
+    pub fn to_json(&self) -> JsonValue {
+        serde_json::json!({
+            "kind": self.kind(),
+            "message": self.to_string(),
+            "feature_index": self.feature_index(),
+            "property": self.property(),
+            "path": self.path().map(|path| path.display().to_string()),
+        })
+    }
+
+    /// The error itself, without the feature it was raised for.
+    pub fn without_context(&self) -> &ConversionError {
+        match self {
+            ConversionError::Feature { source, .. } => source.without_context(),
+            other => other,
+        }
+    }
+}
+
+fn expected_kind(expected: DbfType) -> &'static str {
+    match expected {
+        DbfType::Character => "string",
+        DbfType::Numeric => "number",
+    }
+}
+
+const VALUE_PREVIEW_LEN: usize = 40;
+const PARSE_CONTEXT_LEN: usize = 200;
+
+/// Renders a JSON value for an error message, e.g. `string "n/a"` or `array [1,2]`.
+pub(crate) fn describe_value(value: &JsonValue) -> String {
+    let (kind, rendered) = match value {
+        JsonValue::Null => return "null".to_string(),
+        JsonValue::Bool(flag) => ("boolean", flag.to_string()),
+        JsonValue::Number(number) => ("number", number.to_string()),
+        JsonValue::String(_) => ("string", value.to_string()),
+        JsonValue::Array(_) => ("array", value.to_string()),
+        JsonValue::Object(_) => ("object", value.to_string()),
+    };
+    match rendered.char_indices().nth(VALUE_PREVIEW_LEN) {
+        Some((end, _)) => format!("{} {}…", kind, &rendered[..end]),
+        None => format!("{} {}", kind, rendered),
+    }
+}
+
+fn list_errors(errors: &[FeatureError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  {}", error))
+        .collect()
+}
+
+fn in_file(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!(" in {}", path.display()),
+        None => " passed as a string".to_string(),
+    }
+}
+
+/// Leads with the line and column of a syntax error, which serde_json otherwise appends
+/// to its message.
+fn describe_parse_error(source: &geojson::Error, location: Option<(usize, usize)>) -> String {
+    match (source, location) {
+        (geojson::Error::MalformedJson(err), Some((line, column))) => {
+            let message = err.to_string();
+            let suffix = format!(" at line {} column {}", line, column);
+            format!(
+                "parse error at line {}, column {}: {}",
+                line,
+                column,
+                message.strip_suffix(&suffix).unwrap_or(&message)
+            )
+        }
+        _ => source.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_errors_onto_exit_codes() {
+        let usage = ConversionError::Usage("Only the convert command converts to a shapefile");
+        assert_eq!(usage.exit_code(), ConversionError::EXIT_USAGE);
+        let missing = ConversionError::InputNotFound {
+            path: PathBuf::from("data.geojsn"),
+        };
+        assert_eq!(missing.exit_code(), ConversionError::EXIT_INPUT_NOT_FOUND);
+        let io = ConversionError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(io.exit_code(), ConversionError::EXIT_IO);
+        let geometry = ConversionError::UnsupportedGeometry {
+            geometry_type: "GeometryCollection".to_string(),
+        };
+        assert_eq!(geometry.exit_code(), ConversionError::EXIT_CONVERSION);
+        // Context does not change the class of the error.
+        let in_file = ConversionError::Input {
+            path: PathBuf::from("roads.geojson"),
+            source: Box::new(missing.at_feature(3)),
+        };
+        assert_eq!(in_file.exit_code(), ConversionError::EXIT_INPUT_NOT_FOUND);
+        let schema = ConversionError::TooManyFields {
+            count: 300,
+            limit: 255,
+        };
+        assert_eq!(schema.exit_code(), ConversionError::EXIT_CONVERSION);
+    }
+
+    #[test]
+    fn describes_errors_as_json() {
+        let error = ConversionError::Input {
+            path: PathBuf::from("roads.geojson"),
+            source: Box::new(
+                ConversionError::TextTooLong {
+                    property: "desc".to_string(),
+                    len: 400,
+                    limit: 254,
+                }
+                .at_feature(7),
+            ),
+        };
+        let json = error.to_json();
+        assert_eq!(json["kind"], "text_too_long");
+        assert_eq!(json["message"], error.to_string());
+        assert_eq!(json["feature_index"], 7);
+        assert_eq!(json["property"], "desc");
+        assert_eq!(json["path"], "roads.geojson");
+
+        let json = ConversionError::AlreadyWritten.to_json();
+        assert_eq!(json["kind"], "already_written");
+        assert!(json["feature_index"].is_null());
+        assert!(json["property"].is_null());
+        assert!(json["path"].is_null());
+    }
+
+    #[test]
+    fn renders_feature_and_property_context() {
+        let error = ConversionError::PropertyTypeMismatch {
+            property: "height".to_string(),
+            expected: DbfType::Numeric,
+            value: describe_value(&JsonValue::from("n/a")),
+        }
+        .at_feature(83412);
+        assert_eq!(
+            error.to_string(),
+            r#"feature 83412, property "height": expected number, got string "n/a""#
+        );
+        assert_eq!(error.feature_index(), Some(83412));
+
+        let error = ConversionError::parse(
+            geojson::Error::NotAFeature("Point".to_string()),
+            &format!("{{\"type\": \"Feature\"{}", " ".repeat(300)),
+        )
+        .in_file(Some(Path::new("parcels.geojson")));
+        let message = error.to_string();
+        assert!(message.starts_with("could not parse the GeoJSON in parcels.geojson: "));
+        assert!(message.ends_with(&format!("{{\"type\": \"Feature\"{}…", " ".repeat(182))));
+
+        let contents = "{\n  \"type\": \"FeatureCollection\",\n}";
+        let source = serde_json::from_str::<JsonValue>(contents).unwrap_err();
+        let error = ConversionError::parse(geojson::Error::MalformedJson(source), contents);
+        let message = error.to_string();
+        assert!(message.starts_with(
+            "could not parse the GeoJSON passed as a string: parse error at line 3, column 1: trailing comma\n"
+        ));
+
+        assert_eq!(error.exit_code(), ConversionError::EXIT_PARSE);
+
+        let long = JsonValue::from("x".repeat(100));
+        assert_eq!(describe_value(&long).chars().count(), "string ".len() + 41);
+    }
+}
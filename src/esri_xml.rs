@@ -0,0 +1,114 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use shapefile::ShapeType;
+
+use crate::{DbfType, FieldSchema};
+
+/// Renders a minimal FGDC/ESRI `.shp.xml` metadata document.
+pub(crate) fn esri_metadata_xml(
+    name: &str,
+    shape_type: ShapeType,
+    feature_count: usize,
+    schema: &FieldSchema,
+    generated_at: SystemTime,
+) -> String {
+    let (date, time) = timestamp(generated_at);
+    let name = escape(name);
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<metadata xml:lang=\"en\">\n");
+    xml.push_str(&format!(
+        "  <Esri>\n    <CreaDate>{}</CreaDate>\n    <CreaTime>{}</CreaTime>\n    <ArcGISFormat>1.0</ArcGISFormat>\n  </Esri>\n",
+        date, time
+    ));
+    xml.push_str(&format!(
+        "  <idinfo>\n    <citation>\n      <citeinfo>\n        <title>{}</title>\n        <pubdate>{}</pubdate>\n      </citeinfo>\n    </citation>\n  </idinfo>\n",
+        name, date
+    ));
+    xml.push_str(&format!(
+        "  <spdoinfo>\n    <direct>Vector</direct>\n    <ptvctinf>\n      <esriterm Name=\"{}\">\n        <efeatyp>Simple</efeatyp>\n        <efeageom>{}</efeageom>\n        <efeacnt>{}</efeacnt>\n      </esriterm>\n    </ptvctinf>\n  </spdoinfo>\n",
+        name, shape_type, feature_count
+    ));
+    xml.push_str(&format!(
+        "  <eainfo>\n    <detailed>\n      <enttyp>\n        <enttypl>{}</enttypl>\n        <enttypc>{}</enttypc>\n      </enttyp>\n",
+        name, feature_count
+    ));
+    for field in schema.fields.iter() {
+        let attribute_type = match field.field_type {
+            DbfType::Character => "String",
+            DbfType::Numeric => "Double",
+        };
+        xml.push_str(&format!(
+            "      <attr>\n        <attrlabl>{}</attrlabl>\n        <attalias>{}</attalias>\n        <attrtype>{}</attrtype>\n        <attwidth>{}</attwidth>\n        <atnumdec>{}</atnumdec>\n      </attr>\n",
+            escape(&field.name),
+            escape(&field.property),
+            attribute_type,
+            field.width,
+            field.decimals
+        ));
+    }
+    xml.push_str("    </detailed>\n  </eainfo>\n</metadata>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The UTC date (`YYYYMMDD`) and time (`HHMMSS00`) the ESRI metadata format expects.
+fn timestamp(time: SystemTime) -> (String, String) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    (
+        format!("{:04}{:02}{:02}", year, month, day),
+        format!(
+            "{:02}{:02}{:02}00",
+            seconds_of_day / 3600,
+            seconds_of_day % 3600 / 60,
+            seconds_of_day % 60
+        ),
+    )
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn renders_fields_and_timestamp() {
+        let schema = FieldSchema::placeholder();
+        let generated_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let xml = esri_metadata_xml("parcels", ShapeType::Point, 3, &schema, generated_at);
+        assert!(xml.contains("<CreaDate>20231114</CreaDate>"));
+        assert!(xml.contains("<CreaTime>22132000</CreaTime>"));
+        assert!(xml.contains("<efeacnt>3</efeacnt>"));
+        assert!(xml.contains("<attrlabl>FID</attrlabl>"));
+    }
+}
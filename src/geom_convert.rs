@@ -0,0 +1,771 @@
+//! Conversion of GeoJSON geometries into shapefile shapes.
+//!
+//! Every function here is pure: it reads the coordinates and the coordinate options
+//! (`normalize_coordinates`, `coordinate_precision` and `simplify`) and returns the shape,
+//! without touching a writer or a summary.
+
+use geo::sweep::{Cross, Intersections, LineOrPoint};
+use geo::LineIntersection;
+use geojson::Value;
+use shapefile::{Multipoint, Point, Polygon, PolygonRing, Polyline, Shape, ShapeType};
+
+use crate::simplify::simplify_points;
+use crate::{ConversionError, ConversionOptions};
+
+/// A coordinate value of a position: `f64` as in GeoJSON, or a narrower type such as `f32`
+/// that converts into it. Shapefiles store `f64`, so the shapes are `f64` either way.
+pub trait Coordinate: Copy + Into<f64> {}
+
+impl<T: Copy + Into<f64>> Coordinate for T {}
+
+/// Converts any geometry but a GeometryCollection, which has no shapefile counterpart.
+pub fn geometry_to_shp(
+    value: &Value,
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    match value {
+        Value::Point(position) => point_to_shp(position, options),
+        Value::MultiPoint(positions) => multipoint_to_shp(positions, options),
+        Value::LineString(line) => linestring_to_shp(line, options),
+        Value::MultiLineString(lines) => multilinestring_to_shp(lines, options),
+        Value::Polygon(rings) => polygon_to_shp(rings, options),
+        Value::MultiPolygon(polygons) => multipolygon_to_shp(polygons, options),
+        other => Err(ConversionError::UnsupportedGeometry {
+            geometry_type: geometry_type_name(other).to_string(),
+        }),
+    }
+}
+
+/// Converts a Point into a `Shape::Point`.
+pub fn point_to_shp<T: Coordinate>(
+    position: &[T],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    let (x, y) = position_to_xy(position, options)?;
+    Ok(Shape::Point(Point::new(x, y)))
+}
+
+/// Converts a MultiPoint into a `Shape::Multipoint`.
+pub fn multipoint_to_shp<T: Coordinate, P: AsRef<[T]>>(
+    positions: &[P],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    if positions.is_empty() {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a MultiPoint needs at least one position",
+        });
+    }
+    let points = positions
+        .iter()
+        .map(|position| position_to_xy(position.as_ref(), options).map(|(x, y)| Point::new(x, y)))
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+    Ok(Shape::Multipoint(Multipoint::new(points)))
+}
+
+/// Converts a LineString into a single-part `Shape::Polyline`.
+pub fn linestring_to_shp<T: Coordinate, P: AsRef<[T]>>(
+    line: &[P],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    let part = line_points(line, options)?;
+    Ok(Shape::Polyline(Polyline::new(part)))
+}
+
+/// Converts a MultiLineString into a `Shape::Polyline` with one part per LineString.
+pub fn multilinestring_to_shp<T: Coordinate, P: AsRef<[T]>, L: AsRef<[P]>>(
+    lines: &[L],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    if lines.is_empty() {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a MultiLineString needs at least one LineString",
+        });
+    }
+    let parts = lines
+        .iter()
+        .map(|line| line_points(line.as_ref(), options))
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+    Ok(Shape::Polyline(Polyline::with_parts(parts)))
+}
+
+/// Converts a Polygon into a `Shape::Polygon`, rewinding its rings for the shapefile.
+pub fn polygon_to_shp<T: Coordinate, P: AsRef<[T]>, R: AsRef<[P]>>(
+    rings: &[R],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    let rings = polygon_rings(rings, options)?;
+    Ok(Shape::Polygon(Polygon::with_rings(rings)))
+}
+
+/// Converts a MultiPolygon into a single `Shape::Polygon` holding the rings of every Polygon,
+/// as a shapefile polygon may have several exterior rings.
+pub fn multipolygon_to_shp<T: Coordinate, P: AsRef<[T]>, R: AsRef<[P]>, G: AsRef<[R]>>(
+    polygons: &[G],
+    options: &ConversionOptions,
+) -> Result<Shape, ConversionError> {
+    if polygons.is_empty() {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a MultiPolygon needs at least one Polygon",
+        });
+    }
+    let mut rings = Vec::new();
+    for polygon in polygons {
+        rings.extend(polygon_rings(polygon.as_ref(), options)?);
+    }
+    Ok(Shape::Polygon(Polygon::with_rings(rings)))
+}
+
+fn line_points<T: Coordinate, P: AsRef<[T]>>(
+    line: &[P],
+    options: &ConversionOptions,
+) -> Result<Vec<Point>, ConversionError> {
+    let points = line
+        .iter()
+        .map(|position| position_to_xy(position.as_ref(), options).map(|(x, y)| Point::new(x, y)))
+        .collect::<Result<Vec<Point>, ConversionError>>()?;
+    if points.len() < 2 {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a LineString needs at least two positions",
+        });
+    }
+    let points = simplify(points, options, 2);
+    if options.coordinate_precision.is_none() {
+        return Ok(points);
+    }
+    let points = without_repeated_points(points);
+    if points.len() < 2 {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "rounding to the coordinate precision collapses a LineString into a point",
+        });
+    }
+    Ok(points)
+}
+
+fn polygon_rings<T: Coordinate, P: AsRef<[T]>, R: AsRef<[P]>>(
+    rings: &[R],
+    options: &ConversionOptions,
+) -> Result<Vec<PolygonRing<Point>>, ConversionError> {
+    if rings.is_empty() {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a Polygon needs an exterior ring",
+        });
+    }
+    if rings.iter().any(|ring| ring.as_ref().len() < 3) {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "a Polygon ring needs at least three positions",
+        });
+    }
+    // The first ring is the exterior, the others are holes; with_rings closes the
+    // rings and orders their points the way shapefiles expect.
+    let rings = rings
+        .iter()
+        .enumerate()
+        .map(|(index, ring)| {
+            let ring = ring
+                .as_ref()
+                .iter()
+                .map(|position| {
+                    position_to_xy(position.as_ref(), options).map(|(x, y)| Point::new(x, y))
+                })
+                .collect::<Result<Vec<Point>, ConversionError>>()?;
+            // A closed ring needs its three corners and the closing point.
+            let mut points = simplify(ring, options, 4);
+            if options.coordinate_precision.is_some() {
+                points = without_repeated_points(points);
+                if points.len() < 4 || ring_area_signed(&points) == 0.0 {
+                    return Err(ConversionError::InvalidGeometry {
+                        reason: "rounding to the coordinate precision collapses a Polygon ring",
+                    });
+                }
+            }
+            Ok(match index {
+                0 => PolygonRing::Outer(points),
+                _ => PolygonRing::Inner(points),
+            })
+        })
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+    if options.coordinate_precision.is_some() && rings_cross(&rings) {
+        return Err(ConversionError::InvalidGeometry {
+            reason: "rounding to the coordinate precision makes the rings of a Polygon cross",
+        });
+    }
+    Ok(rings)
+}
+
+/// `points` without the vertices that repeat the one before, as rounding leaves them where
+/// vertices were closer together than the precision.
+fn without_repeated_points(mut points: Vec<Point>) -> Vec<Point> {
+    points.dedup();
+    points
+}
+
+/// A side of a ring, for the sweep in `rings_cross`.
+#[derive(Debug, Clone)]
+struct RingSegment {
+    ring: usize,
+    index: usize,
+    line: geo_types::Line,
+}
+
+impl Cross for RingSegment {
+    type Scalar = f64;
+
+    fn line(&self) -> LineOrPoint<f64> {
+        self.line.into()
+    }
+}
+
+/// Whether a ring crosses itself or another ring of the polygon, or runs along a side of
+/// either. Rings may only meet at single points: neighbouring sides of a ring where they
+/// join, and different rings where they touch.
+fn rings_cross(rings: &[PolygonRing<Point>]) -> bool {
+    let segments = rings.iter().enumerate().flat_map(|(ring, points)| {
+        points
+            .points()
+            .windows(2)
+            .enumerate()
+            .map(move |(index, pair)| RingSegment {
+                ring,
+                index,
+                line: geo_types::Line::new((pair[0].x, pair[0].y), (pair[1].x, pair[1].y)),
+            })
+    });
+    Intersections::from_iter(segments).any(|(a, b, intersection)| {
+        let is_proper = match intersection {
+            LineIntersection::SinglePoint { is_proper, .. } => is_proper,
+            LineIntersection::Collinear { .. } => return true,
+        };
+        if is_proper || a.ring != b.ring {
+            return is_proper;
+        }
+        // Sides of the same ring may only meet where they join.
+        let sides = rings[a.ring].points().len() - 1;
+        let (first, last) = (a.index.min(b.index), a.index.max(b.index));
+        !(last == first + 1 || (first == 0 && last == sides - 1))
+    })
+}
+
+/// The shape type `geometry_to_shp` converts a geometry into, without converting it.
+pub(crate) fn shape_type_of(value: &Value) -> Result<ShapeType, ConversionError> {
+    match value {
+        Value::Point(_) => Ok(ShapeType::Point),
+        Value::MultiPoint(_) => Ok(ShapeType::Multipoint),
+        Value::LineString(_) | Value::MultiLineString(_) => Ok(ShapeType::Polyline),
+        Value::Polygon(_) | Value::MultiPolygon(_) => Ok(ShapeType::Polygon),
+        other => Err(ConversionError::UnsupportedGeometry {
+            geometry_type: geometry_type_name(other).to_string(),
+        }),
+    }
+}
+
+/// The rings of `polygon` grouped into polygons: each exterior ring followed by its holes.
+pub(crate) fn polygon_parts(polygon: &Polygon) -> Vec<Vec<&[Point]>> {
+    let mut polygons: Vec<Vec<&[Point]>> = Vec::new();
+    for ring in polygon.rings() {
+        match (ring, polygons.last_mut()) {
+            (PolygonRing::Inner(points), Some(rings)) => rings.push(points),
+            _ => polygons.push(vec![ring.points()]),
+        }
+    }
+    polygons
+}
+
+/// The vertices of the shapes `geometry_to_shp` writes, none for a Null Shape.
+fn shape_points(shape: &Shape) -> Vec<&Point> {
+    match shape {
+        Shape::Point(point) => vec![point],
+        Shape::Multipoint(multipoint) => multipoint.points().iter().collect(),
+        Shape::Polyline(line) => line.parts().iter().flatten().collect(),
+        Shape::Polygon(polygon) => polygon
+            .rings()
+            .iter()
+            .flat_map(|ring| ring.points())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The `[min_x, min_y, max_x, max_y]` of the shape's vertices, `None` for a Null Shape.
+pub(crate) fn shape_bounds(shape: &Shape) -> Option<[f64; 4]> {
+    shape_points(shape).iter().fold(None, |bounds, point| {
+        let [min_x, min_y, max_x, max_y] = bounds.unwrap_or([point.x, point.y, point.x, point.y]);
+        Some([
+            min_x.min(point.x),
+            min_y.min(point.y),
+            max_x.max(point.x),
+            max_y.max(point.y),
+        ])
+    })
+}
+
+/// The mean of the shape's vertices, `None` for a Null Shape.
+pub(crate) fn shape_centroid(shape: &Shape) -> Option<[f64; 2]> {
+    let points = shape_points(shape);
+    if points.is_empty() {
+        return None;
+    }
+    let count = points.len() as f64;
+    let (x, y) = points
+        .iter()
+        .fold((0.0, 0.0), |(x, y), point| (x + point.x, y + point.y));
+    Some([x / count, y / count])
+}
+
+/// Applies the Douglas-Peucker `simplify` tolerance of the options, keeping the original
+/// line when simplifying would leave fewer than `min_points`.
+fn simplify(line: Vec<Point>, options: &ConversionOptions, min_points: usize) -> Vec<Point> {
+    match options.simplify {
+        Some(tolerance) => {
+            let simplified = simplify_points(&line, tolerance);
+            if simplified.len() >= min_points {
+                simplified
+            } else {
+                line
+            }
+        }
+        None => line,
+    }
+}
+
+/// Signed area of a ring (shoelace formula): positive when its points run
+/// counter-clockwise, negative when they run clockwise.
+pub fn ring_area_signed(points: &[Point]) -> f64 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Number of values GeoJSON defines for a position: longitude, latitude and elevation.
+const POSITION_LEN: usize = 3;
+
+/// The most values past longitude, latitude and elevation held by any position of `value`.
+pub(crate) fn extra_coordinate_values(value: &Value) -> usize {
+    let extra = |position: &Vec<f64>| position.len().saturating_sub(POSITION_LEN);
+    match value {
+        Value::Point(position) => extra(position),
+        Value::MultiPoint(positions) | Value::LineString(positions) => {
+            positions.iter().map(extra).max().unwrap_or(0)
+        }
+        Value::MultiLineString(lines) | Value::Polygon(lines) => {
+            lines.iter().flatten().map(extra).max().unwrap_or(0)
+        }
+        Value::MultiPolygon(polygons) => polygons
+            .iter()
+            .flatten()
+            .flatten()
+            .map(extra)
+            .max()
+            .unwrap_or(0),
+        Value::GeometryCollection(geometries) => geometries
+            .iter()
+            .map(|geometry| extra_coordinate_values(&geometry.value))
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+pub(crate) fn geometry_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Point(_) => "Point",
+        Value::MultiPoint(_) => "MultiPoint",
+        Value::LineString(_) => "LineString",
+        Value::MultiLineString(_) => "MultiLineString",
+        Value::Polygon(_) => "Polygon",
+        Value::MultiPolygon(_) => "MultiPolygon",
+        Value::GeometryCollection(_) => "GeometryCollection",
+    }
+}
+
+/// The x and y of `position`, swapped under `lat_lon_order`. Shapes are always written in
+/// 2D, so an elevation (and any value past it) is dropped.
+fn position_to_xy<T: Coordinate>(
+    position: &[T],
+    options: &ConversionOptions,
+) -> Result<(f64, f64), ConversionError> {
+    let (x, y): (f64, f64) = match position {
+        [lat, lon, ..] if options.lat_lon_order => ((*lon).into(), (*lat).into()),
+        [x, y, ..] => ((*x).into(), (*y).into()),
+        _ => {
+            return Err(ConversionError::InvalidGeometry {
+                reason: "a position needs at least two coordinates",
+            })
+        }
+    };
+    let (x, y) = if options.normalize_coordinates {
+        normalize_lon_lat(x, y)?
+    } else {
+        (x, y)
+    };
+    match options.coordinate_precision {
+        Some(decimals) => Ok((round_to(x, decimals), round_to(y, decimals))),
+        None => Ok((x, y)),
+    }
+}
+
+fn round_to(value: f64, decimals: u8) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Wraps the longitude into [-180, 180], erroring when the latitude is outside of [-90, 90].
+fn normalize_lon_lat(lon: f64, lat: f64) -> Result<(f64, f64), ConversionError> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(ConversionError::LatitudeOutOfRange { latitude: lat });
+    }
+    let lon = if (-180.0..=180.0).contains(&lon) {
+        lon
+    } else {
+        (lon + 180.0).rem_euclid(360.0) - 180.0
+    };
+    Ok((lon, lat))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_each_geometry_type() {
+        let options = ConversionOptions::default();
+        assert!(matches!(
+            point_to_shp(&[1.0, 2.0], &options).unwrap(),
+            Shape::Point(point) if point == Point::new(1.0, 2.0)
+        ));
+        match multipoint_to_shp(&[vec![1.0, 2.0], vec![3.0, 4.0]], &options).unwrap() {
+            Shape::Multipoint(multipoint) => {
+                assert_eq!(
+                    multipoint.points(),
+                    [Point::new(1.0, 2.0), Point::new(3.0, 4.0)]
+                )
+            }
+            other => panic!("expected a multipoint, got {}", other),
+        }
+        let lines = [
+            vec![vec![0.0, 0.0], vec![1.0, 1.0]],
+            vec![vec![2.0, 2.0], vec![3.0, 3.0], vec![4.0, 2.0]],
+        ];
+        match multilinestring_to_shp(&lines, &options).unwrap() {
+            Shape::Polyline(line) => {
+                assert_eq!(line.parts().len(), 2);
+                assert_eq!(line.parts()[1].len(), 3);
+            }
+            other => panic!("expected a polyline, got {}", other),
+        }
+        let square = |min: f64, max: f64| {
+            vec![
+                vec![min, min],
+                vec![max, min],
+                vec![max, max],
+                vec![min, max],
+                vec![min, min],
+            ]
+        };
+        let polygons = [vec![square(0.0, 1.0)], vec![square(2.0, 3.0)]];
+        match multipolygon_to_shp(&polygons, &options).unwrap() {
+            Shape::Polygon(polygon) => {
+                let rings = polygon.rings();
+                assert_eq!(rings.len(), 2);
+                assert!(rings
+                    .iter()
+                    .all(|ring| matches!(ring, PolygonRing::Outer(_))
+                        && ring_area_signed(ring.points()) < 0.0));
+            }
+            other => panic!("expected a polygon, got {}", other),
+        }
+
+        assert_eq!(
+            shape_type_of(&Value::MultiLineString(lines.to_vec())).unwrap(),
+            ShapeType::Polyline
+        );
+        assert!(matches!(
+            multipoint_to_shp::<f64, Vec<f64>>(&[], &options),
+            Err(ConversionError::InvalidGeometry { .. })
+        ));
+        assert!(matches!(
+            multilinestring_to_shp(&[vec![vec![0.0, 0.0]]], &options),
+            Err(ConversionError::InvalidGeometry { .. })
+        ));
+        assert!(matches!(
+            geometry_to_shp(&Value::GeometryCollection(Vec::new()), &options),
+            Err(ConversionError::UnsupportedGeometry { .. })
+        ));
+    }
+
+    #[test]
+    fn converts_f32_coordinates() {
+        let options = ConversionOptions::default().coordinate_precision(3);
+        assert!(matches!(
+            point_to_shp(&[1.5f32, 2.25], &options).unwrap(),
+            Shape::Point(point) if point == Point::new(1.5, 2.25)
+        ));
+        match linestring_to_shp(&[[0.1f32, 0.2], [1.0, 1.0]], &options).unwrap() {
+            Shape::Polyline(line) => {
+                assert_eq!(
+                    line.parts()[0],
+                    [Point::new(0.1, 0.2), Point::new(1.0, 1.0)]
+                )
+            }
+            other => panic!("expected a polyline, got {}", other),
+        }
+        let ring: [[f32; 2]; 5] = [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+        match multipolygon_to_shp(&[[ring]], &options).unwrap() {
+            Shape::Polygon(polygon) => {
+                assert_eq!(polygon.rings().len(), 1);
+                assert_eq!(polygon.rings()[0].points().len(), 5);
+            }
+            other => panic!("expected a polygon, got {}", other),
+        }
+    }
+
+    #[test]
+    fn winds_exteriors_clockwise_and_holes_counter_clockwise() {
+        let square = |min: f64, max: f64| {
+            vec![
+                vec![min, min],
+                vec![max, min],
+                vec![max, max],
+                vec![min, max],
+                vec![min, min],
+            ]
+        };
+        let mut hole = square(1.0, 2.0);
+        hole.reverse();
+        // Right-hand rule: a counter-clockwise exterior and a clockwise hole.
+        let polygon = Value::Polygon(vec![square(0.0, 3.0), hole]);
+        assert!(
+            ring_area_signed(&[
+                Point::new(0.0, 0.0),
+                Point::new(1.0, 0.0),
+                Point::new(0.0, 1.0)
+            ]) > 0.0
+        );
+
+        let options = ConversionOptions::default();
+        match geometry_to_shp(&polygon, &options).unwrap() {
+            Shape::Polygon(polygon) => {
+                let rings = polygon.rings();
+                assert!(ring_area_signed(rings[0].points()) < 0.0);
+                assert!(ring_area_signed(rings[1].points()) > 0.0);
+                assert_eq!(rings[0].points()[1], Point::new(0.0, 3.0));
+            }
+            other => panic!("expected a polygon, got {}", other),
+        }
+    }
+
+    #[test]
+    fn simplifies_dense_lines_within_tolerance() {
+        use geo::EuclideanDistance;
+
+        let dense: Vec<Vec<f64>> = (0..=100)
+            .map(|i| {
+                let x = i as f64 / 10.0;
+                vec![x, (x * 3.0).sin() * 0.001]
+            })
+            .collect();
+        let options = ConversionOptions::default().simplify(0.01);
+        let geometry = Value::LineString(dense.clone());
+        let simplified = match geometry_to_shp(&geometry, &options).unwrap() {
+            Shape::Polyline(line) => geo_types::LineString::from(
+                line.parts()[0]
+                    .iter()
+                    .map(|point| (point.x, point.y))
+                    .collect::<Vec<_>>(),
+            ),
+            other => panic!("expected a polyline, got {}", other),
+        };
+        assert!(simplified.0.len() < dense.len() / 10);
+        for position in dense.iter() {
+            let point = geo_types::Point::new(position[0], position[1]);
+            assert!(point.euclidean_distance(&simplified) <= 0.01);
+        }
+
+        let point = Value::Point(vec![1.0, 2.0]);
+        assert!(matches!(
+            geometry_to_shp(&point, &options).unwrap(),
+            Shape::Point(point) if point == Point::new(1.0, 2.0)
+        ));
+    }
+
+    #[test]
+    fn counts_values_past_elevation() {
+        let line = Value::LineString(vec![vec![1.0, 2.0], vec![1.0, 2.0, 3.0, 4.0, 5.0]]);
+        assert_eq!(extra_coordinate_values(&line), 2);
+        assert_eq!(
+            extra_coordinate_values(&Value::Point(vec![1.0, 2.0, 3.0])),
+            0
+        );
+
+        let options = ConversionOptions::default();
+        let shape = geometry_to_shp(&line, &options).unwrap();
+        assert!(matches!(shape, Shape::Polyline(line) if line.parts()[0].len() == 2));
+    }
+
+    #[test]
+    fn mixes_positions_with_and_without_elevation() {
+        let options = ConversionOptions::default();
+        let ring = vec![
+            vec![0.0, 0.0],
+            vec![0.0, 1.0, 10.0],
+            vec![1.0, 1.0],
+            vec![0.0, 0.0, 10.0],
+        ];
+        let geometries = [
+            Value::Point(vec![1.0, 2.0]),
+            Value::Point(vec![1.0, 2.0, 3.0]),
+            Value::MultiPoint(vec![vec![1.0, 2.0], vec![3.0, 4.0, 5.0]]),
+            Value::LineString(vec![vec![1.0, 2.0, 3.0], vec![3.0, 4.0]]),
+            Value::MultiLineString(vec![vec![vec![1.0, 2.0], vec![3.0, 4.0, 5.0]]]),
+            Value::Polygon(vec![ring.clone()]),
+            Value::MultiPolygon(vec![vec![ring]]),
+        ];
+        for geometry in geometries.iter() {
+            assert!(
+                geometry_to_shp(geometry, &options).is_ok(),
+                "{:?}",
+                geometry
+            );
+        }
+
+        // A position short of a y errors rather than panicking.
+        let short = Value::LineString(vec![vec![1.0, 2.0], vec![3.0]]);
+        assert!(matches!(
+            geometry_to_shp(&short, &options),
+            Err(ConversionError::InvalidGeometry { .. })
+        ));
+        assert!(geometry_to_shp(&Value::Point(vec![]), &options).is_err());
+    }
+
+    #[test]
+    fn normalizes_out_of_range_longitudes() {
+        let options = ConversionOptions {
+            normalize_coordinates: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            position_to_xy(&[200.0, 10.0], &options).unwrap(),
+            (-160.0, 10.0)
+        );
+        assert_eq!(
+            position_to_xy(&[-190.0, 10.0], &options).unwrap(),
+            (170.0, 10.0)
+        );
+        assert_eq!(
+            position_to_xy(&[180.0, 10.0], &options).unwrap(),
+            (180.0, 10.0)
+        );
+        assert!(matches!(
+            position_to_xy(&[0.0, 91.0], &options),
+            Err(ConversionError::LatitudeOutOfRange { .. })
+        ));
+
+        let options = ConversionOptions::default();
+        assert_eq!(
+            position_to_xy(&[200.0, 10.0], &options).unwrap(),
+            (200.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn reads_positions_in_lat_lon_order() {
+        let options = ConversionOptions::default().lat_lon_order(true);
+        let zurich = point_to_shp(&[47.37, 8.54], &options).unwrap();
+        assert!(matches!(zurich, Shape::Point(point) if point == Point::new(8.54, 47.37)));
+        let line = Value::LineString(vec![vec![47.37, 8.54, 408.0], vec![46.95, 7.45]]);
+        match geometry_to_shp(&line, &options).unwrap() {
+            Shape::Polyline(line) => {
+                assert_eq!(
+                    line.parts()[0],
+                    [Point::new(8.54, 47.37), Point::new(7.45, 46.95)]
+                )
+            }
+            other => panic!("expected a polyline, got {}", other),
+        }
+
+        let options = ConversionOptions {
+            normalize_coordinates: true,
+            ..options
+        };
+        assert!(position_to_xy(&[47.37, 188.54], &options).is_ok());
+        assert!(position_to_xy(&[188.54, 47.37], &options).is_err());
+    }
+
+    #[test]
+    fn rounds_coordinates_to_the_requested_precision() {
+        let options = ConversionOptions::default().coordinate_precision(4);
+        let geometry = Value::LineString(vec![
+            vec![47.13026404380798, 9.53280758153806],
+            vec![-47.13080048561096, 9.528776318322342],
+        ]);
+        let shape = geometry_to_shp(&geometry, &options).unwrap();
+        let points = match shape {
+            Shape::Polyline(line) => line.parts()[0].clone(),
+            _ => panic!("expected a polyline"),
+        };
+        assert_eq!((points[0].x, points[0].y), (47.1303, 9.5328));
+        assert_eq!((points[1].x, points[1].y), (-47.1308, 9.5288));
+    }
+
+    #[test]
+    fn checks_polygons_are_still_valid_after_rounding() {
+        let options = ConversionOptions::default().coordinate_precision(0);
+        let square = |min: f64, max: f64| {
+            vec![
+                vec![min, min],
+                vec![max, min],
+                vec![max, max],
+                vec![min, max],
+                vec![min, min],
+            ]
+        };
+        // Vertices that round onto each other are merged.
+        let mut exterior = square(0.0, 10.0);
+        exterior.insert(1, vec![0.2, 0.1]);
+        let hole = square(2.2, 7.9);
+        match geometry_to_shp(&Value::Polygon(vec![exterior, hole.clone()]), &options).unwrap() {
+            Shape::Polygon(polygon) => {
+                assert_eq!(polygon.rings()[0].points().len(), 5);
+                assert_eq!(polygon.rings()[1].points()[0], Point::new(2.0, 2.0));
+            }
+            _ => panic!("expected a polygon"),
+        }
+
+        let invalid = [
+            // A sliver that rounds onto a line.
+            Value::Polygon(vec![vec![
+                vec![0.0, 0.0],
+                vec![10.0, 0.1],
+                vec![20.0, 0.2],
+                vec![0.0, 0.0],
+            ]]),
+            // A hole that rounds onto the side of the exterior.
+            Value::Polygon(vec![square(0.0, 10.0), square(0.4, 5.0)]),
+            // A ring whose narrow waist rounds into a crossing.
+            Value::Polygon(vec![vec![
+                vec![0.0, 0.0],
+                vec![4.6, 4.0],
+                vec![0.0, 8.0],
+                vec![10.0, 8.0],
+                vec![5.4, 4.0],
+                vec![10.0, 0.0],
+                vec![0.0, 0.0],
+            ]]),
+            Value::LineString(vec![vec![0.1, 0.1], vec![0.2, 0.2]]),
+        ];
+        for geometry in invalid.iter() {
+            assert!(
+                matches!(
+                    geometry_to_shp(geometry, &options),
+                    Err(ConversionError::InvalidGeometry { .. })
+                ),
+                "{:?}",
+                geometry
+            );
+        }
+        // Without rounding, the same geometries convert.
+        for geometry in invalid.iter() {
+            assert!(geometry_to_shp(geometry, &ConversionOptions::default()).is_ok());
+        }
+    }
+}
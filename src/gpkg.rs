@@ -0,0 +1,546 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use geojson::FeatureCollection;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection};
+use serde_json::{Map, Value as JsonValue};
+use shapefile::{Point, Shape};
+
+use crate::convert::feature_attributes;
+use crate::geom_convert::{geometry_to_shp, polygon_parts};
+use crate::output::{ensure_directory, OutputFiles};
+use crate::text::prepare_text_values;
+use crate::{
+    apply_filter, parse_feature_collection, ConversionError, ConversionOptions, ConversionSummary,
+    ErrorPolicy, NullGeometryBehavior, SkippedFeature,
+};
+
+/// The `application_id` of a GeoPackage: "GPKG" in ASCII.
+const APPLICATION_ID: i32 = 0x4750_4B47;
+/// The `user_version` of a GeoPackage 1.3.
+const USER_VERSION: i32 = 10300;
+/// EPSG code of WGS 84, the coordinate reference system of GeoJSON.
+const WGS84: i32 = 4326;
+const WGS84_WKT: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]"#;
+
+/// The tables every GeoPackage holds, with the spatial reference systems it must define.
+const GPKG_TABLES: &str = "
+    CREATE TABLE gpkg_spatial_ref_sys (
+        srs_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL PRIMARY KEY,
+        organization TEXT NOT NULL,
+        organization_coordsys_id INTEGER NOT NULL,
+        definition TEXT NOT NULL,
+        description TEXT
+    );
+    CREATE TABLE gpkg_contents (
+        table_name TEXT NOT NULL PRIMARY KEY,
+        data_type TEXT NOT NULL,
+        identifier TEXT UNIQUE,
+        description TEXT DEFAULT '',
+        last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        min_x DOUBLE,
+        min_y DOUBLE,
+        max_x DOUBLE,
+        max_y DOUBLE,
+        srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+    );
+    CREATE TABLE gpkg_geometry_columns (
+        table_name TEXT NOT NULL REFERENCES gpkg_contents(table_name),
+        column_name TEXT NOT NULL,
+        geometry_type_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL REFERENCES gpkg_spatial_ref_sys(srs_id),
+        z TINYINT NOT NULL,
+        m TINYINT NOT NULL,
+        PRIMARY KEY (table_name, column_name)
+    );
+    INSERT INTO gpkg_spatial_ref_sys VALUES
+        ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+        ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system');
+";
+
+/// Writes a FeatureCollection into a GeoPackage, an SQLite database, instead of a shapefile.
+///
+/// The features go into a single table named after the file, with a column per property
+/// under its own name: unlike a dbf, a GeoPackage stores text as UTF-8 and has no limit on
+/// name lengths or text values. Geometries are converted as for a shapefile, then written
+/// as GeoPackage binary in WGS 84.
+pub struct FeatureCollectionToGpkgWriter {
+    feature_collection: FeatureCollection,
+    options: ConversionOptions,
+    table: String,
+    summary: ConversionSummary,
+    /// Set by `write()`, as writing the features again would fail on the existing table.
+    written: bool,
+    connection: Connection,
+    /// Declared last so that it is dropped after the connection has closed the file.
+    outputs: OutputFiles,
+}
+
+impl FeatureCollectionToGpkgWriter {
+    /// Creates `{filepath}.gpkg` for `contents`, which is read as by
+    /// `FeatureCollectionToShpWriter::new`.
+    pub fn new(contents: String, filepath: &str) -> Result<Self, ConversionError> {
+        Self::with_options(contents, filepath, ConversionOptions::default())
+    }
+
+    /// Same as `new`. Of the shapefile options, the ones that shape the dbf (field widths
+    /// and types, long text, encoding) do not apply.
+    pub fn with_options(
+        contents: String,
+        filepath: &str,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let mut feature_collection = parse_feature_collection(&contents)?;
+        let features_filtered = apply_filter(&mut feature_collection, &options);
+        let path = format!("{}.gpkg", filepath);
+        let mut outputs = OutputFiles::new(options.overwrite);
+        outputs.check(std::slice::from_ref(&path))?;
+        ensure_directory(Path::new(&path), options.create_dirs)?;
+        let connection = Connection::open(outputs.reserve(path)?)?;
+        let table = Path::new(filepath)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self {
+            feature_collection,
+            options,
+            table,
+            summary: ConversionSummary {
+                features_filtered,
+                ..ConversionSummary::default()
+            },
+            written: false,
+            connection,
+            outputs,
+        })
+    }
+
+    pub fn summary(&self) -> &ConversionSummary {
+        &self.summary
+    }
+
+    /// Writes every feature, stopping at the first one that cannot be converted unless
+    /// `ErrorPolicy::Skip` is set. Like `FeatureCollectionToShpWriter::write`, it can only be
+    /// called once, and removes the GeoPackage on failure unless `keep_partial` is set.
+    pub fn write(&mut self) -> Result<(), ConversionError> {
+        if self.written {
+            return Err(ConversionError::AlreadyWritten);
+        }
+        self.written = true;
+        let result = self.write_features();
+        if result.is_err() && !self.options.keep_partial {
+            self.outputs.discard();
+        }
+        result
+    }
+
+    /// Closes the GeoPackage and moves it into place, returning the summary of the features
+    /// written. Dropping the writer without calling `finish()` removes the GeoPackage instead.
+    pub fn finish(self) -> Result<ConversionSummary, ConversionError> {
+        let Self {
+            connection,
+            mut summary,
+            mut outputs,
+            ..
+        } = self;
+        if let Err((_, err)) = connection.close() {
+            outputs.discard();
+            return Err(err.into());
+        }
+        summary.files = outputs.commit()?;
+        Ok(summary)
+    }
+
+    fn write_features(&mut self) -> Result<(), ConversionError> {
+        let attributes: Vec<Map<String, JsonValue>> = self
+            .feature_collection
+            .features
+            .iter()
+            .map(|feature| {
+                let mut attributes = feature_attributes(feature, &self.options, &mut self.summary);
+                self.summary.transliterated_values +=
+                    prepare_text_values(&mut attributes, &self.options);
+                attributes
+            })
+            .collect();
+        let columns = columns(&attributes);
+
+        self.connection.execute_batch(&format!(
+            "PRAGMA application_id = {}; PRAGMA user_version = {};",
+            APPLICATION_ID, USER_VERSION
+        ))?;
+        let transaction = self.connection.transaction()?;
+        transaction.execute_batch(GPKG_TABLES)?;
+        transaction.execute(
+            "INSERT INTO gpkg_spatial_ref_sys VALUES ('WGS 84 geodetic', ?1, 'EPSG', ?1, ?2, 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid')",
+            params![WGS84, WGS84_WKT],
+        )?;
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column_type)| format!(", {} {}", quote(name), column_type))
+            .collect();
+        transaction.execute_batch(&format!(
+            "CREATE TABLE {} (fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, geom GEOMETRY{})",
+            quote(&self.table),
+            definitions.concat()
+        ))?;
+
+        let mut bbox: Option<[f64; 4]> = None;
+        {
+            let names: Vec<String> = columns.iter().map(|(name, _)| quote(name)).collect();
+            let mut insert = transaction.prepare(&format!(
+                "INSERT INTO {} (geom{}) VALUES (?{})",
+                quote(&self.table),
+                names
+                    .iter()
+                    .map(|name| format!(", {}", name))
+                    .collect::<String>(),
+                ", ?".repeat(names.len())
+            ))?;
+            for (index, (feature, attributes)) in self
+                .feature_collection
+                .features
+                .iter()
+                .zip(attributes.iter())
+                .enumerate()
+            {
+                self.options.check_cancelled()?;
+                let shape = match &feature.geometry {
+                    Some(geometry) => geometry_to_shp(&geometry.value, &self.options),
+                    None => match self.options.null_geometry_behavior {
+                        NullGeometryBehavior::Skip => continue,
+                        NullGeometryBehavior::WriteNullShape => Ok(Shape::NullShape),
+                        NullGeometryBehavior::Error => Err(ConversionError::MissingGeometry),
+                    },
+                };
+                let shape = match shape {
+                    Ok(shape) => shape,
+                    Err(error) if self.options.on_error == ErrorPolicy::Skip => {
+                        self.summary.skipped_features.push(SkippedFeature {
+                            index,
+                            reason: error.to_string(),
+                        });
+                        continue;
+                    }
+                    Err(error) => return Err(error.at_feature(index)),
+                };
+                let geometry = gpkg_geometry(&shape);
+                if let Some((_, envelope)) = &geometry {
+                    bbox = Some(match bbox {
+                        Some(bbox) => [
+                            bbox[0].min(envelope[0]),
+                            bbox[1].max(envelope[1]),
+                            bbox[2].min(envelope[2]),
+                            bbox[3].max(envelope[3]),
+                        ],
+                        None => *envelope,
+                    });
+                }
+                let values = std::iter::once(match geometry {
+                    Some((blob, _)) => SqlValue::Blob(blob),
+                    None => SqlValue::Null,
+                })
+                .chain(columns.iter().map(|(name, column_type)| {
+                    sql_value(column_value(attributes, name), column_type)
+                }));
+                insert.execute(params_from_iter(values))?;
+                self.summary.features_written += 1;
+            }
+        }
+
+        let [min_x, max_x, min_y, max_y] = match bbox {
+            Some(bbox) => bbox.map(Some),
+            None => [None; 4],
+        };
+        transaction.execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+            params![self.table, min_x, min_y, max_x, max_y, WGS84],
+        )?;
+        transaction.execute(
+            "INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', 'GEOMETRY', ?2, 0, 0)",
+            params![self.table, WGS84],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// The name and SQL type of the column of every property, in order of appearance. Names
+/// are compared ignoring ASCII case, as SQLite does: properties spelled differently share the
+/// column of the first spelling, and the `fid` and `geom` columns the table starts with take
+/// precedence over properties named alike, which are left out.
+fn columns(attributes: &[Map<String, JsonValue>]) -> Vec<(String, &'static str)> {
+    let mut seen: HashSet<String> = ["fid", "geom"].map(String::from).into();
+    let mut columns: Vec<(String, &'static str)> = Vec::new();
+    for attributes in attributes {
+        for (name, value) in attributes {
+            let value_type = match value {
+                JsonValue::Null => continue,
+                JsonValue::Bool(_) => "BOOLEAN",
+                JsonValue::Number(number) if number.is_i64() => "INTEGER",
+                JsonValue::Number(_) => "REAL",
+                _ => "TEXT",
+            };
+            if seen.insert(name.to_ascii_lowercase()) {
+                columns.push((name.clone(), value_type));
+                continue;
+            }
+            if let Some((_, column_type)) = columns
+                .iter_mut()
+                .find(|(column, _)| column.eq_ignore_ascii_case(name))
+            {
+                *column_type = match (*column_type, value_type) {
+                    (a, b) if a == b => a,
+                    ("INTEGER", "REAL") | ("REAL", "INTEGER") => "REAL",
+                    _ => "TEXT",
+                };
+            }
+        }
+    }
+    columns
+}
+
+/// The value of the property of `column`, whichever case its name is spelled in.
+fn column_value<'a>(attributes: &'a Map<String, JsonValue>, column: &str) -> Option<&'a JsonValue> {
+    attributes.get(column).or_else(|| {
+        attributes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(column))
+            .map(|(_, value)| value)
+    })
+}
+
+fn sql_value(value: Option<&JsonValue>, column_type: &str) -> SqlValue {
+    match (value, column_type) {
+        (None | Some(JsonValue::Null), _) => SqlValue::Null,
+        (Some(JsonValue::Bool(value)), "BOOLEAN") => SqlValue::Integer(*value as i64),
+        (Some(JsonValue::Number(number)), "INTEGER") => {
+            SqlValue::Integer(number.as_i64().unwrap_or_default())
+        }
+        (Some(JsonValue::Number(number)), "REAL") => {
+            SqlValue::Real(number.as_f64().unwrap_or_default())
+        }
+        (Some(JsonValue::String(text)), _) => SqlValue::Text(text.clone()),
+        (Some(other), _) => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Quotes an SQL identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// WKB geometry types.
+const WKB_POINT: u32 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTIPOINT: u32 = 4;
+const WKB_MULTILINESTRING: u32 = 5;
+const WKB_MULTIPOLYGON: u32 = 6;
+
+/// Encodes `shape` as GeoPackage binary, returning it with its envelope
+/// (`[min_x, max_x, min_y, max_y]`). `None` for a Null Shape, written as a `NULL` geometry.
+///
+/// A polyline with several parts becomes a MultiLineString, and a polygon with several
+/// exterior rings a MultiPolygon. Rings are wound back to the right-hand rule of GeoJSON.
+fn gpkg_geometry(shape: &Shape) -> Option<(Vec<u8>, [f64; 4])> {
+    let mut wkb = Vec::new();
+    let points: Vec<&Point> = match shape {
+        Shape::Point(point) => {
+            write_wkb_point(&mut wkb, point);
+            vec![point]
+        }
+        Shape::Multipoint(multipoint) => {
+            write_wkb_type(&mut wkb, WKB_MULTIPOINT, multipoint.points().len());
+            for point in multipoint.points() {
+                write_wkb_point(&mut wkb, point);
+            }
+            multipoint.points().iter().collect()
+        }
+        Shape::Polyline(line) => {
+            match &line.parts()[..] {
+                [part] => write_wkb_line(&mut wkb, part.iter()),
+                parts => {
+                    write_wkb_type(&mut wkb, WKB_MULTILINESTRING, parts.len());
+                    for part in parts {
+                        write_wkb_line(&mut wkb, part.iter());
+                    }
+                }
+            }
+            line.parts().iter().flatten().collect()
+        }
+        Shape::Polygon(polygon) => {
+            match &polygon_parts(polygon)[..] {
+                [rings] => write_wkb_polygon(&mut wkb, rings),
+                polygons => {
+                    write_wkb_type(&mut wkb, WKB_MULTIPOLYGON, polygons.len());
+                    for rings in polygons {
+                        write_wkb_polygon(&mut wkb, rings);
+                    }
+                }
+            }
+            polygon
+                .rings()
+                .iter()
+                .flat_map(|ring| ring.points())
+                .collect()
+        }
+        _ => return None,
+    };
+    let envelope = points.iter().fold(
+        [
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ],
+        |[min_x, max_x, min_y, max_y], point| {
+            [
+                min_x.min(point.x),
+                max_x.max(point.x),
+                min_y.min(point.y),
+                max_y.max(point.y),
+            ]
+        },
+    );
+
+    // Magic, version 0, then flags: little-endian, with an xy envelope.
+    let mut blob = vec![b'G', b'P', 0, 0b0000_0011];
+    blob.extend_from_slice(&WGS84.to_le_bytes());
+    for value in envelope {
+        blob.extend_from_slice(&value.to_le_bytes());
+    }
+    blob.extend(wkb);
+    Some((blob, envelope))
+}
+
+/// Starts a little-endian WKB geometry of `geometry_type` holding `count` items.
+fn write_wkb_type(wkb: &mut Vec<u8>, geometry_type: u32, count: usize) {
+    wkb.push(1);
+    wkb.extend_from_slice(&geometry_type.to_le_bytes());
+    wkb.extend_from_slice(&(count as u32).to_le_bytes());
+}
+
+fn write_wkb_point(wkb: &mut Vec<u8>, point: &Point) {
+    wkb.push(1);
+    wkb.extend_from_slice(&WKB_POINT.to_le_bytes());
+    write_xy(wkb, point);
+}
+
+fn write_wkb_line<'a>(wkb: &mut Vec<u8>, points: impl ExactSizeIterator<Item = &'a Point>) {
+    write_wkb_type(wkb, WKB_LINESTRING, points.len());
+    for point in points {
+        write_xy(wkb, point);
+    }
+}
+
+fn write_wkb_polygon(wkb: &mut Vec<u8>, rings: &[&[Point]]) {
+    write_wkb_type(wkb, WKB_POLYGON, rings.len());
+    for ring in rings {
+        wkb.extend_from_slice(&(ring.len() as u32).to_le_bytes());
+        for point in ring.iter().rev() {
+            write_xy(wkb, point);
+        }
+    }
+}
+
+fn write_xy(wkb: &mut Vec<u8>, point: &Point) {
+    wkb.extend_from_slice(&point.x.to_le_bytes());
+    wkb.extend_from_slice(&point.y.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_features_into_a_geopackage() {
+        let output = crate::tests::temp_output("geopackage");
+        let output = output.as_str();
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "Zürich", "population_estimate_2024": 421878, "area": 87.88}},
+            {"type": "Feature", "geometry": {"type": "MultiPolygon", "coordinates": [
+                [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]],
+                [[[2.0, 2.0], [3.0, 2.0], [3.0, 3.0], [2.0, 2.0]]]
+            ]}, "properties": {"name": "islands", "area": 1, "fid": 7}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "nowhere"}}
+        ]"#;
+        let mut writer = FeatureCollectionToGpkgWriter::new(contents.to_string(), output).unwrap();
+        writer.write().unwrap();
+        assert!(matches!(
+            writer.write(),
+            Err(ConversionError::AlreadyWritten)
+        ));
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.features_written, 2);
+
+        let connection = Connection::open(format!("{}.gpkg", output)).unwrap();
+        let application_id: i32 = connection
+            .query_row("PRAGMA application_id", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(application_id, APPLICATION_ID);
+        let (data_type, min_x, max_y): (String, f64, f64) = connection
+            .query_row(
+                "SELECT data_type, min_x, max_y FROM gpkg_contents WHERE table_name = 'geopackage'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((data_type.as_str(), min_x, max_y), ("features", 0.0, 3.0));
+
+        let rows: Vec<(Vec<u8>, String, Option<i64>, f64)> = connection
+            .prepare(
+                "SELECT geom, name, population_estimate_2024, area FROM geopackage ORDER BY fid",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].1, "Zürich");
+        assert_eq!(rows[0].2, Some(421878));
+        assert_eq!(rows[1].3, 1.0);
+        for (geom, ..) in rows.iter() {
+            assert_eq!(&geom[..2], b"GP");
+            // The header takes 8 bytes and the envelope 32, followed by the WKB.
+            assert_eq!(geom[40], 1);
+        }
+        assert_eq!(rows[0].0[41..45], WKB_POINT.to_le_bytes());
+        assert_eq!(rows[1].0[41..45], WKB_MULTIPOLYGON.to_le_bytes());
+    }
+
+    #[test]
+    fn writes_properties_spelled_in_another_case_into_one_column() {
+        let output = crate::tests::temp_output("geopackage_case");
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"Name": "a", "count": 1}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "b", "COUNT": 2.5}}
+        ]"#;
+        let mut writer = FeatureCollectionToGpkgWriter::new(contents.to_string(), &output).unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let connection = Connection::open(format!("{}.gpkg", output)).unwrap();
+        let rows: Vec<(String, f64)> = connection
+            .prepare("SELECT name, count FROM geopackage_case ORDER BY fid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows, [("a".to_string(), 1.0), ("b".to_string(), 2.5)]);
+        let count_type: String = connection
+            .query_row(
+                "SELECT type FROM pragma_table_info('geopackage_case') WHERE name = 'count'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count_type, "REAL");
+    }
+}
@@ -0,0 +1,68 @@
+//! Decompresses gzip input as it is read, so that a `.geojson.gz` is converted without a
+//! decompressed copy on disk or in memory.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+/// Reads the decompressed contents of gzip data, including files of several members
+/// concatenated together. The CRC and size in each member's trailer are checked.
+pub type GzDecoder<R> = flate2::read::MultiGzDecoder<R>;
+
+/// The first two bytes of every gzip member.
+pub(crate) const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether `reader` starts with the gzip magic bytes, without consuming them.
+pub(crate) fn is_gzip(reader: &mut impl BufRead) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.starts_with(&MAGIC))
+}
+
+/// `reader`, decompressed as it is read.
+pub(crate) fn decompress<R: Read + Send + 'static>(reader: R) -> Box<dyn BufRead + Send> {
+    Box::new(BufReader::new(GzDecoder::new(reader)))
+}
+
+/// `reader`, decompressed when it starts with the gzip magic bytes, for input that does not
+/// come with a file name to go by, such as stdin.
+pub fn decompress_if_gzipped<R: BufRead + Send + 'static>(
+    mut reader: R,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    if is_gzip(&mut reader)? {
+        Ok(decompress(reader))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gunzip(data: &[u8]) -> io::Result<String> {
+        let mut contents = String::new();
+        decompress_if_gzipped(io::Cursor::new(data.to_vec()))?.read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    #[test]
+    fn decompresses_only_gzip_data() {
+        // `hello hello hello`, compressed with the fixed code.
+        let fixed = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00, 0x80, 0x88, 0xf9, 0xe5, 0x11, 0x00, 0x00, 0x00,
+        ];
+        assert!(is_gzip(&mut &fixed[..]).unwrap());
+        assert!(!is_gzip(&mut &b"{}"[..]).unwrap());
+        assert_eq!(gunzip(&fixed).unwrap(), "hello hello hello");
+        // Members concatenated together are read one after the other.
+        assert_eq!(
+            gunzip(&[&fixed[..], &fixed[..]].concat()).unwrap(),
+            "hello hello hellohello hello hello"
+        );
+        // Anything else is passed through as it is.
+        let geojson = r#"{"type": "FeatureCollection"}"#;
+        assert_eq!(gunzip(geojson.as_bytes()).unwrap(), geojson);
+
+        let compressed = std::fs::read("./fixtures/countries.geojson.gz").unwrap();
+        let expected = std::fs::read_to_string("./fixtures/countries.geojson").unwrap();
+        assert_eq!(gunzip(&compressed).unwrap(), expected);
+    }
+}
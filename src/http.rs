@@ -0,0 +1,194 @@
+//! Downloads GeoJSON over HTTP or HTTPS with `ureq`, for `FeatureCollectionToShpWriter::from_url`.
+
+use std::time::Duration;
+
+use ureq::Agent;
+
+use crate::ConversionError;
+
+/// How `from_url` fetches the GeoJSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// Time the whole request may take, from connecting to reading the last of the body.
+    pub timeout: Duration,
+    /// Redirects followed before giving up; 0 to fail on the first one.
+    pub max_redirects: usize,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_redirects: 5,
+        }
+    }
+}
+
+/// Media types accepted as GeoJSON, besides any other `+json` type.
+const GEOJSON_MEDIA_TYPES: [&str; 4] = [
+    "application/geo+json",
+    "application/vnd.geo+json",
+    "application/json",
+    "text/json",
+];
+
+/// Fetches `url` with a GET request, following redirects, and returns the body.
+///
+/// Fails on a status other than 2xx, and on a `Content-Type` that is not JSON, such as the
+/// HTML of an error page served with a 200.
+pub(crate) fn fetch(url: &str, options: &HttpOptions) -> Result<String, ConversionError> {
+    let error = |reason: String| ConversionError::Http {
+        url: url.to_string(),
+        reason,
+    };
+    let too_many_redirects = || error(format!("more than {} redirects", options.max_redirects));
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(options.timeout))
+        .max_redirects(options.max_redirects.try_into().unwrap_or(u32::MAX))
+        .http_status_as_error(false)
+        .user_agent("geojson_to_shp")
+        .build()
+        .into();
+    let mut response = match agent
+        .get(url)
+        .header("Accept", "application/geo+json, application/json")
+        .call()
+    {
+        Ok(response) => response,
+        Err(ureq::Error::TooManyRedirects) => return Err(too_many_redirects()),
+        Err(err) => return Err(error(err.to_string())),
+    };
+    let status = response.status().as_u16();
+    match status {
+        200..=299 => {}
+        // Only returned, rather than followed, once `max_redirects` is 0.
+        300..=399 => return Err(too_many_redirects()),
+        status => return Err(error(format!("the server responded {}", status))),
+    }
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|value| value.to_str().unwrap_or_default());
+    check_content_type(content_type).map_err(error)?;
+    response
+        .body_mut()
+        .with_config()
+        .read_to_string()
+        .map_err(|err| error(err.to_string()))
+}
+
+fn check_content_type(content_type: Option<&str>) -> Result<(), String> {
+    let content_type = match content_type {
+        Some(content_type) => content_type,
+        None => return Ok(()),
+    };
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if GEOJSON_MEDIA_TYPES.contains(&media_type.as_str()) || media_type.ends_with("+json") {
+        Ok(())
+    } else {
+        Err(format!("expected GeoJSON, got {}", content_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves `responses` to one connection each, returning the base URL of the server.
+    fn serve(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn follows_redirects_and_reads_chunked_bodies() {
+        let base = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: /data/roads.geojson?bbox=1/2\r\nContent-Length: 0\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/geo+json; charset=utf-8\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"ty\r\n6\r\npe\": 1\r\n1\r\n}\r\n0\r\n\r\n"
+                .to_string(),
+        ]);
+        let body = fetch(&format!("{}/roads", base), &HttpOptions::default()).unwrap();
+        assert_eq!(body, r#"{"type": 1}"#);
+    }
+
+    #[test]
+    fn rejects_responses_that_are_not_geojson() {
+        let base = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 6\r\n\r\n<html>"
+                .to_string(),
+            "HTTP/1.1 301 Moved\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n".to_string(),
+        ]);
+        let options = HttpOptions::default();
+        match fetch(&base, &options) {
+            Err(ConversionError::Http { reason, .. }) => assert!(reason.contains("text/html")),
+            other => panic!("unexpected {:?}", other),
+        }
+        let no_redirects = HttpOptions {
+            max_redirects: 0,
+            ..options
+        };
+        match fetch(&base, &no_redirects) {
+            Err(ConversionError::Http { reason, .. }) => {
+                assert_eq!(reason, "more than 0 redirects")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fails_on_statuses_other_than_success() {
+        let base = serve(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}"
+                .to_string(),
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n".to_string(),
+        ]);
+        for status in ["404", "503"] {
+            match fetch(&base, &HttpOptions::default()) {
+                Err(ConversionError::Http { reason, .. }) => {
+                    assert_eq!(reason, format!("the server responded {}", status))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn speaks_tls_to_https_urls() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/roads.geojson", listener.local_addr().unwrap());
+        let handshake = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut record_type = [0; 1];
+            std::io::Read::read_exact(&mut stream, &mut record_type).unwrap();
+            record_type[0]
+        });
+        // The server hangs up rather than finish the handshake, so the fetch fails with
+        // the TLS error rather than a refusal of the scheme.
+        match fetch(&url, &HttpOptions::default()) {
+            Err(ConversionError::Http { url: failed, .. }) => assert_eq!(failed, url),
+            other => panic!("unexpected {:?}", other),
+        }
+        // 0x16 starts a TLS handshake record, the ClientHello.
+        assert_eq!(handshake.join().unwrap(), 0x16);
+    }
+}
@@ -1,177 +1,3282 @@
-use std::env::Args;
+#[cfg(feature = "cli")]
+mod cli;
+mod convert;
+mod csv;
+mod encoding;
+mod error;
+#[cfg(feature = "esri-metadata")]
+mod esri_xml;
+pub mod geom_convert;
+#[cfg(feature = "gpkg")]
+mod gpkg;
+mod gzip;
+#[cfg(feature = "http")]
+mod http;
+mod options;
+mod output;
+mod schema;
+mod shp;
+mod simplify;
+mod stats;
+mod stream;
+mod summary;
+mod text;
+mod to_geojson;
+#[cfg(feature = "topojson")]
+mod topology;
+mod wkt;
+
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::path::Path;
-use std::{error::Error, fs::read_to_string};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use geojson::{Feature, FeatureCollection, GeoJson};
+use serde_json::{Map, Value as JsonValue};
+use shapefile::dbase::{FieldValue, Record, TableWriter};
+use shapefile::{Shape, ShapeType};
+
+use convert::{convert_feature, feature_attributes};
+use encoding::EncodedDbf;
+use geom_convert::{
+    extra_coordinate_values, geometry_to_shp, geometry_type_name, shape_bounds, shape_centroid,
+    shape_type_of,
+};
+use output::{ensure_directory, shapefile_base, OutputFiles};
+use text::prepare_text_values;
 
-use geojson::{FeatureCollection, GeoJson, Value};
-use shapefile::{
-    dbase::{FieldName, TableWriter, TableWriterBuilder},
-    ShapeWriter,
+#[cfg(feature = "cli")]
+pub use cli::{flag_hint, Cli, Command, ErrorFormat, OutputFormat, VERSION};
+pub use convert::feature_to_shape_record;
+pub use csv::FeatureCollectionToCsvWriter;
+pub use encoding_rs::Encoding;
+pub use error::{ConversionError, FeatureError};
+#[cfg(feature = "gpkg")]
+pub use gpkg::FeatureCollectionToGpkgWriter;
+pub use gzip::{decompress_if_gzipped, GzDecoder};
+#[cfg(feature = "http")]
+pub use http::HttpOptions;
+pub use options::{
+    ConversionOptions, ErrorPolicy, FeatureFilter, InexactNumberPolicy, LongTextPolicy,
+    NullGeometryBehavior, SchemaSample, UnencodablePolicy,
 };
+pub use schema::{DbfType, FieldDefinition, FieldSchema, SchemaWarning};
+pub use shp::ShapeRecordWriter;
+pub use stats::{FieldStat, FieldStats};
+pub use stream::{convert_file_streaming, FeatureReader};
+pub use summary::{ConversionSummary, ConversionWarning, SkippedFeature};
+pub use text::transliterate;
+pub use to_geojson::{GeoJsonFormat, ShapefileToGeoJsonConverter};
 
-pub struct Cli {
-    geojson: String,
-    output_path: String,
+pub struct FeatureCollectionToShpWriter<W: Write + Seek = File> {
+    feature_collection: FeatureCollection,
+    options: ConversionOptions,
+    schema: FieldSchema,
+    /// Properties seen while inferring the schema from a sample of the features.
+    sampled_properties: Option<HashSet<String>>,
+    shape_writer: ShapeRecordWriter<W>,
+    dbf_writer: TableWriter<EncodedDbf<W>>,
+    summary: ConversionSummary,
+    /// The `.wkt` sidecar under `ConversionOptions::write_wkt_sidecar`.
+    wkt_dest: Option<Box<dyn Write + Send>>,
+    filepath: String,
+    /// The files written, set for a writer that writes files (see `shp_path()`).
+    paths: OutputPaths,
+    /// Set by `write()` and `write_next()`, as writing the features again would duplicate
+    /// every record.
+    written: bool,
+    progress: WriteProgress,
+    /// Declared last so that it is dropped after the writers have closed the files.
+    outputs: OutputFiles,
 }
 
-impl Cli {
-    pub fn new(mut args: Args) -> Result<Cli, &'static str> {
-        if args.len() < 3 {
-            return Err(
-                "Not enough arguments! Requires 2 positional arguments For example:
-  ./geojson_to_shp [path_to_file OR geojson_as_string] [output_file_path_no_extension]",
-            );
+/// The contents of a shapefile's .shp, .shx, .dbf and sidecars, as produced by `convert_to_bytes`.
+///
+/// `shx` is left empty under `ConversionOptions::skip_index`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShapefileBytes {
+    pub shp: Vec<u8>,
+    pub shx: Vec<u8>,
+    pub dbf: Vec<u8>,
+    /// The .cpg contents, under `ConversionOptions::write_cpg`.
+    pub cpg: Option<String>,
+    /// The .prj contents, under `ConversionOptions::write_prj`.
+    pub prj: Option<String>,
+}
+
+/// The size in bytes of the files a conversion writes, as computed by `estimate_output_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputSizeEstimate {
+    pub shp: u64,
+    pub shx: u64,
+    pub dbf: u64,
+}
+
+impl OutputSizeEstimate {
+    pub fn total(&self) -> u64 {
+        self.shp + self.shx + self.dbf
+    }
+}
+
+/// A filtered FeatureCollection and the schema to write it with.
+struct PreparedCollection {
+    feature_collection: FeatureCollection,
+    schema: FieldSchema,
+    sampled_properties: Option<HashSet<String>>,
+    summary: ConversionSummary,
+}
+
+impl PreparedCollection {
+    fn new(
+        mut feature_collection: FeatureCollection,
+        schema: Option<FieldSchema>,
+        options: &ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let features_filtered = apply_filter(&mut feature_collection, options);
+        if let Some(field) = &options.feature_id_field {
+            validate_feature_ids(&feature_collection, field, options)?;
+        }
+
+        let mut summary = ConversionSummary {
+            features_filtered,
+            ..ConversionSummary::default()
+        };
+        let (mut schema, sampled_properties) = match schema {
+            Some(schema) => (schema, None),
+            None => {
+                let attributes = sampled_attributes(&feature_collection, options);
+                let (schema, warnings) =
+                    FieldSchema::infer_with_warnings(attributes.iter(), options)?;
+                summary.schema_warnings = warnings;
+                let sampled_properties = match options.schema_sample {
+                    SchemaSample::First(count) if count < feature_collection.features.len() => {
+                        Some(attributes.iter().flat_map(Map::keys).cloned().collect())
+                    }
+                    _ => None,
+                };
+                (schema, sampled_properties)
+            }
+        };
+        if let Some(field) = &options.feature_id_field {
+            schema.move_to_front(field);
+        }
+        Ok(Self {
+            feature_collection,
+            schema,
+            sampled_properties,
+            summary,
+        })
+    }
+}
+
+/// Everything needed to create a `FeatureCollectionToShpWriter`, short of the open files.
+///
+/// The config can be kept around, inspected and cloned to run the same conversion again;
+/// `writer()` creates the output files.
+#[derive(Debug, Clone)]
+pub struct FeatureCollectionToShpWriterConfig {
+    pub feature_collection: FeatureCollection,
+    pub options: ConversionOptions,
+    pub filepath: String,
+    /// The schema to write with; inferred from the features when `None`.
+    pub schema: Option<FieldSchema>,
+}
+
+impl FeatureCollectionToShpWriterConfig {
+    pub fn new(contents: &str, filepath: &str) -> Result<Self, ConversionError> {
+        Ok(Self {
+            feature_collection: parse_feature_collection(contents)?,
+            options: ConversionOptions::default(),
+            filepath: shapefile_base(filepath).to_string(),
+            schema: None,
+        })
+    }
+
+    pub fn options(mut self, options: ConversionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn schema(mut self, schema: FieldSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Creates the output files and returns a writer ready to `write()`.
+    pub fn writer(self) -> Result<FeatureCollectionToShpWriter, ConversionError> {
+        FeatureCollectionToShpWriter::from_feature_collection(
+            self.feature_collection,
+            &self.filepath,
+            self.schema,
+            self.options,
+        )
+    }
+
+    /// Writes one shapefile per value of the `field` property into `dir`, named after the
+    /// value (`{dir}/{value}.shp`). Features missing the property, or holding `null`, go to
+    /// `{dir}/_unknown.shp`; `filepath` is not used.
+    ///
+    /// Values that make the same file name, such as `a/b` and `a_b`, or `Road` and `road` on
+    /// file systems that ignore case, are told apart with a `_001`, `_002`… suffix.
+    ///
+    /// Every partition is written with the same schema, inferred from all of the features
+    /// unless one was given. Returns the file name (without extension) and summary of each
+    /// partition, keyed by its value: `None` for the features without one.
+    pub fn write_partitioned(
+        &self,
+        dir: &str,
+        field: &str,
+    ) -> Result<BTreeMap<Option<String>, (String, ConversionSummary)>, ConversionError> {
+        let mut feature_collection = self.feature_collection.clone();
+        apply_filter(&mut feature_collection, &self.options);
+        let schema = match &self.schema {
+            Some(schema) => schema.clone(),
+            None => collection_schema(&feature_collection, &self.options)?,
+        };
+        let mut partitions: BTreeMap<Option<String>, Vec<Feature>> = BTreeMap::new();
+        for feature in feature_collection.features {
+            let value = match feature.property(field) {
+                None | Some(JsonValue::Null) => None,
+                Some(JsonValue::String(text)) => Some(text.clone()),
+                Some(value) => Some(value.to_string()),
+            };
+            partitions.entry(value).or_default().push(feature);
         }
 
-        // Ignore the first arg, which contains the binary name
-        args.next();
+        std::fs::create_dir_all(dir)?;
+        let mut names = HashSet::new();
+        let mut written = BTreeMap::new();
+        for (value, features) in partitions {
+            let name = match &value {
+                Some(value) => partition_name(value),
+                None => UNKNOWN_PARTITION.to_string(),
+            };
+            let name = unique_partition_name(name, &mut names);
+            let partition = FeatureCollection {
+                bbox: None,
+                features,
+                foreign_members: feature_collection.foreign_members.clone(),
+            };
+            let filepath = Path::new(dir).join(&name);
+            let mut writer = FeatureCollectionToShpWriter::from_feature_collection(
+                partition,
+                &filepath.to_string_lossy(),
+                Some(schema.clone()),
+                self.options.clone(),
+            )?;
+            writer.write()?;
+            written.insert(value, (name, writer.finish()?));
+        }
+        Ok(written)
+    }
 
-        let geojson = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a GeoJSON string! Pass a file location or GeoJSON feature collection content."),
+    /// Writes the features to as many shapefiles as it takes to keep every .shp and .dbf under
+    /// `ConversionOptions::max_file_size`: `{filepath}`, then `{filepath}_001`, `{filepath}_002`…
+    ///
+    /// Every shapefile is written with the same schema, inferred from all of the features
+    /// unless one was given. Returns the path (without extension) and summary of each.
+    pub fn write_chunked(&self) -> Result<Vec<(String, ConversionSummary)>, ConversionError> {
+        let mut feature_collection = self.feature_collection.clone();
+        let features_filtered = apply_filter(&mut feature_collection, &self.options);
+        let schema = match &self.schema {
+            Some(schema) => schema.clone(),
+            None => collection_schema(&feature_collection, &self.options)?,
         };
+        let limit = self
+            .options
+            .max_file_size
+            .unwrap_or(ConversionOptions::MAX_FILE_SIZE);
+        let shp_header = shp::HEADER_SIZE as u64;
+        // The dbf records are followed by an end-of-file marker.
+        let dbf_header = schema.header_len() + 1;
+
+        let mut chunks = Vec::new();
+        let mut chunk = Vec::new();
+        let (mut shp_size, mut dbf_size) = (shp_header, dbf_header);
+        for feature in feature_collection.features {
+            // Features that fail to convert are left to `write()` and its error policy.
+            let shape_size = match &feature.geometry {
+                Some(geometry) => geometry_to_shp(&geometry.value, &self.options)
+                    .map_or(0, |shape| shp::record_size(&shape)),
+                None => shp::record_size(&Shape::NullShape),
+            };
+            let is_full = shp_size + shape_size > limit || dbf_size + schema.record_len() > limit;
+            if is_full && !chunk.is_empty() {
+                chunks.push(std::mem::take(&mut chunk));
+                (shp_size, dbf_size) = (shp_header, dbf_header);
+            }
+            shp_size += shape_size;
+            dbf_size += schema.record_len();
+            chunk.push(feature);
+        }
+        chunks.push(chunk);
+
+        let mut written = Vec::with_capacity(chunks.len());
+        // Index of the first feature of the chunk, which the chunk's own indices start from.
+        let mut offset = 0;
+        for (index, features) in chunks.into_iter().enumerate() {
+            let filepath = match index {
+                0 => self.filepath.clone(),
+                _ => format!("{}_{:03}", self.filepath, index),
+            };
+            let len = features.len();
+            let chunk = FeatureCollection {
+                bbox: None,
+                features,
+                foreign_members: feature_collection.foreign_members.clone(),
+            };
+            let mut writer = FeatureCollectionToShpWriter::from_feature_collection(
+                chunk,
+                &filepath,
+                Some(schema.clone()),
+                self.options.clone(),
+            )?;
+            writer
+                .write()
+                .map_err(|err| err.offset_feature_index(offset))?;
+            let mut summary = writer.finish()?;
+            summary.offset_feature_indices(offset);
+            written.push((filepath, summary));
+            offset += len;
+        }
+        // The chunks hold only the features the filter kept.
+        written[0].1.features_filtered = features_filtered;
+        Ok(written)
+    }
 
-        let output_path =
-            match args.next() {
-                Some(arg) => arg,
-                None => return Err(
-                    "Didn't get an output location! Pass an output location without an extension.",
-                ),
+    /// Writes only the attribute table, `{filepath}.dbf` (and its .cpg under
+    /// `ConversionOptions::write_cpg`), for tabular joins. No .shp or .shx is created,
+    /// and the geometries are not converted, so they cannot fail; `self.filepath` is not used.
+    pub fn write_dbf_only(&self, filepath: &str) -> Result<ConversionSummary, ConversionError> {
+        let prepared = PreparedCollection::new(
+            self.feature_collection.clone(),
+            self.schema.clone(),
+            &self.options,
+        )?;
+        prepared.schema.validate()?;
+        let filepath = shapefile_base(filepath);
+        let mut outputs = OutputFiles::new(self.options.overwrite);
+        let mut components = vec![format!("{}.dbf", filepath)];
+        if self.options.write_cpg {
+            components.push(format!("{}.cpg", filepath));
+        }
+        outputs.check(&components)?;
+        ensure_directory(Path::new(&components[0]), self.options.create_dirs)?;
+        if let Some(cpg) = self.options.cpg() {
+            outputs.write(format!("{}.cpg", filepath), cpg)?;
+        }
+        let dbf = outputs.create(format!("{}.dbf", filepath))?;
+        let dbf_dest =
+            EncodedDbf::new(BufWriter::new(dbf), &prepared.schema, self.options.encoding);
+        let mut dbf_writer = prepared.schema.table_builder()?.build_with_dest(dbf_dest);
+
+        let mut summary = prepared.summary;
+        for (index, mut feature) in prepared.feature_collection.features.into_iter().enumerate() {
+            feature.geometry = None;
+            let converted = convert_feature(
+                &feature,
+                &prepared.schema,
+                &self.options,
+                prepared.sampled_properties.as_ref(),
+                &mut summary,
+            );
+            let mut record = match converted {
+                Ok((_, record)) => record,
+                Err(error) if self.options.on_error == ErrorPolicy::Skip => {
+                    summary.skipped_features.push(SkippedFeature {
+                        index,
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+                Err(error) => return Err(error.at_feature(index)),
             };
+            if prepared.schema.is_placeholder() {
+                record.insert(
+                    FieldSchema::PLACEHOLDER_FIELD.to_string(),
+                    FieldValue::Numeric(Some(summary.features_written as f64)),
+                );
+            }
+            dbf_writer.write_record(&record).map_err(|err| {
+                ConversionError::from(shapefile::Error::from(err)).at_feature(index)
+            })?;
+            summary.features_written += 1;
+        }
+        // dbase completes the .dbf header when its writer is dropped.
+        drop(dbf_writer);
+        summary.files = outputs.commit()?;
+        Ok(summary)
+    }
+}
+
+/// The partition written by `write_partitioned` for features without a partition value.
+pub const UNKNOWN_PARTITION: &str = "_unknown";
 
-        Ok(Cli {
-            geojson,
-            output_path,
+/// Makes a partition value safe to use as a file name.
+fn partition_name(value: &str) -> String {
+    let name: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
         })
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => UNKNOWN_PARTITION.to_string(),
+        _ => name,
     }
+}
 
-    pub fn to_writer(self) -> Result<FeatureCollectionToShpWriter, Box<dyn Error>> {
-        let contents = match Path::new(&self.geojson).is_file() {
-            true => read_to_string(&self.geojson)?,
-            false => self.geojson,
-        };
-        FeatureCollectionToShpWriter::new(contents, &self.output_path)
+/// `name`, with the first free `_001`, `_002`… suffix when another partition already has the
+/// name. Names are compared ignoring case, as some file systems do.
+fn unique_partition_name(name: String, taken: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut suffix = 0;
+    while !taken.insert(unique.to_lowercase()) {
+        suffix += 1;
+        unique = format!("{}_{:03}", name, suffix);
     }
+    unique
 }
 
-pub struct FeatureCollectionToShpWriter {
-    feature_collection: FeatureCollection,
-    shape_writer: ShapeWriter<File>,
-    dbf_writer: TableWriter<File>,
+/// Collection-level members that have no place in the shapefile itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectionMetadata {
+    pub bbox: Option<Vec<f64>>,
+    pub foreign_members: Map<String, JsonValue>,
+}
+
+impl CollectionMetadata {
+    fn of(feature_collection: &FeatureCollection) -> Self {
+        CollectionMetadata {
+            bbox: feature_collection.bbox.clone(),
+            foreign_members: feature_collection
+                .foreign_members
+                .clone()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bbox.is_none() && self.foreign_members.is_empty()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut object = self.foreign_members.clone();
+        if let Some(bbox) = &self.bbox {
+            object.insert("bbox".to_string(), JsonValue::from(bbox.clone()));
+        }
+        JsonValue::Object(object)
+    }
 }
 
 impl FeatureCollectionToShpWriter {
-    pub fn new(contents: String, filepath: &str) -> Result<Self, Box<dyn Error>> {
-        let geojson = contents.parse::<GeoJson>()?;
-        let feature_collection = match geojson {
-            GeoJson::FeatureCollection(collection) => collection,
-            _ => panic!("FeatureCollections only!"),
+    /// Creates the output files for `contents`, which is a FeatureCollection, a JSON array of
+    /// Features, a single Feature or a bare Geometry; the latter two are written as a
+    /// one-feature collection.
+    pub fn new(contents: String, filepath: &str) -> Result<Self, ConversionError> {
+        Self::with_options(contents, filepath, ConversionOptions::default())
+    }
+
+    pub fn with_options(
+        contents: String,
+        filepath: &str,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        FeatureCollectionToShpWriterConfig::new(&contents, filepath)?
+            .options(options)
+            .writer()
+    }
+
+    /// Downloads the GeoJSON at `url`, `http://` or `https://`, and creates the output files
+    /// for it, as `with_options` does. `ConversionOptions::http` sets the timeout and the
+    /// number of redirects followed.
+    #[cfg(feature = "http")]
+    pub fn from_url(
+        url: &str,
+        filepath: &str,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let contents = http::fetch(url, &options.http)?;
+        Self::with_options(contents, filepath, options)
+    }
+
+    /// Creates a writer that uses a previously inferred (or hand-written) schema
+    /// instead of scanning the features for one.
+    pub fn from_reader_with_schema<R: Read>(
+        mut reader: R,
+        filepath: &str,
+        schema: FieldSchema,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        FeatureCollectionToShpWriterConfig::new(&contents, filepath)?
+            .options(options)
+            .schema(schema)
+            .writer()
+    }
+
+    fn from_feature_collection(
+        feature_collection: FeatureCollection,
+        filepath: &str,
+        schema: Option<FieldSchema>,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let prepared = PreparedCollection::new(feature_collection, schema, &options)?;
+        Self::from_prepared_collection(prepared, filepath, options)
+    }
+
+    fn from_prepared_collection(
+        prepared: PreparedCollection,
+        filepath: &str,
+        options: ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        let filepath = shapefile_base(filepath);
+        prepared.schema.validate()?;
+        // Files are written under temporary names until the conversion is finished, and
+        // removed again if a later step fails.
+        let mut outputs = OutputFiles::new(options.overwrite);
+        // Every file the writer produces is checked before any is created.
+        let component = |extension: &str| format!("{}.{}", filepath, extension);
+        let mut components = vec![component("shp"), component("dbf")];
+        if !options.skip_index {
+            components.push(component("shx"));
+        }
+        if options.write_cpg {
+            components.push(component("cpg"));
+        }
+        if options.write_prj {
+            components.push(component("prj"));
+        }
+        if !CollectionMetadata::of(&prepared.feature_collection).is_empty() {
+            components.push(component("meta.json"));
+        }
+        #[cfg(feature = "esri-metadata")]
+        if options.esri_metadata {
+            components.push(component("shp.xml"));
+        }
+        if options.write_wkt_sidecar {
+            components.push(component("wkt"));
+        }
+        outputs.check(&components)?;
+        ensure_directory(Path::new(&components[0]), options.create_dirs)?;
+        if let Some(cpg) = options.cpg() {
+            outputs.write(component("cpg"), cpg)?;
+        }
+        if let Some(prj) = options.prj() {
+            outputs.write(component("prj"), prj)?;
+        }
+        let shx = if options.skip_index {
+            None
+        } else {
+            Some(outputs.create(format!("{}.shx", filepath))?)
         };
+        let shp = outputs.create(format!("{}.shp", filepath))?;
+        let dbf = outputs.create(format!("{}.dbf", filepath))?;
+        let wkt = if options.write_wkt_sidecar {
+            Some(outputs.create(component("wkt"))?)
+        } else {
+            None
+        };
+        let paths = OutputPaths {
+            shp: PathBuf::from(component("shp")),
+            shx: (!options.skip_index).then(|| PathBuf::from(component("shx"))),
+            dbf: PathBuf::from(component("dbf")),
+            cpg: options.write_cpg.then(|| PathBuf::from(component("cpg"))),
+            prj: options.write_prj.then(|| PathBuf::from(component("prj"))),
+        };
+        let mut writer = Self::from_prepared(prepared, options, shp, shx, dbf, filepath)?;
+        writer.paths = paths;
+        writer.wkt_dest = wkt.map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write + Send>);
+        writer.outputs = outputs;
+        Ok(writer)
+    }
+
+    /// The path of the .shp, where it is moved by `finish()`.
+    pub fn shp_path(&self) -> &Path {
+        &self.paths.shp
+    }
+
+    /// The path of the .shx, `None` under `ConversionOptions::skip_index`.
+    pub fn shx_path(&self) -> Option<&Path> {
+        self.paths.shx.as_deref()
+    }
+
+    pub fn dbf_path(&self) -> &Path {
+        &self.paths.dbf
+    }
+
+    /// The path of the .cpg naming the code page of the dbf, written under
+    /// `ConversionOptions::write_cpg`.
+    pub fn cpg_path(&self) -> Option<&Path> {
+        self.paths.cpg.as_deref()
+    }
+
+    /// The path of the .prj naming the coordinate reference system, written under
+    /// `ConversionOptions::write_prj`.
+    pub fn prj_path(&self) -> Option<&Path> {
+        self.paths.prj.as_deref()
+    }
+
+    /// Writes the collection metadata to `{filepath}.meta.json`.
+    ///
+    /// Nothing is written when the collection carries no metadata, in which case `None` is returned.
+    /// Like the shapefile itself, the sidecar is only moved into place by `finish()`.
+    pub fn write_metadata_sidecar(&mut self) -> Result<Option<String>, ConversionError> {
+        let metadata = self.metadata();
+        if metadata.is_empty() {
+            return Ok(None);
+        }
+        let path = format!("{}.meta.json", self.filepath);
+        let file = self.outputs.create(path.clone())?;
+        serde_json::to_writer_pretty(file, &metadata.to_json())?;
+        Ok(Some(path))
+    }
 
-        let shape_writer = ShapeWriter::with_shx(
-            File::create(format!("{}.shp", &filepath))?,
-            File::create(format!("{}.shx", &filepath))?,
+    /// Writes a second shapefile at `filepath` holding a single polygon: the rectangle of the
+    /// collection's `bbox`, or, without one, of the extent of its features. The polygon's
+    /// record holds its `min_x`, `min_y`, `max_x` and `max_y`, which are also returned.
+    ///
+    /// The files are written right away, under the `overwrite` and `create_dirs` options of
+    /// the writer.
+    pub fn write_bbox(&self, filepath: &str) -> Result<[f64; 4], ConversionError> {
+        let [min_x, min_y, max_x, max_y] = match self.feature_collection.bbox.as_deref() {
+            Some(&[min_x, min_y, max_x, max_y]) => [min_x, min_y, max_x, max_y],
+            Some(&[min_x, min_y, _, max_x, max_y, _]) => [min_x, min_y, max_x, max_y],
+            Some(_) => {
+                return Err(ConversionError::InvalidGeometry {
+                    reason: "a bbox needs 4 or 6 values",
+                })
+            }
+            None => self.extent()?.ok_or(ConversionError::InvalidGeometry {
+                reason: "the collection has no bbox and no geometry to compute one from",
+            })?,
+        };
+        let mut properties = Map::new();
+        for (name, value) in [
+            ("min_x", min_x),
+            ("min_y", min_y),
+            ("max_x", max_x),
+            ("max_y", max_y),
+        ] {
+            properties.insert(name.to_string(), JsonValue::from(value));
+        }
+        let ring = vec![
+            vec![min_x, min_y],
+            vec![max_x, min_y],
+            vec![max_x, max_y],
+            vec![min_x, max_y],
+            vec![min_x, min_y],
+        ];
+        let feature_collection = FeatureCollection {
+            bbox: None,
+            features: vec![Feature {
+                bbox: None,
+                geometry: Some(geojson::Geometry::new(geojson::Value::Polygon(vec![ring]))),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
+            }],
+            foreign_members: None,
+        };
+        let options = ConversionOptions {
+            overwrite: self.options.overwrite,
+            create_dirs: self.options.create_dirs,
+            ..Default::default()
+        };
+        let mut writer =
+            Self::from_feature_collection(feature_collection, filepath, None, options)?;
+        writer.write()?;
+        writer.finish()?;
+        Ok([min_x, min_y, max_x, max_y])
+    }
+
+    /// The bounds of every geometry in the collection, as converted.
+    fn extent(&self) -> Result<Option<[f64; 4]>, ConversionError> {
+        let mut extent: Option<[f64; 4]> = None;
+        for (index, feature) in self.feature_collection.features.iter().enumerate() {
+            let geometry = match &feature.geometry {
+                Some(geometry) => geometry,
+                None => continue,
+            };
+            let shape = match geometry_to_shp(&geometry.value, &self.options) {
+                Ok(shape) => shape,
+                Err(_) if self.options.on_error == ErrorPolicy::Skip => continue,
+                Err(err) => return Err(err.at_feature(index)),
+            };
+            if let Some([min_x, min_y, max_x, max_y]) = shape_bounds(&shape) {
+                extent = Some(match extent {
+                    Some(bounds) => [
+                        bounds[0].min(min_x),
+                        bounds[1].min(min_y),
+                        bounds[2].max(max_x),
+                        bounds[3].max(max_y),
+                    ],
+                    None => [min_x, min_y, max_x, max_y],
+                });
+            }
+        }
+        Ok(extent)
+    }
+
+    /// Writes an ESRI-style metadata document to `{filepath}.shp.xml`, returning its path.
+    ///
+    /// Call it after `write()`, as it records the number of features written. Like the
+    /// shapefile itself, the document is only moved into place by `finish()`.
+    #[cfg(feature = "esri-metadata")]
+    pub fn write_esri_metadata(&mut self) -> Result<String, ConversionError> {
+        let name = Path::new(&self.filepath)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let xml = esri_xml::esri_metadata_xml(
+            &name,
+            self.shape_writer.shape_type(),
+            self.summary.features_written,
+            &self.schema,
+            std::time::SystemTime::now(),
         );
-        let dbf_writer = build_dbf_writer(filepath, &feature_collection)?;
+        let path = format!("{}.shp.xml", self.filepath);
+        self.outputs.write(path.clone(), &xml)?;
+        Ok(path)
+    }
+}
 
+impl<W: Write + Seek> FeatureCollectionToShpWriter<W> {
+    fn from_prepared(
+        prepared: PreparedCollection,
+        options: ConversionOptions,
+        shp_dest: W,
+        shx_dest: Option<W>,
+        dbf_dest: W,
+        filepath: &str,
+    ) -> Result<Self, ConversionError> {
+        let dbf_dest = EncodedDbf::new(dbf_dest, &prepared.schema, options.encoding);
+        let dbf_writer = prepared.schema.table_builder()?.build_with_dest(dbf_dest);
         Ok(Self {
-            feature_collection,
-            shape_writer,
+            feature_collection: prepared.feature_collection,
+            options,
+            schema: prepared.schema,
+            sampled_properties: prepared.sampled_properties,
+            shape_writer: match shx_dest {
+                Some(shx_dest) => ShapeRecordWriter::with_shx(shp_dest, shx_dest),
+                None => ShapeRecordWriter::new(shp_dest),
+            },
             dbf_writer,
+            summary: prepared.summary,
+            wkt_dest: None,
+            filepath: filepath.to_string(),
+            paths: OutputPaths::default(),
+            written: false,
+            progress: WriteProgress::default(),
+            outputs: OutputFiles::default(),
         })
     }
 
-    pub fn write(&mut self) -> Result<(), Box<dyn Error>> {
-        for feature in self.feature_collection.features.iter() {
-            let geometry = match &feature.geometry {
-                Some(g) => g,
-                None => panic!("No geometry for this feature!"),
-            };
-            match &geometry.value {
-                Value::Point(p) => {
-                    let geom: geo_types::Point<f64> = (p[0], p[1]).try_into()?;
-                    let geom: shapefile::Point = geom.try_into()?;
+    /// The distribution of the values of every dbf field, without writing anything: the
+    /// range of Numeric fields, and the longest value of Character fields.
+    ///
+    /// Values are taken as they would be written (after `transliterate`, `trim_strings`…) but
+    /// before they are truncated to their field width.
+    pub fn compute_stats(&self) -> FieldStats {
+        let attributes = self.feature_collection.features.iter().map(|feature| {
+            let mut attributes =
+                feature_attributes(feature, &self.options, &mut ConversionSummary::default());
+            prepare_text_values(&mut attributes, &self.options);
+            attributes
+        });
+        stats::field_stats(attributes, &self.schema)
+    }
 
-                    self.shape_writer.write_shape(&geom)?;
-                }
-                Value::LineString(line) => {
-                    let points: Vec<(f64, f64)> =
-                        line.iter().map(|point| (point[0], point[1])).collect();
-                    let geom = geo_types::LineString::from(points);
-                    let geom: shapefile::Polyline = geom.try_into()?;
+    /// The dbf schema the writer writes records with.
+    pub fn schema(&self) -> &FieldSchema {
+        &self.schema
+    }
 
-                    self.shape_writer.write_shape(&geom)?;
-                }
-                _ => panic!("Unimplemented Geometry Type!"),
-            };
+    /// Warnings raised while writing, such as foreign members shadowed by properties.
+    pub fn warnings(&self) -> &[String] {
+        &self.summary.warnings
+    }
 
-            let properties = match &feature.properties {
-                Some(props) => props,
-                None => panic!("No properties!"),
-            };
+    /// Truncations, collisions, type fallbacks and width clamps made while inferring the schema.
+    pub fn schema_warnings(&self) -> &[SchemaWarning] {
+        &self.summary.schema_warnings
+    }
+
+    pub fn summary(&self) -> &ConversionSummary {
+        &self.summary
+    }
+
+    /// The FeatureCollection's bbox and foreign members, such as `"generated_at"` or `"source"`.
+    pub fn metadata(&self) -> CollectionMetadata {
+        CollectionMetadata::of(&self.feature_collection)
+    }
+
+    /// The FeatureCollection's members outside of the GeoJSON spec, such as `"links"` or an
+    /// ArcGIS `"exceededTransferLimit"`, or `None` when it has none. Unlike `metadata()`,
+    /// borrows them rather than copying.
+    pub fn foreign_members(&self) -> Option<&Map<String, JsonValue>> {
+        self.feature_collection
+            .foreign_members
+            .as_ref()
+            .filter(|members| !members.is_empty())
+    }
+
+    /// Writes every feature, stopping at the first one that cannot be converted.
+    ///
+    /// Under `ErrorPolicy::Skip` failing features are left out instead. With
+    /// `ConversionOptions::keep_going` every feature is still converted after a failure, and
+    /// all failures are returned together as `ConversionError::Features`; unless they are
+    /// skipped, nothing is written past the first failure.
+    ///
+    /// When the output is left incomplete, the files the writer created are removed, unless
+    /// `ConversionOptions::keep_partial` is set; `finish()` then moves them into place. The
+    /// features are written once: calling `write()` again fails with
+    /// `ConversionError::AlreadyWritten`.
+    pub fn write(&mut self) -> Result<(), ConversionError> {
+        let features = std::mem::take(&mut self.feature_collection.features);
+        let result = self.write_from(features.iter().map(Ok));
+        self.progress.next = features.len();
+        self.feature_collection.features = features;
+        result
+    }
+
+    /// Converts and writes the next feature, returning `None` once every feature has been
+    /// through `write_next()` or `write()`. For callers that drive the conversion themselves,
+    /// to report progress or decide what to do about each failure.
+    ///
+    /// Features the options leave out (without geometry, or duplicates) give `Ok(())`. A
+    /// feature that cannot be converted gives its error, regardless of `ErrorPolicy`, and
+    /// the next call moves on to the next feature; nothing is removed on failure. `write()`
+    /// cannot be called once this has been.
+    pub fn write_next(&mut self) -> Option<Result<(), ConversionError>> {
+        let index = self.progress.next;
+        if index >= self.feature_collection.features.len() {
+            return None;
+        }
+        self.written = true;
+        self.progress.next += 1;
+        let features = std::mem::take(&mut self.feature_collection.features);
+        let feature = &features[index];
+        let result = match self.convert_next(index, feature) {
+            Ok(Some(converted)) => self.write_converted(index, feature, converted),
+            Ok(None) => Ok(()),
+            Err(error) => Err(error.at_feature(index)),
+        };
+        self.feature_collection.features = features;
+        self.update_centroids();
+        Some(result)
+    }
+
+    /// Calls `write_next()` for every remaining feature, yielding its result.
+    pub fn write_each(&mut self) -> impl Iterator<Item = Result<(), ConversionError>> + '_ {
+        std::iter::from_fn(move || self.write_next())
+    }
+
+    /// Same as `write()`, for features read one at a time: stops at the first read error.
+    fn write_from<F: Borrow<Feature>>(
+        &mut self,
+        features: impl Iterator<Item = Result<F, ConversionError>>,
+    ) -> Result<(), ConversionError> {
+        if self.written {
+            return Err(ConversionError::AlreadyWritten);
+        }
+        self.written = true;
+        let result = self.write_features(features);
+        if let Err(err) = &result {
+            // Skipped features still leave a complete shapefile behind.
+            let is_complete = matches!(err, ConversionError::Features(_))
+                && self.options.on_error == ErrorPolicy::Skip;
+            if !is_complete && !self.options.keep_partial {
+                self.outputs.discard();
+            }
+        }
+        result
+    }
+
+    /// Completes the headers of the output files and moves the files into place.
+    ///
+    /// The files are written under temporary names next to their destination, so that a
+    /// half-written shapefile is never seen under its own name. Dropping the writer without
+    /// calling `finish()` removes them instead.
+    ///
+    /// Returns the summary of the features written.
+    pub fn finish(self) -> Result<ConversionSummary, ConversionError> {
+        let Self {
+            mut shape_writer,
+            dbf_writer,
+            wkt_dest,
+            mut summary,
+            mut outputs,
+            ..
+        } = self;
+        let closed = shape_writer
+            .close()
+            .map_err(ConversionError::from)
+            .and_then(|_| match wkt_dest {
+                Some(mut wkt_dest) => wkt_dest.flush().map_err(ConversionError::from),
+                None => Ok(()),
+            });
+        // dbase completes the .dbf header when its writer is dropped.
+        drop(shape_writer);
+        drop(dbf_writer);
+        if let Err(err) = closed {
+            outputs.discard();
+            return Err(err);
+        }
+        summary.files = outputs.commit()?;
+        Ok(summary)
+    }
 
-            let mut record = shapefile::dbase::Record::default();
-            for (prop_name, value) in properties.into_iter() {
-                match value {
-                    serde_json::Value::Number(val) => {
-                        record.insert(
-                            prop_name.to_string(),
-                            shapefile::dbase::FieldValue::Numeric(val.as_f64()),
-                        );
+    fn write_features<F: Borrow<Feature>>(
+        &mut self,
+        features: impl Iterator<Item = Result<F, ConversionError>>,
+    ) -> Result<(), ConversionError> {
+        let mut errors = Vec::new();
+        for (index, feature) in features.enumerate() {
+            self.options.check_cancelled()?;
+            let feature = feature?;
+            let feature = feature.borrow();
+            let converted = match self.convert_next(index, feature) {
+                Ok(Some(converted)) => converted,
+                Ok(None) => continue,
+                Err(error) => {
+                    if self.options.on_error == ErrorPolicy::Skip {
+                        self.summary.skipped_features.push(SkippedFeature {
+                            index,
+                            reason: error.to_string(),
+                        });
                     }
-                    serde_json::Value::String(val) => {
-                        record.insert(
-                            prop_name.to_string(),
-                            shapefile::dbase::FieldValue::Character(Some(val.to_string())),
-                        );
+                    if self.options.keep_going {
+                        errors.push(FeatureError { index, error });
+                    } else if self.options.on_error == ErrorPolicy::FailFast {
+                        return Err(error.at_feature(index));
                     }
-                    _ => panic!("lazy"),
+                    continue;
                 }
+            };
+            if !errors.is_empty() && self.options.on_error == ErrorPolicy::FailFast {
+                // Only looking for further errors from here on.
+                continue;
+            }
+            self.write_converted(index, feature, converted)?;
+        }
+        self.update_centroids();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConversionError::Features(errors))
+        }
+    }
+
+    /// Converts the feature at `index`, or returns `None` when the options leave it out.
+    fn convert_next(
+        &mut self,
+        index: usize,
+        feature: &Feature,
+    ) -> Result<Option<ConvertedFeature>, ConversionError> {
+        if feature.geometry.is_none() {
+            match self.options.null_geometry_behavior {
+                NullGeometryBehavior::Skip => return Ok(None),
+                NullGeometryBehavior::WriteNullShape => {}
+                NullGeometryBehavior::Error => return Err(ConversionError::MissingGeometry),
+            }
+        }
+        let key = if self.options.dedupe {
+            Some(feature_key(feature)?)
+        } else {
+            None
+        };
+        if key
+            .as_ref()
+            .is_some_and(|key| self.progress.seen.contains(key))
+        {
+            self.summary.duplicates_skipped += 1;
+            self.summary
+                .conversion_warnings
+                .push(ConversionWarning::DuplicateFeatureSkipped {
+                    feature_index: index,
+                });
+            return Ok(None);
+        }
+        let (shape, record) = convert_feature(
+            feature,
+            &self.schema,
+            &self.options,
+            self.sampled_properties.as_ref(),
+            &mut self.summary,
+        )?;
+        let extra = feature
+            .geometry
+            .as_ref()
+            .map_or(0, |geometry| extra_coordinate_values(&geometry.value));
+        if extra > 0 {
+            self.summary
+                .conversion_warnings
+                .push(ConversionWarning::ExtraCoordinatesDropped {
+                    feature_index: index,
+                    count: extra,
+                });
+        }
+        if let Some(actual) = shape_type_conflict(self.shape_writer.shape_type(), &shape) {
+            return Err(ConversionError::MixedShapeTypes {
+                expected: self.shape_writer.shape_type(),
+                actual,
+            });
+        }
+        Ok(Some(ConvertedFeature { shape, record, key }))
+    }
+
+    /// Writes the converted feature at `index` to the .shp, .dbf and sidecars.
+    ///
+    /// The feature only counts as written, and as seen under `ConversionOptions::dedupe`,
+    /// once all of them are.
+    fn write_converted(
+        &mut self,
+        index: usize,
+        feature: &Feature,
+        converted: ConvertedFeature,
+    ) -> Result<(), ConversionError> {
+        let ConvertedFeature {
+            shape,
+            mut record,
+            key,
+        } = converted;
+        let record_number = self.progress.record_number;
+        if self.schema.is_placeholder() {
+            record.insert(
+                FieldSchema::PLACEHOLDER_FIELD.to_string(),
+                FieldValue::Numeric(Some(record_number as f64)),
+            );
+        }
+        let limit = self
+            .options
+            .max_file_size
+            .unwrap_or(ConversionOptions::MAX_FILE_SIZE);
+        let shp_size = self.shape_writer.file_size() + shp::record_size(&shape);
+        let dbf_size =
+            self.schema.header_len() + self.schema.record_len() * (record_number as u64 + 1) + 1;
+        if shp_size.max(dbf_size) > limit {
+            return Err(ConversionError::FileSizeLimit { limit }.at_feature(index));
+        }
+        if record_number as u64 >= FieldSchema::MAX_RECORDS {
+            return Err(ConversionError::TooManyRecords {
+                limit: FieldSchema::MAX_RECORDS,
             }
-            self.dbf_writer
-                .write_record(&record)
-                .expect("Could not write record!");
+            .at_feature(index));
+        }
+        self.shape_writer
+            .write_shape(&shape)
+            .map_err(|err| ConversionError::from(err).at_feature(index))?;
+        self.dbf_writer
+            .write_record(&record)
+            .map_err(|err| ConversionError::from(shapefile::Error::from(err)).at_feature(index))?;
+        if let Some(wkt_dest) = &mut self.wkt_dest {
+            writeln!(wkt_dest, "{}", wkt::shape_to_wkt(&shape))
+                .map_err(|err| ConversionError::from(err).at_feature(index))?;
+        }
+        if let (Some(geometry), Some([x, y])) = (&feature.geometry, shape_centroid(&shape)) {
+            let sum = self
+                .progress
+                .centroid_sums
+                .entry(geometry_type_name(&geometry.value))
+                .or_insert((0.0, 0.0, 0));
+            *sum = (sum.0 + x, sum.1 + y, sum.2 + 1);
+        }
+        self.progress.record_number += 1;
+        self.summary.features_written += 1;
+        if let Some(key) = key {
+            self.progress.seen.insert(key);
         }
         Ok(())
     }
+
+    /// Sets the mean centroid per geometry type of the features written so far.
+    fn update_centroids(&mut self) {
+        self.summary.centroids = self
+            .progress
+            .centroid_sums
+            .iter()
+            .map(|(geometry_type, (x, y, count))| {
+                let count = *count as f64;
+                (geometry_type.to_string(), [x / count, y / count])
+            })
+            .collect();
+    }
 }
 
-fn build_dbf_writer(
-    filepath: &str,
-    feature_collection: &FeatureCollection,
-) -> Result<TableWriter<File>, Box<dyn Error>> {
-    let feature = feature_collection.features[0].clone();
-    let properties = match feature.properties {
-        Some(props) => props,
-        None => panic!(
-            "No properties in the first feature from the collection! Cannot build dbf writer."
-        ),
-    };
+/// A feature converted by `convert_next()`, ready to be written.
+struct ConvertedFeature {
+    shape: Shape,
+    record: Record,
+    /// Its key under `ConversionOptions::dedupe`.
+    key: Option<String>,
+}
 
-    let mut writer = TableWriterBuilder::new();
-    for (prop_name, value) in properties.iter() {
-        match value {
-            serde_json::Value::Number(_) => {
-                writer = writer.add_numeric_field(FieldName::try_from(&prop_name[..])?, 22, 20)
-            }
-            serde_json::Value::String(_) => {
-                writer = writer.add_character_field(FieldName::try_from(&prop_name[..])?, 255);
+/// The files a writer writes.
+#[derive(Debug, Default)]
+struct OutputPaths {
+    shp: PathBuf,
+    shx: Option<PathBuf>,
+    dbf: PathBuf,
+    cpg: Option<PathBuf>,
+    prj: Option<PathBuf>,
+}
+
+/// Where a writer is in the features, between calls to `write_next()`.
+#[derive(Debug, Default)]
+struct WriteProgress {
+    /// Index of the feature `write_next()` converts next.
+    next: usize,
+    /// Number of records written.
+    record_number: usize,
+    /// Keys of the features written, under `ConversionOptions::dedupe`.
+    seen: HashSet<String>,
+    /// Sums of the centroid coordinates per geometry type, with the number of features.
+    centroid_sums: HashMap<&'static str, (f64, f64, usize)>,
+}
+
+/// Converts a GeoJSON FeatureCollection into `{output_base}.shp`, `.shx` and `.dbf`.
+///
+/// `input` is either the path to a GeoJSON file or the GeoJSON itself. As a convenience for
+/// the many APIs that respond with a bare JSON array of Features, such an array is read as
+/// the collection of its Features. The collection metadata, if any, is written to
+/// `{output_base}.meta.json`.
+pub fn convert(input: &str, output_base: &str) -> Result<ConversionSummary, ConversionError> {
+    convert_with_options(input, output_base, ConversionOptions::default())
+}
+
+/// Same as `convert`, with control over how the features are converted.
+pub fn convert_with_options(
+    input: &str,
+    output_base: &str,
+    options: ConversionOptions,
+) -> Result<ConversionSummary, ConversionError> {
+    let contents = read_input(input)?;
+    convert_contents(contents, input_path(input), output_base, options)
+}
+
+/// Converts GeoJSON `contents`, read from the file at `path` if any.
+pub(crate) fn convert_contents(
+    contents: String,
+    path: Option<&Path>,
+    output_base: &str,
+    options: ConversionOptions,
+) -> Result<ConversionSummary, ConversionError> {
+    let mut writer = FeatureCollectionToShpWriter::with_options(contents, output_base, options)
+        .map_err(|err| err.in_file(path))?;
+    if let Err(err) = writer.write() {
+        // Still puts the files write() leaves behind into place: those of a conversion that
+        // skipped features, or the partial output under `keep_partial`.
+        writer.finish()?;
+        return Err(err);
+    }
+    writer.write_metadata_sidecar()?;
+    #[cfg(feature = "esri-metadata")]
+    if writer.options.esri_metadata {
+        writer.write_esri_metadata()?;
+    }
+    writer.finish()
+}
+
+/// Converts every file matching the glob `pattern`, writing `data/foo.geojson` to
+/// `{output_dir}/foo.shp` (and `.shx`, `.dbf`).
+///
+/// Returns each converted file with its summary, or an empty list when nothing matches.
+/// The conversion stops at the first file that fails.
+pub fn convert_glob(
+    pattern: &str,
+    output_dir: &str,
+    options: &ConversionOptions,
+) -> Result<Vec<(PathBuf, ConversionSummary)>, ConversionError> {
+    let paths = glob::glob(pattern)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(std::io::Error::from)?;
+    if !paths.is_empty() {
+        std::fs::create_dir_all(output_dir)?;
+    }
+    let mut converted = Vec::with_capacity(paths.len());
+    for path in paths {
+        let stem = path.file_stem().unwrap_or_default();
+        let output_base = Path::new(output_dir).join(stem);
+        let summary = convert_with_options(
+            &path.to_string_lossy(),
+            &output_base.to_string_lossy(),
+            options.clone(),
+        )
+        .map_err(|err| match err {
+            // Parse errors already name the file.
+            err @ ConversionError::Parse { .. } => err,
+            err => ConversionError::Input {
+                path: path.clone(),
+                source: Box::new(err),
             },
-            _ => panic!("Property type not supported! Only Number and String values are currently supported.")
+        })?;
+        converted.push((path, summary));
+    }
+    Ok(converted)
+}
+
+/// Computes the size of the .shp, .shx and .dbf that `convert` would write for `contents`,
+/// without writing anything.
+///
+/// Record sizes only depend on the shape type, the number of parts and points, and the
+/// schema, so the estimate matches the output of a successful default conversion.
+pub fn estimate_output_bytes(contents: &str) -> Result<OutputSizeEstimate, ConversionError> {
+    let options = ConversionOptions::default();
+    let prepared = PreparedCollection::new(parse_feature_collection(contents)?, None, &options)?;
+    let shapes = prepared
+        .feature_collection
+        .features
+        .iter()
+        .enumerate()
+        // Features without a geometry are skipped by default.
+        .filter_map(|(index, feature)| Some((index, feature.geometry.as_ref()?)))
+        .map(|(index, geometry)| {
+            geometry_to_shp(&geometry.value, &options).map_err(|err| err.at_feature(index))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (shp, shx) = shp::file_sizes(&shapes);
+    let schema = &prepared.schema;
+    Ok(OutputSizeEstimate {
+        shp,
+        shx,
+        // The records are followed by an end-of-file marker.
+        dbf: schema.header_len() + schema.record_len() * shapes.len() as u64 + 1,
+    })
+}
+
+/// Converts GeoJSON into shapefile bytes without touching the file system.
+pub fn convert_to_bytes(
+    geojson: &str,
+    options: &ConversionOptions,
+) -> Result<ShapefileBytes, ConversionError> {
+    let feature_collection = parse_feature_collection(geojson)?;
+    let prepared = PreparedCollection::new(feature_collection, None, options)?;
+    let mut shp = Cursor::new(Vec::new());
+    let mut shx = Cursor::new(Vec::new());
+    let mut dbf = Cursor::new(Vec::new());
+    {
+        let mut writer = FeatureCollectionToShpWriter::from_prepared(
+            prepared,
+            options.clone(),
+            &mut shp,
+            (!options.skip_index).then_some(&mut shx),
+            &mut dbf,
+            "",
+        )?;
+        writer.write()?;
+        writer.shape_writer.close()?;
+    }
+    Ok(ShapefileBytes {
+        shp: shp.into_inner(),
+        shx: shx.into_inner(),
+        dbf: dbf.into_inner(),
+        cpg: options.cpg().map(str::to_string),
+        prj: options.prj().map(str::to_string),
+    })
+}
+
+/// Treats `input` as GeoJSON content when it starts with `{` or `[`, otherwise reads it as a file.
+pub(crate) fn read_input(input: &str) -> Result<String, ConversionError> {
+    match input_path(input) {
+        Some(path) => read_input_file(path),
+        None => Ok(input.to_string()),
+    }
+}
+
+/// Reads the input file at `path`, naming it when it does not exist. A gzipped file is
+/// decompressed (see `open_input_file`).
+pub(crate) fn read_input_file(path: &Path) -> Result<String, ConversionError> {
+    let mut contents = String::new();
+    open_input_file(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Opens the input file at `path`, naming it when it does not exist. A file ending in `.gz`,
+/// or starting with the gzip magic bytes, is decompressed as it is read.
+pub(crate) fn open_input_file(path: &Path) -> Result<Box<dyn BufRead + Send>, ConversionError> {
+    let file = File::open(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => ConversionError::InputNotFound {
+            path: path.to_path_buf(),
+        },
+        _ => err.into(),
+    })?;
+    let mut reader = BufReader::new(file);
+    if is_gzip_path(path) || gzip::is_gzip(&mut reader)? {
+        Ok(gzip::decompress(reader))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Whether `path` ends in `.gz`, such as `parcels.geojson.gz`.
+pub(crate) fn is_gzip_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gz"))
+}
+
+/// The file `input` names, or `None` when it is GeoJSON content or a URL.
+///
+/// Input starting with `{` or `[` is content. Otherwise it is a file when it exists, or when
+/// it looks like a path, so that a mistyped file name is reported as missing rather than as
+/// invalid GeoJSON.
+pub(crate) fn input_path(input: &str) -> Option<&Path> {
+    let trimmed = input.trim_start_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}');
+    if trimmed.starts_with('{') || trimmed.starts_with('[') || is_url(input) {
+        return None;
+    }
+    let path = Path::new(input);
+    if path.exists() || looks_like_path(input) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Whether `input` is an `http://` or `https://` URL, which the binary downloads rather than
+/// reads as a file.
+pub(crate) fn is_url(input: &str) -> bool {
+    let scheme = input
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https")) && !input.contains(char::is_whitespace)
+}
+
+/// Whether `input` reads as a file path: a single short line without braces or quotes, with
+/// a directory or an extension.
+fn looks_like_path(input: &str) -> bool {
+    input.len() <= 4096
+        && !input.contains(['\n', '{', '}', '"'])
+        && (input.contains(['/', '\\']) || Path::new(input).extension().is_some())
+}
+
+/// The shape type of the shapefile the collection converts into.
+///
+/// Features without geometry are left out; a collection without any geometry gives
+/// `ShapeType::NullShape`. Fails on geometries that cannot be converted, or that convert into
+/// different shape types.
+pub fn infer_shape_type(
+    feature_collection: &FeatureCollection,
+) -> Result<ShapeType, ConversionError> {
+    let mut inferred = ShapeType::NullShape;
+    for (index, feature) in feature_collection.features.iter().enumerate() {
+        let geometry = match &feature.geometry {
+            Some(geometry) => geometry,
+            None => continue,
+        };
+        let shape_type = shape_type_of(&geometry.value).map_err(|err| err.at_feature(index))?;
+        if inferred == ShapeType::NullShape {
+            inferred = shape_type;
+        } else if shape_type != inferred {
+            return Err(ConversionError::MixedShapeTypes {
+                expected: inferred,
+                actual: shape_type,
+            }
+            .at_feature(index));
         }
     }
-    let dest = File::create(format!("{}.dbf", filepath))?;
-    Ok(writer.build_with_dest(dest))
+    Ok(inferred)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The shape type of `shape` when it cannot go in a file of `file_type`.
+fn shape_type_conflict(file_type: ShapeType, shape: &Shape) -> Option<ShapeType> {
+    let shape_type = shape.shapetype();
+    match (file_type, shape_type) {
+        (ShapeType::NullShape, _) | (_, ShapeType::NullShape) => None,
+        (file_type, shape_type) if file_type != shape_type => Some(shape_type),
+        _ => None,
+    }
+}
 
-    #[test]
-    fn creates_new_writer_and_writes_without_error() {
-        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
-        let mut writer = FeatureCollectionToShpWriter::new(contents, "./fixtures/test").unwrap();
-        writer.write().expect("Shapes")
+/// The serialized geometry and properties, used to recognize duplicate features. The whole
+/// serialization is kept, rather than a hash of it, so that features whose hashes happen to
+/// collide are still told apart.
+///
+/// serde_json keeps object keys sorted, so property order does not affect the key.
+fn feature_key(feature: &Feature) -> Result<String, ConversionError> {
+    Ok(serde_json::to_string(&(
+        &feature.geometry,
+        &feature.properties,
+    ))?)
+}
+
+/// Infers the dbf schema for a GeoJSON FeatureCollection without writing anything.
+pub fn infer_schema(
+    contents: &str,
+    options: &ConversionOptions,
+) -> Result<FieldSchema, ConversionError> {
+    let mut feature_collection = parse_feature_collection(contents)?;
+    apply_filter(&mut feature_collection, options);
+    collection_schema(&feature_collection, options)
+}
+
+/// The schema of an already filtered collection, with the feature id field first.
+fn collection_schema(
+    feature_collection: &FeatureCollection,
+    options: &ConversionOptions,
+) -> Result<FieldSchema, ConversionError> {
+    let mut schema = build_schema(feature_collection, options)?;
+    if let Some(field) = &options.feature_id_field {
+        schema.move_to_front(field);
+    }
+    Ok(schema)
+}
+
+/// Checks that every feature carries the id property, and that the ids are unique when required.
+fn validate_feature_ids(
+    feature_collection: &FeatureCollection,
+    field: &str,
+    options: &ConversionOptions,
+) -> Result<(), ConversionError> {
+    let mut check = FeatureIdCheck::default();
+    for (index, feature) in feature_collection.features.iter().enumerate() {
+        check.add(index, feature, field, options);
+    }
+    check.finish(field)
+}
+
+/// The features found missing the id property, or repeating an id, so far.
+#[derive(Default)]
+struct FeatureIdCheck {
+    missing: Vec<usize>,
+    duplicates: Vec<usize>,
+    seen: HashSet<String>,
+}
+
+impl FeatureIdCheck {
+    fn add(&mut self, index: usize, feature: &Feature, field: &str, options: &ConversionOptions) {
+        let mut id = feature.properties.as_ref().and_then(|p| p.get(field));
+        if id.is_none() && options.include_foreign_members {
+            id = feature.foreign_members.as_ref().and_then(|m| m.get(field));
+        }
+        match id {
+            None | Some(JsonValue::Null) => self.missing.push(index),
+            Some(id) => {
+                if options.require_unique_id && !self.seen.insert(id.to_string()) {
+                    self.duplicates.push(index);
+                }
+            }
+        }
+    }
+
+    fn finish(self, field: &str) -> Result<(), ConversionError> {
+        let FeatureIdCheck {
+            missing,
+            duplicates,
+            ..
+        } = self;
+        if !missing.is_empty() {
+            return Err(ConversionError::MissingFeatureId {
+                field: field.to_string(),
+                indices: missing,
+            });
+        }
+        if !duplicates.is_empty() {
+            return Err(ConversionError::DuplicateFeatureId {
+                field: field.to_string(),
+                indices: duplicates,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Leaves out the features `ConversionOptions::filter` rejects, returning how many it did.
+fn apply_filter(feature_collection: &mut FeatureCollection, options: &ConversionOptions) -> usize {
+    let count = feature_collection.features.len();
+    if let Some(filter) = &options.filter {
+        feature_collection
+            .features
+            .retain(|feature| filter.matches(feature));
+    }
+    count - feature_collection.features.len()
+}
+
+fn parse_feature_collection(contents: &str) -> Result<FeatureCollection, ConversionError> {
+    // Some (mostly Windows) tools start the file with a UTF-8 byte order mark, which may
+    // also end up after blank lines when files are concatenated or templated. The blank lines
+    // are kept, so that parse errors point at the right line.
+    let body = contents.trim_start_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}');
+    let leading = &contents[..contents.len() - body.len()];
+    let contents: Cow<str> = if leading.contains('\u{FEFF}') {
+        format!("{}{}", leading.replace('\u{FEFF}', ""), body).into()
+    } else {
+        contents.into()
+    };
+    let contents = contents.as_ref();
+    let mut value: JsonValue = serde_json::from_str(contents)
+        .map_err(|err| ConversionError::parse(geojson::Error::MalformedJson(err), contents))?;
+    // Some services emit a bare array of Features without the FeatureCollection around it.
+    if value.is_array() {
+        value = serde_json::json!({"type": "FeatureCollection", "features": value});
+    }
+    #[cfg(feature = "topojson")]
+    if topology::is_topology(&value) {
+        value = topology::topology_to_feature_collection(value)?;
+    }
+    fill_missing_properties(&mut value);
+    let geojson =
+        GeoJson::from_json_value(value).map_err(|err| ConversionError::parse(err, contents))?;
+    let features = match geojson {
+        GeoJson::FeatureCollection(collection) => return Ok(collection),
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(geometry) => vec![Feature {
+            bbox: None,
+            geometry: Some(geometry),
+            id: None,
+            properties: None,
+            foreign_members: None,
+        }],
+    };
+    Ok(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
+}
+
+/// Adds `"properties": null` to features that omit the member entirely,
+/// which the geojson crate would otherwise reject.
+fn fill_missing_properties(value: &mut JsonValue) {
+    if value.get("type").and_then(JsonValue::as_str) == Some("Feature") {
+        if let Some(feature) = value.as_object_mut() {
+            feature.entry("properties").or_insert(JsonValue::Null);
+        }
+        return;
+    }
+    let features = match value.get_mut("features").and_then(JsonValue::as_array_mut) {
+        Some(features) => features,
+        None => return,
+    };
+    for feature in features.iter_mut().filter_map(JsonValue::as_object_mut) {
+        feature.entry("properties").or_insert(JsonValue::Null);
+    }
+}
+
+fn build_schema(
+    feature_collection: &FeatureCollection,
+    options: &ConversionOptions,
+) -> Result<FieldSchema, ConversionError> {
+    let attributes = sampled_attributes(feature_collection, options);
+    FieldSchema::infer_with_options(attributes.iter(), options)
+}
+
+/// The prepared attributes of the features the schema is inferred from.
+fn sampled_attributes(
+    feature_collection: &FeatureCollection,
+    options: &ConversionOptions,
+) -> Vec<Map<String, JsonValue>> {
+    let sample_size = match options.schema_sample {
+        SchemaSample::First(count) => count,
+        SchemaSample::All => usize::MAX,
+    };
+    feature_collection
+        .features
+        .iter()
+        .take(sample_size)
+        .map(|feature| {
+            let mut attributes =
+                feature_attributes(feature, options, &mut ConversionSummary::default());
+            prepare_text_values(&mut attributes, options);
+            attributes
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_new_writer_and_writes_without_error() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let output = temp_output("creates_new_writer");
+        let mut writer = FeatureCollectionToShpWriter::new(contents, &output).unwrap();
+        writer.write().expect("Shapes");
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn overrides_only_the_options_given() {
+        let options = ConversionOptions {
+            dedupe: true,
+            ..Default::default()
+        };
+        assert!(options.dedupe);
+        assert!(options.write_prj && options.write_cpg);
+        assert_eq!(options.null_geometry_behavior, NullGeometryBehavior::Skip);
+        assert_eq!(options.on_error, ErrorPolicy::FailFast);
+        assert_eq!(options.schema_sample, SchemaSample::All);
+        assert_eq!(options.default_string_width, None);
+        assert_eq!(options.encoding, None);
+        assert!(!options.skip_index && !options.overwrite && !options.keep_going);
+    }
+
+    /// A path to write the shapefile `name` to, clear of the files of earlier runs.
+    pub(crate) fn temp_output(name: &str) -> String {
+        let dir = std::env::temp_dir().join("geojson_to_shp_tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            if file_name.starts_with(&format!("{}.", name)) {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        dir.join(name).to_str().unwrap().to_string()
+    }
+
+    const NULL_GEOMETRY_COLLECTION: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "b"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "c"}}
+        ]
+    }"#;
+
+    #[test]
+    fn strips_a_shapefile_extension_from_the_output() {
+        let output = temp_output("with_extension");
+        for extension in ["shp", "DBF"] {
+            let filepath = format!("{}.{}", output, extension);
+            let options = ConversionOptions::default().overwrite(true);
+            let mut writer = FeatureCollectionToShpWriter::with_options(
+                NULL_GEOMETRY_COLLECTION.to_string(),
+                &filepath,
+                options,
+            )
+            .unwrap();
+            writer.write().unwrap();
+            writer.finish().unwrap();
+            for component in ["shp", "shx", "dbf"] {
+                assert!(Path::new(&format!("{}.{}", output, component)).is_file());
+                assert!(!Path::new(&format!("{}.shp.{}", output, component)).exists());
+            }
+        }
+    }
+
+    #[test]
+    fn converts_a_fixture_in_one_call() {
+        let output = temp_output("convert_points");
+        let summary = convert("./fixtures/points.geojson", &output).unwrap();
+        assert!(summary.features_written > 0);
+        assert!(Path::new(&format!("{}.shp", output)).is_file());
+        assert!(Path::new(&format!("{}.dbf", output)).is_file());
+    }
+
+    #[test]
+    fn writes_a_readable_empty_shapefile() {
+        let output = temp_output("empty_collection");
+        let contents = r#"{"type": "FeatureCollection", "features": []}"#;
+        let mut writer = FeatureCollectionToShpWriter::new(contents.to_string(), &output).unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let shapes_and_records =
+            shapefile::read(format!("{}.shp", output)).expect("the empty shapefile is readable");
+        assert!(shapes_and_records.is_empty());
+        let mut dbf = shapefile::dbase::Reader::from_path(format!("{}.dbf", output)).unwrap();
+        let fields: Vec<_> = dbf.fields().iter().map(|field| field.name()).collect();
+        assert_eq!(fields, ["DeletionFlag", FieldSchema::PLACEHOLDER_FIELD]);
+        assert!(dbf.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn writes_3d_input_as_a_2d_shapefile() {
+        let output = temp_output("elevations");
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0, 310.5], [3.0, 4.0, 312.0, 7.0]]}, "properties": {}}
+        ]"#;
+        let mut writer = FeatureCollectionToShpWriter::new(contents.to_string(), &output).unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let reader = shapefile::ShapeReader::from_path(format!("{}.shp", output)).unwrap();
+        assert_eq!(reader.header().shape_type, ShapeType::Polyline);
+        assert_eq!(reader.header().bbox.min.z, 0.0);
+        assert_eq!(reader.header().bbox.max.z, 0.0);
+        match &shapefile::read(format!("{}.shp", output)).unwrap()[0].0 {
+            Shape::Polyline(line) => {
+                assert_eq!(line.parts()[0][1], shapefile::Point::new(3.0, 4.0))
+            }
+            other => panic!("expected a polyline, got {}", other),
+        }
+    }
+
+    #[test]
+    fn writes_one_shapefile_per_partition_value() {
+        let dir = temp_output("partitioned");
+        let _ = std::fs::remove_dir_all(&dir);
+        let contents = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"county_fips": "001", "name": "a"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"county_fips": "003", "name": "b"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5.0, 6.0]}, "properties": {"county_fips": "001", "name": "c"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [7.0, 8.0]}, "properties": {"name": "d"}}
+        ]}"#;
+        let config = FeatureCollectionToShpWriterConfig::new(contents, "unused").unwrap();
+        let written = config.write_partitioned(&dir, "county_fips").unwrap();
+        let counts: Vec<_> = written
+            .iter()
+            .map(|(value, (name, summary))| {
+                (value.as_deref(), name.as_str(), summary.features_written)
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (None, UNKNOWN_PARTITION, 1),
+                (Some("001"), "001", 2),
+                (Some("003"), "003", 1)
+            ]
+        );
+
+        let records = shapefile::dbase::read(Path::new(&dir).join("001.dbf")).unwrap();
+        let names: Vec<_> = records.iter().map(|record| record.get("name")).collect();
+        assert_eq!(
+            names,
+            [
+                Some(&FieldValue::Character(Some("a".to_string()))),
+                Some(&FieldValue::Character(Some("c".to_string())))
+            ]
+        );
+        assert!(Path::new(&dir).join("_unknown.shp").is_file());
+    }
+
+    #[test]
+    fn tells_apart_partition_values_that_make_the_same_file_name() {
+        let dir = temp_output("partition_collisions");
+        let _ = std::fs::remove_dir_all(&dir);
+        let feature = |value: &str| {
+            format!(
+                r#"{{"type": "Feature", "geometry": {{"type": "Point", "coordinates": [1.0, 2.0]}}, "properties": {{"road": {}}}}}"#,
+                value
+            )
+        };
+        let values = [
+            r#""a/b""#,
+            r#""a_b""#,
+            r#""Road""#,
+            r#""road""#,
+            r#""_unknown""#,
+            "null",
+        ];
+        let features: Vec<_> = values.iter().map(|value| feature(value)).collect();
+        let contents = format!(
+            r#"{{"type": "FeatureCollection", "features": [{}]}}"#,
+            features.join(",")
+        );
+        let config = FeatureCollectionToShpWriterConfig::new(&contents, "unused").unwrap();
+        let written = config.write_partitioned(&dir, "road").unwrap();
+        let names: Vec<_> = written
+            .iter()
+            .map(|(value, (name, summary))| {
+                assert_eq!(summary.features_written, 1);
+                (value.as_deref(), name.as_str())
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (None, "_unknown"),
+                (Some("Road"), "Road"),
+                (Some("_unknown"), "_unknown_001"),
+                (Some("a/b"), "a_b"),
+                (Some("a_b"), "a_b_001"),
+                (Some("road"), "road_001")
+            ]
+        );
+        for (name, _) in written.values() {
+            assert!(Path::new(&dir).join(format!("{}.shp", name)).is_file());
+        }
+    }
+
+    #[test]
+    fn discards_the_output_of_a_writer_dropped_unfinished() {
+        let output = temp_output("dropped");
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let mut writer = FeatureCollectionToShpWriter::new(contents, &output).unwrap();
+        writer.write().unwrap();
+        drop(writer);
+        let dir = Path::new(&output).parent().unwrap();
+        let left: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("dropped.") || name.starts_with(".dropped."))
+            .collect();
+        assert!(left.is_empty(), "left behind {:?}", left);
+    }
+
+    #[test]
+    fn removes_the_output_files_of_a_failed_conversion() {
+        let output = temp_output("partial");
+        let exists = |extension: &str| Path::new(&format!("{}.{}", output, extension)).exists();
+        for extension in ["shp", "shx", "dbf", "cpg"] {
+            let _ = std::fs::remove_file(format!("{}.{}", output, extension));
+        }
+        let contents = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"rank": 1}},
+            {"type": "Feature", "geometry": {"type": "GeometryCollection", "geometries": []}, "properties": {"rank": 2}}
+        ]}"#;
+        let options = ConversionOptions::default().encoding(encoding_rs::WINDOWS_1252);
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            contents.to_string(),
+            &output,
+            options.clone(),
+        )
+        .unwrap();
+        assert!(writer.write().is_err());
+        writer.finish().unwrap();
+        for extension in ["shp", "shx", "dbf", "cpg"] {
+            assert!(!exists(extension), ".{} was left behind", extension);
+        }
+        let temporary_files = std::fs::read_dir(Path::new(&output).parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".partial.")
+            })
+            .count();
+        assert_eq!(temporary_files, 0);
+
+        let options = ConversionOptions {
+            keep_partial: true,
+            ..options
+        };
+        assert!(convert_with_options(contents, &output, options).is_err());
+        assert!(exists("shp") && exists("dbf"));
+
+        // Creating the writer fails after the files are created: the schema has a field name
+        // the dbf cannot hold.
+        let output = temp_output("partial_schema");
+        let schema = FieldSchema {
+            fields: vec![FieldDefinition {
+                property: "rank".to_string(),
+                name: "a_much_too_long_name".to_string(),
+                field_type: DbfType::Numeric,
+                width: 4,
+                decimals: 0,
+                part: None,
+            }],
+        };
+        let result = FeatureCollectionToShpWriter::from_reader_with_schema(
+            contents.as_bytes(),
+            &output,
+            schema,
+            ConversionOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(ConversionError::InvalidFieldName { .. })
+        ));
+        assert!(!Path::new(&format!("{}.shp", output)).exists());
+    }
+
+    #[test]
+    fn computes_field_stats_without_writing() {
+        let contents = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": null, "properties": {"name": "Oak", "rank": 3}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "Main Street", "rank": -1.5}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "Oak", "rank": null}}
+        ]}"#;
+        let writer =
+            FeatureCollectionToShpWriter::new(contents.to_string(), &temp_output("stats")).unwrap();
+        let stats = writer.compute_stats();
+        assert_eq!(
+            stats["name"],
+            FieldStat {
+                min: None,
+                max: None,
+                null_count: 0,
+                unique_count: 2,
+                max_str_len: Some(11),
+            }
+        );
+        assert_eq!(
+            stats["rank"],
+            FieldStat {
+                min: Some(-1.5),
+                max: Some(3.0),
+                null_count: 1,
+                unique_count: 2,
+                max_str_len: None,
+            }
+        );
+    }
+
+    #[test]
+    fn moves_the_files_into_place_once_finished() {
+        let output = temp_output("atomic");
+        let shp = format!("{}.shp", output);
+        let _ = std::fs::remove_file(&shp);
+        let mut writer =
+            FeatureCollectionToShpWriter::new(NULL_GEOMETRY_COLLECTION.to_string(), &output)
+                .unwrap();
+        writer.write().unwrap();
+        assert!(!Path::new(&shp).exists());
+        writer.finish().unwrap();
+        assert_eq!(shapefile::read(&shp).unwrap().len(), 2);
+
+        // An existing shapefile is replaced.
+        let options = ConversionOptions::default().overwrite(true);
+        convert_with_options("./fixtures/points.geojson", &output, options).unwrap();
+        assert_eq!(shapefile::read(&shp).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn creates_missing_output_directories_when_asked() {
+        let dir = temp_output("nested");
+        let _ = std::fs::remove_dir_all(&dir);
+        let output = Path::new(&dir).join("2024").join("parcels");
+        let output = output.to_str().unwrap();
+
+        match convert("./fixtures/points.geojson", output) {
+            Err(ConversionError::MissingOutputDirectory { directory, path }) => {
+                assert_eq!(directory, Path::new(&dir).join("2024"));
+                assert_eq!(path, Path::new(&format!("{}.shp", output)));
+            }
+            other => panic!("expected MissingOutputDirectory, got {:?}", other),
+        }
+        assert!(!Path::new(&dir).exists());
+
+        let options = ConversionOptions {
+            create_dirs: true,
+            ..Default::default()
+        };
+        convert_with_options("./fixtures/points.geojson", output, options).unwrap();
+        assert_eq!(shapefile::read(format!("{}.shp", output)).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn writes_a_wkt_sidecar_line_per_record() {
+        let output = temp_output("wkt_sidecar");
+        let options = ConversionOptions {
+            null_geometry_behavior: NullGeometryBehavior::WriteNullShape,
+            write_wkt_sidecar: true,
+            ..Default::default()
+        };
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            NULL_GEOMETRY_COLLECTION.to_string(),
+            &output,
+            options,
+        )
+        .unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let wkt = std::fs::read_to_string(format!("{}.wkt", output)).unwrap();
+        let records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(wkt.lines().count(), records.len());
+        assert_eq!(
+            wkt.lines().collect::<Vec<_>>(),
+            ["POINT (1 2)", "GEOMETRYCOLLECTION EMPTY", "POINT (3 4)"]
+        );
+    }
+
+    #[test]
+    fn writes_a_manifest_of_the_files_written() {
+        let output = temp_output("manifest");
+        let options = ConversionOptions {
+            write_wkt_sidecar: true,
+            ..Default::default()
+        };
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            NULL_GEOMETRY_COLLECTION.to_string(),
+            &output,
+            options,
+        )
+        .unwrap();
+        writer.write().unwrap();
+        let summary = writer.finish().unwrap();
+        let manifest_path = format!("{}.manifest.json", output);
+        summary.write_manifest(&manifest_path).unwrap();
+
+        let manifest: JsonValue =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        let entries = manifest["files"].as_array().unwrap();
+        let mut paths: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry["path"].as_str().unwrap())
+            .collect();
+        paths.sort();
+        let mut expected: Vec<String> = ["cpg", "dbf", "prj", "shp", "shx", "wkt"]
+            .iter()
+            .map(|extension| format!("{}.{}", output, extension))
+            .collect();
+        expected.sort();
+        assert_eq!(paths, expected);
+        use sha2::{Digest, Sha256};
+        for entry in entries {
+            let bytes = std::fs::read(entry["path"].as_str().unwrap()).unwrap();
+            let sha256 = format!("{:x}", Sha256::digest(&bytes));
+            assert_eq!(entry["sha256"], sha256);
+            assert_eq!(entry["bytes"], bytes.len());
+        }
+    }
+
+    #[test]
+    fn writes_the_bbox_as_a_polygon_shapefile() {
+        let output = temp_output("bbox");
+        let contents = r#"{"type": "FeatureCollection", "bbox": [-10.0, -5.0, 20.0, 15.0], "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[3.0, -4.0], [-6.0, 7.0]]}, "properties": {}}
+        ]}"#;
+        let writer =
+            FeatureCollectionToShpWriter::new(contents.to_string(), &temp_output("bbox_source"))
+                .unwrap();
+        assert_eq!(
+            writer.write_bbox(&output).unwrap(),
+            [-10.0, -5.0, 20.0, 15.0]
+        );
+        let shape_records = shapefile::read_as::<_, shapefile::Polygon, shapefile::dbase::Record>(
+            format!("{}.shp", output),
+        )
+        .unwrap();
+        assert_eq!(shape_records.len(), 1);
+        let (polygon, record) = &shape_records[0];
+        assert_eq!(polygon.rings().len(), 1);
+        let bbox = polygon.bbox();
+        assert_eq!(
+            [bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y],
+            [-10.0, -5.0, 20.0, 15.0]
+        );
+        assert_eq!(record.get("max_y"), Some(&FieldValue::Numeric(Some(15.0))));
+        assert!(matches!(
+            writer.write_bbox(&output),
+            Err(ConversionError::OutputExists { .. })
+        ));
+        writer.finish().unwrap();
+
+        // Without a bbox, the extent of the features is written.
+        let contents = contents.replace(r#""bbox": [-10.0, -5.0, 20.0, 15.0], "#, "");
+        let writer = FeatureCollectionToShpWriter::with_options(
+            contents,
+            &temp_output("bbox_source"),
+            ConversionOptions::default().overwrite(true),
+        )
+        .unwrap();
+        assert_eq!(writer.write_bbox(&output).unwrap(), [-6.0, -4.0, 3.0, 7.0]);
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn writes_only_the_dbf() {
+        let output = temp_output("dbf_only");
+        // The second geometry cannot be converted, which does not matter without a .shp.
+        let contents = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a", "rank": 1}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[3.0, 4.0]]}, "properties": {"name": "b", "rank": 2}},
+            {"type": "Feature", "geometry": null, "properties": {"name": "c", "rank": 3}}
+        ]}"#;
+        let config = FeatureCollectionToShpWriterConfig::new(contents, "unused").unwrap();
+        let summary = config.write_dbf_only(&format!("{}.dbf", output)).unwrap();
+        assert_eq!(summary.features_written, 3);
+        assert_eq!(
+            summary.files,
+            [
+                PathBuf::from(format!("{}.cpg", output)),
+                PathBuf::from(format!("{}.dbf", output))
+            ]
+        );
+        for extension in ["shp", "shx", "prj"] {
+            assert!(!Path::new(&format!("{}.{}", output, extension)).exists());
+        }
+
+        let records = shapefile::dbase::read(format!("{}.dbf", output)).unwrap();
+        let names: Vec<_> = records
+            .iter()
+            .map(|record| match record.get("name") {
+                Some(FieldValue::Character(Some(name))) => name.as_str(),
+                other => panic!("unexpected name {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(
+            records[2].get("rank"),
+            Some(&FieldValue::Numeric(Some(3.0)))
+        );
+        assert!(matches!(
+            config.write_dbf_only(&output),
+            Err(ConversionError::OutputExists { .. })
+        ));
+    }
+
+    #[test]
+    fn refuses_to_write_the_features_twice() {
+        let output = temp_output("written_twice");
+        let mut writer =
+            FeatureCollectionToShpWriter::new(NULL_GEOMETRY_COLLECTION.to_string(), &output)
+                .unwrap();
+        writer.write().unwrap();
+        assert!(matches!(
+            writer.write(),
+            Err(ConversionError::AlreadyWritten)
+        ));
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.features_written, 2);
+        assert_eq!(shapefile::read(format!("{}.shp", output)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn stops_writing_when_cancelled() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let output = temp_output("cancelled");
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ConversionOptions::default().cancel(Arc::clone(&cancel));
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents, &output, options).unwrap();
+        let features = writer.feature_collection.features.clone();
+        assert!(features.len() > 2);
+        // The flag is set as the third feature is read.
+        let result = writer.write_from(features.iter().enumerate().map(|(index, feature)| {
+            if index == 2 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            Ok(feature)
+        }));
+        assert!(matches!(result, Err(ConversionError::Cancelled)));
+        assert_eq!(writer.summary().features_written, 2);
+        writer.finish().unwrap();
+        assert!(!Path::new(&format!("{}.shp", output)).exists());
+        assert!(!Path::new(&format!("{}.dbf", output)).exists());
+    }
+
+    #[test]
+    fn tells_the_paths_of_its_files() {
+        let output = temp_output("paths");
+        let writer =
+            FeatureCollectionToShpWriter::new(NULL_GEOMETRY_COLLECTION.to_string(), &output)
+                .unwrap();
+        assert_eq!(writer.shp_path(), Path::new(&format!("{}.shp", output)));
+        assert_eq!(
+            writer.shx_path(),
+            Some(Path::new(&format!("{}.shx", output)))
+        );
+        assert_eq!(writer.dbf_path(), Path::new(&format!("{}.dbf", output)));
+        assert_eq!(
+            writer.cpg_path(),
+            Some(Path::new(&format!("{}.cpg", output)))
+        );
+        assert_eq!(
+            writer.prj_path(),
+            Some(Path::new(&format!("{}.prj", output)))
+        );
+        let paths: Vec<PathBuf> = [writer.shp_path(), writer.dbf_path()]
+            .into_iter()
+            .chain(writer.prj_path())
+            .map(Path::to_path_buf)
+            .collect();
+        writer.finish().unwrap();
+        assert!(paths.iter().all(|path| path.is_file()));
+
+        let output = temp_output("paths_without_index");
+        let options = ConversionOptions {
+            skip_index: true,
+            write_cpg: false,
+            write_prj: false,
+            ..ConversionOptions::default()
+        };
+        let writer = FeatureCollectionToShpWriter::with_options(
+            NULL_GEOMETRY_COLLECTION.to_string(),
+            &format!("{}.shp", output),
+            options,
+        )
+        .unwrap();
+        assert_eq!(writer.shp_path(), Path::new(&format!("{}.shp", output)));
+        assert_eq!(writer.shx_path(), None);
+        assert_eq!(writer.cpg_path(), None);
+        assert_eq!(writer.prj_path(), None);
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn writes_one_feature_per_call() {
+        let mut contents: JsonValue =
+            serde_json::from_str(&std::fs::read_to_string("./fixtures/points.geojson").unwrap())
+                .unwrap();
+        let features = contents["features"].as_array_mut().unwrap();
+        let count = features.len();
+        features.insert(1, serde_json::json!({"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}, "properties": {}}));
+
+        let output = temp_output("write_next");
+        let mut writer = FeatureCollectionToShpWriter::new(contents.to_string(), &output).unwrap();
+        let results: Vec<_> = writer.write_each().collect();
+        assert_eq!(results.len(), count + 1);
+        assert_eq!(
+            results.iter().filter(|result| result.is_ok()).count(),
+            count
+        );
+        assert!(matches!(
+            &results[1],
+            Err(ConversionError::Feature { index: 1, source })
+                if matches!(**source, ConversionError::MixedShapeTypes { .. })
+        ));
+        assert!(writer.write_next().is_none());
+        assert!(matches!(
+            writer.write(),
+            Err(ConversionError::AlreadyWritten)
+        ));
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.features_written, count);
+        assert_eq!(summary.centroids.len(), 1);
+        assert_eq!(
+            shapefile::read(format!("{}.shp", output)).unwrap().len(),
+            count
+        );
+    }
+
+    #[test]
+    fn refuses_to_overwrite_existing_outputs() {
+        let output = temp_output("existing");
+        let options = ConversionOptions::default().encoding(encoding_rs::WINDOWS_1252);
+        convert_with_options("./fixtures/points.geojson", &output, options.clone()).unwrap();
+        std::fs::remove_file(format!("{}.shp", output)).unwrap();
+        let dbf = std::fs::metadata(format!("{}.dbf", output)).unwrap();
+
+        match convert_with_options("./fixtures/lines.geojson", &output, options.clone()) {
+            Err(ConversionError::OutputExists { paths }) => assert_eq!(
+                paths,
+                [
+                    format!("{}.dbf", output),
+                    format!("{}.shx", output),
+                    format!("{}.cpg", output),
+                    format!("{}.prj", output)
+                ]
+            ),
+            other => panic!("expected OutputExists, got {:?}", other),
+        }
+        assert!(!Path::new(&format!("{}.shp", output)).exists());
+        let unchanged = std::fs::metadata(format!("{}.dbf", output)).unwrap();
+        assert_eq!(unchanged.modified().unwrap(), dbf.modified().unwrap());
+
+        convert_with_options("./fixtures/lines.geojson", &output, options.overwrite(true)).unwrap();
+        assert!(matches!(
+            shapefile::read(format!("{}.shp", output)).unwrap()[0].0,
+            Shape::Polyline(_)
+        ));
+    }
+
+    #[test]
+    fn infers_the_shape_type_of_a_collection() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let collection = parse_feature_collection(&contents).unwrap();
+        assert_eq!(infer_shape_type(&collection).unwrap(), ShapeType::Point);
+
+        let collection = parse_feature_collection(NULL_GEOMETRY_COLLECTION).unwrap();
+        assert_eq!(infer_shape_type(&collection).unwrap(), ShapeType::Point);
+
+        let mixed = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}, "properties": {}}
+        ]"#;
+        let collection = parse_feature_collection(mixed).unwrap();
+        assert!(matches!(
+            infer_shape_type(&collection),
+            Err(ConversionError::Feature { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rolls_over_to_a_new_shapefile_at_the_size_limit() {
+        let output = temp_output("chunked");
+        temp_output("chunked_001");
+        temp_output("chunked_002");
+        let features: Vec<String> = (0..10)
+            .map(|i| {
+                format!(
+                    r#"{{"type": "Feature", "geometry": {{"type": "Point", "coordinates": [{}.0, 1.0]}}, "properties": {{"rank": {}}}}}"#,
+                    i, i
+                )
+            })
+            .collect();
+        let contents = format!("[{}]", features.join(","));
+        // The .shp header takes 100 bytes and each point record 28 more: four points fit.
+        let options = ConversionOptions {
+            max_file_size: Some(100 + 4 * 28),
+            ..Default::default()
+        };
+
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents.clone(), &output, options.clone())
+                .unwrap();
+        assert!(matches!(
+            writer.write().unwrap_err(),
+            ConversionError::Feature { index: 4, source } if matches!(*source, ConversionError::FileSizeLimit { .. })
+        ));
+
+        let written = FeatureCollectionToShpWriterConfig::new(&contents, &output)
+            .unwrap()
+            .options(options)
+            .write_chunked()
+            .unwrap();
+        let chunks: Vec<_> = written
+            .iter()
+            .map(|(path, summary)| (path.clone(), summary.features_written))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                (output.clone(), 4),
+                (format!("{}_001", output), 4),
+                (format!("{}_002", output), 2)
+            ]
+        );
+        let shapes = shapefile::read(format!("{}_001.shp", output)).unwrap();
+        assert_eq!(shapes.len(), 4);
+        assert_eq!(
+            shapes[0].1.get("rank"),
+            Some(&FieldValue::Numeric(Some(4.0)))
+        );
+    }
+
+    #[test]
+    fn tells_the_input_index_of_features_skipped_after_a_rollover() {
+        let output = temp_output("chunked_skip");
+        temp_output("chunked_skip_001");
+        temp_output("chunked_skip_002");
+        // Feature 5, in the second chunk, has a position without a latitude.
+        let features: Vec<String> = (0..10)
+            .map(|i| {
+                format!(
+                    r#"{{"type": "Feature", "geometry": {{"type": "Point", "coordinates": [{}.0{}]}}, "properties": {{"rank": {}}}}}"#,
+                    i,
+                    if i == 5 { "" } else { ", 1.0" },
+                    i
+                )
+            })
+            .collect();
+        let contents = format!("[{}]", features.join(","));
+        let options = ConversionOptions {
+            max_file_size: Some(100 + 4 * 28),
+            ..Default::default()
+        };
+        let config = FeatureCollectionToShpWriterConfig::new(&contents, &output).unwrap();
+
+        let err = config.clone().options(options.clone()).write_chunked();
+        assert!(matches!(
+            err,
+            Err(ConversionError::Feature { index: 5, source }) if matches!(*source, ConversionError::InvalidGeometry { .. })
+        ));
+
+        let skip = ConversionOptions {
+            on_error: ErrorPolicy::Skip,
+            overwrite: true,
+            ..options
+        };
+        let written = config.options(skip).write_chunked().unwrap();
+        let skipped: Vec<_> = written
+            .iter()
+            .flat_map(|(_, summary)| summary.skipped_features.iter())
+            .map(|skipped| skipped.index)
+            .collect();
+        assert_eq!(skipped, [5]);
+        assert!(written[0].1.skipped_features.is_empty());
+    }
+
+    #[test]
+    fn leaves_out_the_index_when_asked() {
+        let output = temp_output("no_index");
+        let _ = std::fs::remove_file(format!("{}.shx", output));
+        let options = ConversionOptions {
+            skip_index: true,
+            ..Default::default()
+        };
+        convert_with_options("./fixtures/points.geojson", &output, options).unwrap();
+        assert!(Path::new(&format!("{}.shp", output)).is_file());
+        assert!(Path::new(&format!("{}.dbf", output)).is_file());
+        assert!(!Path::new(&format!("{}.shx", output)).exists());
+
+        let shapes = shapefile::ShapeReader::from_path(format!("{}.shp", output))
+            .unwrap()
+            .read()
+            .unwrap();
+        assert_eq!(shapes.len(), 3);
+    }
+
+    #[test]
+    fn reports_the_mean_centroid_per_geometry_type() {
+        let summary = convert("./fixtures/points.geojson", &temp_output("centroids")).unwrap();
+        let [x, y] = summary.centroids["Point"];
+        assert!((x - 101.333_333).abs() < 1e-6);
+        assert!((y - 0.166_667).abs() < 1e-6);
+        assert_eq!(summary.centroids.len(), 1);
+    }
+
+    #[test]
+    fn skips_duplicate_features_when_deduping() {
+        let output = temp_output("dedupe");
+        let feature = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a", "rank": 1}}"#;
+        let reordered = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"rank": 1, "name": "a"}}"#;
+        let contents = format!(
+            r#"{{"type": "FeatureCollection", "features": [{}, {}]}}"#,
+            feature, reordered
+        );
+        let options = ConversionOptions::default().dedupe(true);
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents, &output, options).unwrap();
+        writer.write().unwrap();
+        assert_eq!(writer.summary().features_written, 1);
+        assert_eq!(writer.summary().duplicates_skipped, 1);
+        assert_eq!(
+            writer.summary().conversion_warnings,
+            [ConversionWarning::DuplicateFeatureSkipped { feature_index: 1 }]
+        );
+        writer.finish().unwrap();
+
+        let records = shapefile::dbase::read(format!("{}.dbf", output)).unwrap();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn does_not_dedupe_against_features_that_failed() {
+        let line = r#"{"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}, "properties": {"rank": 1}}"#;
+        let point = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"rank": 2}}"#;
+        let contents = format!(
+            r#"{{"type": "FeatureCollection", "features": [{}, {}, {}]}}"#,
+            line, point, point
+        );
+        let options = ConversionOptions {
+            on_error: ErrorPolicy::Skip,
+            ..Default::default()
+        }
+        .dedupe(true);
+        let output = temp_output("dedupe_failed");
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents, &output, options).unwrap();
+        writer.write().unwrap();
+        let skipped: Vec<_> = writer
+            .summary()
+            .skipped_features
+            .iter()
+            .map(|skipped| skipped.index)
+            .collect();
+        assert_eq!(skipped, vec![1, 2]);
+        assert_eq!(writer.summary().duplicates_skipped, 0);
+        assert_eq!(writer.summary().features_written, 1);
+    }
+
+    #[test]
+    fn infers_the_schema_from_a_sample_of_features() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "b", "late": 1}}
+            ]
+        }"#;
+        let options = ConversionOptions {
+            schema_sample: SchemaSample::First(1),
+            ..Default::default()
+        };
+        let output = temp_output("schema_sample");
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            contents.to_string(),
+            &output,
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(writer.schema().fields.len(), 1);
+        writer.write().unwrap();
+        assert_eq!(writer.summary().features_written, 2);
+        assert_eq!(writer.warnings().len(), 1);
+        assert!(writer.warnings()[0].contains("\"late\""));
+
+        let options = ConversionOptions {
+            strict_schema: true,
+            ..options
+        };
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents.to_string(), &output, options)
+                .unwrap();
+        let err = writer.write().unwrap_err();
+        assert_eq!(err.feature_index(), Some(1));
+        assert!(matches!(
+            err.without_context(),
+            ConversionError::UnexpectedProperty { .. }
+        ));
+    }
+
+    #[test]
+    fn reuses_a_config_for_several_conversions() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let config = FeatureCollectionToShpWriterConfig::new(&contents, &temp_output("config_a"))
+            .unwrap()
+            .options(ConversionOptions::default().dedupe(true));
+        assert!(format!("{:?}", config).contains("dedupe: true"));
+
+        let mut second = config.clone();
+        second.filepath = temp_output("config_b");
+        for config in [config, second] {
+            let mut writer = config.writer().unwrap();
+            writer.write().unwrap();
+            assert_eq!(writer.summary().features_written, 3);
+        }
+    }
+
+    #[test]
+    fn validates_and_promotes_the_feature_id_field() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a", "parcel_apn": "001"}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "b", "parcel_apn": "002"}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5.0, 6.0]}, "properties": {"name": "c", "parcel_apn": "001"}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [7.0, 8.0]}, "properties": {"name": "d"}}
+            ]
+        }"#;
+        let output = temp_output("feature_id");
+        let options = ConversionOptions {
+            feature_id_field: Some("parcel_apn".to_string()),
+            ..Default::default()
+        };
+        let result = FeatureCollectionToShpWriter::with_options(
+            contents.to_string(),
+            &output,
+            options.clone(),
+        );
+        assert!(matches!(
+            result,
+            Err(ConversionError::MissingFeatureId { indices, .. }) if indices == vec![3]
+        ));
+
+        let options = ConversionOptions {
+            require_unique_id: true,
+            ..options.filter(|feature| feature.contains_property("parcel_apn"))
+        };
+        let result = FeatureCollectionToShpWriter::with_options(
+            contents.to_string(),
+            &output,
+            options.clone(),
+        );
+        assert!(matches!(
+            result,
+            Err(ConversionError::DuplicateFeatureId { indices, .. }) if indices == vec![2]
+        ));
+
+        let options = ConversionOptions {
+            require_unique_id: false,
+            ..options
+        };
+        let writer =
+            FeatureCollectionToShpWriter::with_options(contents.to_string(), &output, options)
+                .unwrap();
+        assert_eq!(writer.schema().fields[0].name, "parcel_apn");
+    }
+
+    #[cfg(feature = "esri-metadata")]
+    #[test]
+    fn writes_an_esri_metadata_sidecar() {
+        let output = temp_output("esri_metadata");
+        let options = ConversionOptions {
+            esri_metadata: true,
+            ..Default::default()
+        };
+        convert_with_options("./fixtures/points.geojson", &output, options).unwrap();
+        let xml = std::fs::read_to_string(format!("{}.shp.xml", output)).unwrap();
+        assert!(xml.contains("<attrlabl>category</attrlabl>"));
+        assert!(xml.contains("<efeacnt>3</efeacnt>"));
+    }
+
+    #[cfg(feature = "topojson")]
+    #[test]
+    fn converts_a_topojson_fixture() {
+        let output = temp_output("topojson");
+        let summary = convert("./fixtures/roads.topojson", &output).unwrap();
+        assert_eq!(summary.features_written, 3);
+
+        let shapes_and_records = shapefile::read_as::<
+            _,
+            shapefile::Polyline,
+            shapefile::dbase::Record,
+        >(format!("{}.shp", output))
+        .unwrap();
+        let (line, _) = &shapes_and_records[0];
+        assert_eq!(line.parts()[0].len(), 3);
+        assert_eq!(
+            shapes_and_records[1].1.get("name"),
+            Some(&FieldValue::Character(Some("Oak Ave".to_string())))
+        );
+    }
+
+    #[test]
+    fn wraps_a_lone_feature_or_geometry_in_a_collection() {
+        let feature = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}}"#;
+        let mut writer =
+            FeatureCollectionToShpWriter::new(feature.to_string(), &temp_output("lone_feature"))
+                .unwrap();
+        writer.write().unwrap();
+        assert_eq!(writer.summary().features_written, 1);
+        assert_eq!(writer.schema().fields[0].name, "name");
+        let schema = infer_schema(feature, &ConversionOptions::default()).unwrap();
+        assert_eq!(schema, *writer.schema());
+
+        let geometry = r#"{"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}"#;
+        let mut writer =
+            FeatureCollectionToShpWriter::new(geometry.to_string(), &temp_output("lone_geometry"))
+                .unwrap();
+        writer.write().unwrap();
+        assert_eq!(writer.summary().features_written, 1);
+        assert!(writer.schema().is_placeholder());
+    }
+
+    #[test]
+    fn converts_a_bare_point_or_polygon() {
+        let point = r#"{"type": "Point", "coordinates": [-73.98, 40.75]}"#;
+        let output = temp_output("bare_point");
+        convert(point, &output).unwrap();
+        let shapes = shapefile::ShapeReader::from_path(format!("{}.shp", output))
+            .unwrap()
+            .read()
+            .unwrap();
+        assert!(
+            matches!(shapes[..], [Shape::Point(point)] if point.x == -73.98 && point.y == 40.75)
+        );
+
+        let polygon = r#"{"type": "Polygon", "coordinates": [
+            [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]],
+            [[2.0, 2.0], [2.0, 4.0], [4.0, 4.0], [4.0, 2.0], [2.0, 2.0]]
+        ]}"#;
+        let output = temp_output("bare_polygon");
+        let summary = convert(polygon, &output).unwrap();
+        assert_eq!(summary.features_written, 1);
+        let shapes_and_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        let (shape, record) = &shapes_and_records[0];
+        let rings = match shape {
+            Shape::Polygon(polygon) => polygon.rings(),
+            other => panic!("expected a polygon, got {}", other),
+        };
+        assert!(matches!(
+            rings,
+            [
+                shapefile::PolygonRing::Outer(_),
+                shapefile::PolygonRing::Inner(_)
+            ]
+        ));
+        assert!(record.get(FieldSchema::PLACEHOLDER_FIELD).is_some());
+    }
+
+    #[test]
+    fn accepts_a_bare_array_of_features() {
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "a"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}}
+        ]"#;
+        let summary = convert(contents, &temp_output("feature_array")).unwrap();
+        assert_eq!(summary.features_written, 2);
+
+        let summary = convert(" [ ] ", &temp_output("feature_array")).unwrap();
+        assert_eq!(summary.features_written, 0);
+
+        let err = convert(
+            r#"[{"type": "Point", "coordinates": [1.0, 2.0]}]"#,
+            &temp_output("feature_array"),
+        );
+        assert!(matches!(err, Err(ConversionError::Parse { .. })));
+    }
+
+    #[test]
+    fn reports_colliding_field_names() {
+        let contents = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"population_2010": 1, "population_2020": 2}}"#;
+        let writer =
+            FeatureCollectionToShpWriter::new(contents.to_string(), &temp_output("collisions"))
+                .unwrap();
+        assert!(writer
+            .schema_warnings()
+            .iter()
+            .any(|warning| matches!(warning, SchemaWarning::NameCollision { name, .. } if name == "populati_1")));
+    }
+
+    #[test]
+    fn converts_every_file_matching_a_glob() {
+        let output_dir = temp_output("glob");
+        let options = ConversionOptions::default().overwrite(true);
+        let converted = convert_glob("./fixtures/[pl]*.geojson", &output_dir, &options).unwrap();
+        let names: Vec<_> = converted
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["lines.geojson", "points.geojson"]);
+        assert!(Path::new(&output_dir).join("points.shp").exists());
+        assert!(Path::new(&output_dir).join("lines.dbf").exists());
+
+        let nothing = convert_glob("./fixtures/*.nope", &output_dir, &Default::default());
+        assert!(nothing.unwrap().is_empty());
+    }
+
+    #[test]
+    fn estimates_the_output_size() {
+        for fixture in ["points", "lines", "geometry_only"] {
+            let contents =
+                std::fs::read_to_string(format!("./fixtures/{}.geojson", fixture)).unwrap();
+            let estimate = estimate_output_bytes(&contents).unwrap();
+            let bytes = convert_to_bytes(&contents, &ConversionOptions::default()).unwrap();
+            assert_eq!(estimate.shp, bytes.shp.len() as u64, "{}", fixture);
+            assert_eq!(estimate.shx, bytes.shx.len() as u64, "{}", fixture);
+            assert_eq!(estimate.dbf, bytes.dbf.len() as u64, "{}", fixture);
+        }
+    }
+
+    #[test]
+    fn converts_to_bytes_in_memory() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let bytes = convert_to_bytes(&contents, &ConversionOptions::default()).unwrap();
+
+        let shapes = shapefile::ShapeReader::with_shx(
+            Cursor::new(bytes.shp.clone()),
+            Cursor::new(bytes.shx.clone()),
+        )
+        .unwrap()
+        .read()
+        .unwrap();
+        assert_eq!(shapes.len(), 3);
+        let records = shapefile::dbase::Reader::new(Cursor::new(bytes.dbf))
+            .unwrap()
+            .read()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(bytes.cpg.as_deref(), Some("UTF-8"));
+        assert!(bytes.prj.unwrap().starts_with("GEOGCS[\"GCS_WGS_1984\""));
+
+        let options = ConversionOptions {
+            write_cpg: false,
+            write_prj: false,
+            ..Default::default()
+        };
+        let bytes = convert_to_bytes(&contents, &options).unwrap();
+        assert_eq!((bytes.cpg, bytes.prj), (None, None));
+    }
+
+    #[test]
+    fn writes_character_fields_in_the_requested_encoding() {
+        let contents = r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [8.5, 47.4]}, "properties": {"city": "Zürich"}}"#;
+        let latin1 = Encoding::for_label(b"latin1").unwrap();
+        let options = ConversionOptions::default().encoding(latin1);
+        let bytes = convert_to_bytes(contents, &options).unwrap();
+        assert_eq!(bytes.cpg.as_deref(), Some("1252"));
+        assert!(bytes.dbf.windows(6).any(|window| window == b"Z\xfcrich"));
+        assert!(!bytes
+            .dbf
+            .windows(7)
+            .any(|window| window == "Zürich".as_bytes()));
+
+        let contents = contents.replace("Zürich", "Łódź");
+        let bytes = convert_to_bytes(&contents, &options).unwrap();
+        assert!(bytes.dbf.windows(4).any(|window| window == b"?\xf3d?"));
+
+        let options = ConversionOptions {
+            unencodable: UnencodablePolicy::Error,
+            ..options
+        };
+        assert!(matches!(
+            convert_to_bytes(&contents, &options)
+                .unwrap_err()
+                .without_context(),
+            ConversionError::UnencodableCharacter {
+                character: 'Ł', ..
+            }
+        ));
+    }
+
+    #[test]
+    fn applies_the_long_text_policy_to_a_400_character_value() {
+        let description = "d".repeat(400);
+        let contents = format!(
+            r#"{{"type": "Feature", "geometry": {{"type": "Point", "coordinates": [1.0, 2.0]}}, "properties": {{"desc": "{}"}}}}"#,
+            description
+        );
+        let options = ConversionOptions {
+            long_text: LongTextPolicy::Error,
+            ..Default::default()
+        };
+        assert!(matches!(
+            convert_to_bytes(&contents, &options)
+                .unwrap_err()
+                .without_context(),
+            ConversionError::TextTooLong { property, len: 400, limit: 254 } if property == "desc"
+        ));
+
+        let options = ConversionOptions {
+            long_text: LongTextPolicy::Split { max_fields: 4 },
+            ..Default::default()
+        };
+        let bytes = convert_to_bytes(&contents, &options).unwrap();
+        let records = shapefile::dbase::Reader::new(Cursor::new(bytes.dbf))
+            .unwrap()
+            .read()
+            .unwrap();
+        let joined: String = ["desc_1", "desc_2"]
+            .iter()
+            .map(|name| match records[0].get(name) {
+                Some(FieldValue::Character(Some(text))) => text.clone(),
+                other => panic!("unexpected {}: {:?}", name, other),
+            })
+            .collect();
+        assert_eq!(joined, description);
+    }
+
+    #[test]
+    fn applies_the_inexact_number_policy() {
+        let contents = r#"[
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"id": 18446744073709551615}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"id": 9007199254740992}}
+        ]"#;
+        let ids = |options: &ConversionOptions| {
+            let bytes = convert_to_bytes(contents, options).unwrap();
+            shapefile::dbase::Reader::new(Cursor::new(bytes.dbf))
+                .unwrap()
+                .read()
+                .unwrap()
+                .iter()
+                .map(|record| match record.get("id") {
+                    Some(FieldValue::Numeric(value)) => *value,
+                    other => panic!("unexpected {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&ConversionOptions::default()),
+            [Some(18446744073709551615.0), Some(9007199254740992.0)]
+        );
+        let sentinel = ConversionOptions {
+            inexact_numbers: InexactNumberPolicy::Sentinel(-9999.0),
+            ..Default::default()
+        };
+        assert_eq!(ids(&sentinel), [Some(-9999.0), Some(9007199254740992.0)]);
+
+        let error = ConversionOptions {
+            inexact_numbers: InexactNumberPolicy::Error,
+            ..Default::default()
+        };
+        let err = convert_to_bytes(contents, &error).unwrap_err();
+        assert_eq!(err.feature_index(), Some(0));
+        assert!(matches!(
+            err.without_context(),
+            ConversionError::InexactNumber { property, value }
+                if property == "id" && value == "18446744073709551615"
+        ));
+    }
+
+    #[test]
+    fn names_the_feature_behind_every_write_error() {
+        let point = r#"{"type": "Point", "coordinates": [1.0, 2.0]}"#;
+        let cases = [
+            (
+                r#"{"type": "GeometryCollection", "geometries": []}"#,
+                r#"{}"#,
+                ConversionOptions::default(),
+            ),
+            (point, r#"{"rank": "first"}"#, ConversionOptions::default()),
+            (point, r#"{"rank": [1]}"#, ConversionOptions::default()),
+            (
+                r#"{"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}"#,
+                r#"{}"#,
+                ConversionOptions::default(),
+            ),
+            (
+                "null",
+                r#"{}"#,
+                ConversionOptions {
+                    null_geometry_behavior: NullGeometryBehavior::Error,
+                    ..Default::default()
+                },
+            ),
+            (
+                r#"{"type": "Point", "coordinates": [1.0, 95.0]}"#,
+                r#"{}"#,
+                ConversionOptions {
+                    normalize_coordinates: true,
+                    ..Default::default()
+                },
+            ),
+        ];
+        let schema =
+            FieldSchema::infer([serde_json::json!({"rank": 1}).as_object().unwrap()]).unwrap();
+        for (geometry, properties, options) in cases {
+            let contents = format!(
+                r#"{{"type": "FeatureCollection", "features": [
+                    {{"type": "Feature", "geometry": {}, "properties": {{}}}},
+                    {{"type": "Feature", "geometry": {}, "properties": {}}}
+                ]}}"#,
+                point, geometry, properties
+            );
+            let mut writer = FeatureCollectionToShpWriter::from_reader_with_schema(
+                contents.as_bytes(),
+                &temp_output("feature_index"),
+                schema.clone(),
+                options,
+            )
+            .unwrap();
+            let err = writer.write().unwrap_err();
+            assert_eq!(err.feature_index(), Some(1), "{}", err);
+            assert!(err.to_string().starts_with("feature 1, "), "{}", err);
+        }
+    }
+
+    #[test]
+    fn reports_conversion_failures_as_errors() {
+        let output = temp_output("errors");
+        assert!(matches!(
+            FeatureCollectionToShpWriter::new("{not json".to_string(), &output),
+            Err(ConversionError::Parse { path: None, .. })
+        ));
+
+        let broken = format!("{}.geojson", temp_output("broken"));
+        std::fs::write(&broken, r#"{"type": "FeatureCollection", "features": [}"#).unwrap();
+        match convert(&broken, &output) {
+            Err(ConversionError::Parse { path, context, .. }) => {
+                assert_eq!(path.as_deref(), Some(Path::new(&broken)));
+                assert!(context.starts_with(r#"{"type": "FeatureCollection""#));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        let collection = r#"{"type": "GeometryCollection", "geometries": []}"#;
+        let mut writer =
+            FeatureCollectionToShpWriter::new(collection.to_string(), &output).unwrap();
+        assert!(matches!(
+            writer.write().unwrap_err().without_context(),
+            ConversionError::UnsupportedGeometry { .. }
+        ));
+
+        let options = ConversionOptions {
+            null_geometry_behavior: NullGeometryBehavior::Error,
+            ..Default::default()
+        };
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            NULL_GEOMETRY_COLLECTION.to_string(),
+            &output,
+            options,
+        )
+        .unwrap();
+        let err = writer.write().unwrap_err();
+        assert_eq!(err.feature_index(), Some(1));
+        assert!(matches!(
+            err.without_context(),
+            ConversionError::MissingGeometry
+        ));
+    }
+
+    #[test]
+    fn strips_a_leading_byte_order_mark() {
+        let contents = std::fs::read_to_string("./fixtures/bom.geojson").unwrap();
+        assert!(contents.starts_with('\u{FEFF}'));
+        let mut writer = FeatureCollectionToShpWriter::new(contents, &temp_output("bom")).unwrap();
+        writer.write().unwrap();
+        assert_eq!(writer.summary().features_written, 1);
+
+        let inline = "\r\n \u{FEFF}\n\t{\"type\": \"Point\", \"coordinates\": [1.0, 2.0]}";
+        assert!(input_path(inline).is_none());
+        let collection = parse_feature_collection(inline).unwrap();
+        assert_eq!(collection.features.len(), 1);
+    }
+
+    #[test]
+    fn detects_inline_json_before_trying_the_file_system() {
+        let inline = r#"  {"type": "Point", "coordinates": [1.0, 2.0], "path": "./fixtures/points.geojson"}"#;
+        assert_eq!(read_input(inline).unwrap(), inline);
+
+        let from_file = read_input("./fixtures/points.geojson").unwrap();
+        assert!(from_file.contains("FeatureCollection"));
+
+        let missing = read_input("./fixtures/points.geojsn").unwrap_err();
+        assert!(matches!(
+            missing,
+            ConversionError::InputNotFound { path } if path == Path::new("./fixtures/points.geojsn")
+        ));
+        assert!(matches!(
+            read_input("points.geojsn"),
+            Err(ConversionError::InputNotFound { .. })
+        ));
+
+        // Neither a file nor a path, so it is parsed (and rejected) as GeoJSON.
+        assert_eq!(read_input("Point 1 2").unwrap(), "Point 1 2");
+        assert!(matches!(
+            convert("Point 1 2", &temp_output("not_a_path")),
+            Err(ConversionError::Parse { path: None, .. })
+        ));
+    }
+
+    #[test]
+    fn skips_invalid_features_when_asked() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"rank": 1}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"rank": [2]}},
+                {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}, "properties": {"rank": 3}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5.0, 6.0]}, "properties": {"rank": 4}}
+            ]
+        }"#;
+        let schema =
+            FieldSchema::infer([serde_json::json!({"rank": 1}).as_object().unwrap()]).unwrap();
+        let options = ConversionOptions {
+            on_error: ErrorPolicy::Skip,
+            ..Default::default()
+        };
+        let output = temp_output("skip_invalid");
+        let mut writer = FeatureCollectionToShpWriter::from_reader_with_schema(
+            contents.as_bytes(),
+            &output,
+            schema,
+            options,
+        )
+        .unwrap();
+        writer.write().unwrap();
+        let skipped: Vec<_> = writer
+            .summary()
+            .skipped_features
+            .iter()
+            .map(|skipped| skipped.index)
+            .collect();
+        assert_eq!(skipped, vec![1, 2]);
+        assert_eq!(writer.summary().features_written, 2);
+        writer.finish().unwrap();
+
+        let shapes_and_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shapes_and_records.len(), 2);
+    }
+
+    #[test]
+    fn collects_every_error_when_keeping_going() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"rank": 1}},
+                {"type": "Feature", "geometry": {"type": "GeometryCollection", "geometries": []}, "properties": {"rank": 2}},
+                {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}, "properties": {"rank": 3}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [5.0, 6.0]}, "properties": {"rank": 4}}
+            ]
+        }"#;
+        let output = temp_output("keep_going");
+        let _ = std::fs::remove_file(format!("{}.shp", output));
+        let options = ConversionOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let errors = match convert_with_options(contents, &output, options.clone()) {
+            Err(ConversionError::Features(errors)) => errors,
+            other => panic!("expected every feature error, got {:?}", other),
+        };
+        let indices: Vec<_> = errors.iter().map(|error| error.index).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert!(matches!(
+            errors[0].error,
+            ConversionError::UnsupportedGeometry { .. }
+        ));
+        assert!(!Path::new(&format!("{}.shp", output)).exists());
+
+        let options = ConversionOptions {
+            on_error: ErrorPolicy::Skip,
+            ..options
+        };
+        assert!(matches!(
+            convert_with_options(contents, &output, options),
+            Err(ConversionError::Features(errors)) if errors.len() == 2
+        ));
+        let shapes_and_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shapes_and_records.len(), 2);
+    }
+
+    #[test]
+    fn writes_null_shapes_paired_with_attributes() {
+        let output = temp_output("null_shapes");
+        let options = ConversionOptions {
+            null_geometry_behavior: NullGeometryBehavior::WriteNullShape,
+            ..Default::default()
+        };
+        let mut writer = FeatureCollectionToShpWriter::with_options(
+            NULL_GEOMETRY_COLLECTION.to_string(),
+            &output,
+            options,
+        )
+        .unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shape_records.len(), 3);
+        assert!(matches!(shape_records[1].0, Shape::NullShape));
+        assert_eq!(
+            shape_records[1].1.get("name"),
+            Some(&FieldValue::Character(Some("b".to_string())))
+        );
+    }
+
+    #[test]
+    fn skips_null_geometries_by_default() {
+        let output = temp_output("null_shapes_skipped");
+        let mut writer =
+            FeatureCollectionToShpWriter::new(NULL_GEOMETRY_COLLECTION.to_string(), &output)
+                .unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shape_records.len(), 2);
+    }
+
+    #[test]
+    fn includes_foreign_members_as_attributes() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {"type": "Point", "coordinates": [1.0, 2.0]},
+                "properties": {"name": "a", "severity": "high"},
+                "severity": 3,
+                "source": "sensor"
+            }]
+        }"#;
+        let output = temp_output("foreign_members");
+        let options = ConversionOptions {
+            include_foreign_members: true,
+            ..Default::default()
+        };
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents.to_string(), &output, options)
+                .unwrap();
+        writer.write().unwrap();
+        assert_eq!(writer.warnings().len(), 1);
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        let record = &shape_records[0].1;
+        assert_eq!(
+            record.get("source"),
+            Some(&FieldValue::Character(Some("sensor".to_string())))
+        );
+        assert_eq!(
+            record.get("severity"),
+            Some(&FieldValue::Character(Some("high".to_string())))
+        );
+    }
+
+    #[test]
+    fn filters_features_before_schema_inference_and_writing() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let output = temp_output("filtered");
+        let options = ConversionOptions::default().filter(|feature| {
+            feature
+                .property("category")
+                .is_some_and(|category| category == "medium")
+        });
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents, &output, options).unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shape_records.len(), 1);
+        for (_, record) in shape_records {
+            assert_eq!(
+                record.get("category"),
+                Some(&FieldValue::Character(Some("medium".to_string())))
+            );
+        }
+    }
+
+    #[test]
+    fn exposes_collection_metadata_and_writes_sidecar() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "generated_at": "2022-01-01T00:00:00Z",
+            "source": "county gis",
+            "exceededTransferLimit": true,
+            "features": [{
+                "type": "Feature",
+                "geometry": {"type": "Point", "coordinates": [1.0, 2.0]},
+                "properties": {"name": "a"}
+            }]
+        }"#;
+        let output = temp_output("metadata");
+        let _ = std::fs::remove_file(format!("{}.meta.json", output));
+        let mut writer = FeatureCollectionToShpWriter::new(contents.to_string(), &output).unwrap();
+
+        let metadata = writer.metadata();
+        assert_eq!(metadata.foreign_members["source"], "county gis");
+        let foreign_members = writer.foreign_members().unwrap();
+        assert_eq!(foreign_members["exceededTransferLimit"], true);
+        assert_eq!(foreign_members.len(), 3);
+
+        let path = writer.write_metadata_sidecar().unwrap().unwrap();
+        assert!(!Path::new(&path).exists());
+        writer.finish().unwrap();
+        let sidecar: JsonValue =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(sidecar["generated_at"], "2022-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn reuses_a_serialized_schema() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let writer =
+            FeatureCollectionToShpWriter::new(contents.clone(), &temp_output("schema_first"))
+                .unwrap();
+        let serialized = serde_json::to_string(writer.schema()).unwrap();
+        writer.finish().unwrap();
+
+        let schema: FieldSchema = serde_json::from_str(&serialized).unwrap();
+        let output = temp_output("schema_second");
+        let mut writer = FeatureCollectionToShpWriter::from_reader_with_schema(
+            contents.as_bytes(),
+            &output,
+            schema.clone(),
+            ConversionOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(writer.schema(), &schema);
+        writer.write().unwrap();
+    }
+
+    #[test]
+    fn converts_features_without_properties() {
+        let contents = std::fs::read_to_string("./fixtures/geometry_only.geojson").unwrap();
+        let output = temp_output("geometry_only");
+        let mut writer = FeatureCollectionToShpWriter::new(contents, &output).unwrap();
+        writer.write().unwrap();
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(shape_records.len(), 3);
+        assert_eq!(
+            shape_records[2].1.get(FieldSchema::PLACEHOLDER_FIELD),
+            Some(&FieldValue::Numeric(Some(2.0)))
+        );
+    }
+
+    #[test]
+    fn transliterates_character_values() {
+        let contents = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "properties": {"name": "Straße"}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.0, 4.0]}, "properties": {"name": "plain"}}
+            ]
+        }"#;
+        let output = temp_output("transliterated");
+        let options = ConversionOptions {
+            transliterate: true,
+            ..Default::default()
+        };
+        let mut writer =
+            FeatureCollectionToShpWriter::with_options(contents.to_string(), &output, options)
+                .unwrap();
+        assert_eq!(writer.schema().fields[0].width, 7);
+        writer.write().unwrap();
+        assert_eq!(writer.summary().transliterated_values, 1);
+        writer.finish().unwrap();
+
+        let shape_records = shapefile::read(format!("{}.shp", output)).unwrap();
+        assert_eq!(
+            shape_records[0].1.get("name"),
+            Some(&FieldValue::Character(Some("Strasse".to_string())))
+        );
     }
 }
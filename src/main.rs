@@ -1,20 +1,181 @@
 use std::{env, process};
 
-use geojson_to_shp::Cli;
+use geojson_to_shp::{flag_hint, Cli, Command, ConversionError, ConversionSummary, ErrorFormat};
 
 fn main() {
-    let cli = Cli::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("A problem occurred while parsing the args: {}", err);
-        process::exit(1);
+    let args: Vec<String> = env::args().collect();
+    let cli = Cli::new(args.iter().cloned()).unwrap_or_else(|err| {
+        // --help and --version, which clap prints to stdout.
+        if !err.use_stderr() {
+            err.exit();
+        }
+        let reporter = Reporter {
+            format: ErrorFormat::from_args(&args),
+            input: None,
+            output: None,
+        };
+        match reporter.format {
+            ErrorFormat::Text => {
+                let _ = err.print();
+            }
+            ErrorFormat::Json => {
+                let report = serde_json::json!({
+                    "kind": "usage",
+                    "message": err.to_string(),
+                    "feature_index": null,
+                    "property": null,
+                    "path": null,
+                });
+                eprintln!("{}", reporter.with_paths(report));
+            }
+        }
+        process::exit(ConversionError::EXIT_USAGE);
     });
+    let reporter = Reporter {
+        format: cli.error_format(),
+        input: Some(cli.input().to_string()),
+        output: cli.output().map(str::to_string),
+    };
 
-    let mut writer = cli.to_writer().unwrap_or_else(|err| {
-        eprintln!("An error occurred while creating the Writer: {:?}", err);
-        process::exit(1);
-    });
+    if let Command::Schema { .. } = cli.command() {
+        let report = cli.schema_report().unwrap_or_else(|err| {
+            reporter.fail("An error occurred while inferring the schema", err)
+        });
+        print!("{}", report);
+        return;
+    }
 
-    writer.write().unwrap_or_else(|err| {
-        eprintln!("An error occurred while writing the shapefile: {:?}", err);
-        process::exit(1);
-    });
+    if let Command::ToGeoJson { .. } = cli.command() {
+        let written = cli.to_geojson().unwrap_or_else(|err| {
+            reporter.fail("An error occurred while converting to GeoJSON", err)
+        });
+        println!("Wrote {} features", written);
+        return;
+    }
+
+    if let Command::ConvertGlob { output_dir } = cli.command() {
+        let converted = cli
+            .convert_glob()
+            .unwrap_or_else(|err| reporter.fail("An error occurred while converting", err));
+        if converted.is_empty() {
+            eprintln!("Warning: no files match {}", cli.input());
+            process::exit(ConversionError::EXIT_INPUT_NOT_FOUND);
+        }
+        for (path, summary) in converted.iter() {
+            println!("Converted {} into {}", path.display(), output_dir);
+            reporter.report(summary);
+        }
+        exit_if_skipped(converted.iter().map(|(_, summary)| summary));
+        return;
+    }
+
+    if cli.split_at_limit() {
+        let parts = cli
+            .convert_in_parts()
+            .unwrap_or_else(|err| reporter.fail("An error occurred while converting", err));
+        for (path, summary) in parts.iter() {
+            println!(
+                "Wrote {} features to {}.shp",
+                summary.features_written, path
+            );
+            reporter.report(summary);
+        }
+        exit_if_skipped(parts.iter().map(|(_, summary)| summary));
+        return;
+    }
+
+    let summary = cli
+        .convert()
+        .unwrap_or_else(|err| reporter.fail("An error occurred while converting", err));
+    reporter.report(&summary);
+    exit_if_skipped([&summary]);
+}
+
+/// Exits with `ConversionError::EXIT_CONVERSION` when any feature was skipped, so that scripts
+/// do not mistake a partial conversion for a complete one.
+fn exit_if_skipped<'a>(summaries: impl IntoIterator<Item = &'a ConversionSummary>) {
+    if summaries
+        .into_iter()
+        .any(|summary| !summary.skipped_features.is_empty())
+    {
+        process::exit(ConversionError::EXIT_CONVERSION);
+    }
+}
+
+/// Writes failures and warnings to stderr in the `--error-format` asked for.
+struct Reporter {
+    format: ErrorFormat,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl Reporter {
+    fn fail(&self, context: &str, err: ConversionError) -> ! {
+        match self.format {
+            ErrorFormat::Text => {
+                eprintln!("{}: {}", context, err);
+                if let Some(hint) = flag_hint(&err) {
+                    eprintln!("Hint: {}", hint);
+                }
+            }
+            ErrorFormat::Json => {
+                let mut report = self.with_paths(err.to_json());
+                report["hint"] = serde_json::json!(flag_hint(&err));
+                eprintln!("{}", report);
+            }
+        }
+        process::exit(err.exit_code());
+    }
+
+    fn with_paths(&self, mut report: serde_json::Value) -> serde_json::Value {
+        report["input"] = serde_json::json!(self.input);
+        report["output"] = serde_json::json!(self.output);
+        report
+    }
+
+    fn report(&self, summary: &ConversionSummary) {
+        match self.format {
+            ErrorFormat::Text => report(summary),
+            ErrorFormat::Json if summary.has_warnings() => {
+                eprintln!("{}", self.with_paths(summary.to_json()));
+            }
+            ErrorFormat::Json => {}
+        }
+    }
+}
+
+fn report(summary: &ConversionSummary) {
+    for warning in summary.schema_warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+    for warning in summary.warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+    for warning in summary.conversion_warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    for skipped in summary.skipped_features.iter() {
+        eprintln!("Skipped feature {}: {}", skipped.index, skipped.reason);
+    }
+
+    if summary.duplicates_skipped > 0 {
+        println!("Skipped {} duplicate features", summary.duplicates_skipped);
+    }
+
+    let mut centroids: Vec<_> = summary.centroids.iter().collect();
+    centroids.sort_by(|a, b| a.0.cmp(b.0));
+    for (geometry_type, [x, y]) in centroids {
+        println!(
+            "Mean centroid of the {} features: {}, {}",
+            geometry_type, x, y
+        );
+    }
+
+    if summary.transliterated_values > 0 {
+        println!(
+            "Transliterated {} attribute values to ASCII",
+            summary.transliterated_values
+        );
+    }
 }
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use encoding_rs::Encoding;
+use geojson::Feature;
+use serde_json::Value as JsonValue;
+
+use crate::DbfType;
+
+/// Options controlling how a FeatureCollection is converted into a shapefile.
+///
+/// The defaults convert as plainly as possible: features without geometry are skipped,
+/// Character fields are as wide as their longest value, the dbf is UTF-8, and the .prj and
+/// .cpg sidecars say so; every other flag is off. Set only what differs, as in
+/// `ConversionOptions { write_prj: false, ..Default::default() }`.
+#[derive(Debug, Clone)]
+pub struct ConversionOptions {
+    pub null_geometry_behavior: NullGeometryBehavior,
+    /// Read positions as `[latitude, longitude]` instead of the `[longitude, latitude]` of
+    /// GeoJSON, for sources that store them the other way around.
+    pub lat_lon_order: bool,
+    /// Wrap longitudes into [-180, 180] and reject latitudes outside of [-90, 90].
+    pub normalize_coordinates: bool,
+    /// Round every coordinate to this many decimal places. Every vertex is rounded the same
+    /// way, so rings that share an edge still do. Vertices that round onto the one before are
+    /// merged, and a geometry that rounding collapses or makes cross itself is rejected as
+    /// invalid.
+    pub coordinate_precision: Option<u8>,
+    /// Simplify LineStrings and Polygon rings with Douglas-Peucker, dropping vertices that are
+    /// closer than this distance (in coordinate units) to the simplified line.
+    pub simplify: Option<f64>,
+    /// Treat members found directly on a Feature (outside of `properties`) as attributes.
+    pub include_foreign_members: bool,
+    /// Transliterate Character values to ASCII (é → e, ß → ss) before measuring and writing them.
+    pub transliterate: bool,
+    /// Trim leading and trailing whitespace from Character values.
+    pub trim_strings: bool,
+    /// Collapse runs of whitespace inside Character values into a single space.
+    pub collapse_whitespace: bool,
+    /// Code page Character values are written in. UTF-8 when `None`.
+    pub encoding: Option<&'static Encoding>,
+    /// Write a `.cpg` sidecar naming the code page of the dbf, so that readers do not take
+    /// UTF-8 text for their local code page.
+    pub write_cpg: bool,
+    /// Write a `.prj` sidecar naming WGS 84, the coordinate reference system of GeoJSON.
+    pub write_prj: bool,
+    /// What to do with characters the `encoding` cannot represent.
+    pub unencodable: UnencodablePolicy,
+    /// What to do when a feature's geometry or attributes cannot be converted.
+    pub on_error: ErrorPolicy,
+    /// Convert every feature even after one fails, and report all of the failures at once.
+    pub keep_going: bool,
+    /// Skip features whose geometry and properties exactly match a feature already written.
+    pub dedupe: bool,
+    /// What to do with Character values longer than the 254 bytes a dbf field can hold.
+    pub long_text: LongTextPolicy,
+    /// What to do with numbers a Numeric field cannot hold exactly.
+    pub inexact_numbers: InexactNumberPolicy,
+    /// Property every feature must carry, written as the first dbf column.
+    pub feature_id_field: Option<String>,
+    /// Also require the `feature_id_field` values to be unique.
+    pub require_unique_id: bool,
+    /// Field types forced per property instead of inferred, such as a Character `zip` that
+    /// keeps its leading zeros. Values are coerced to the forced type when written.
+    pub field_types: HashMap<String, DbfType>,
+    /// Width of every Character field, instead of the width of its longest value.
+    /// Longer values are truncated with a warning.
+    pub default_string_width: Option<u8>,
+    /// Character field widths per property, taking precedence over `default_string_width`.
+    pub string_widths: HashMap<String, u8>,
+    /// Which features the schema is inferred from.
+    pub schema_sample: SchemaSample,
+    /// Fail instead of warning when a feature outside of the schema sample has a new property.
+    pub strict_schema: bool,
+    /// Write only the .shp and .dbf, without the .shx index.
+    pub skip_index: bool,
+    /// Size in bytes the .shp or .dbf may grow to, `MAX_FILE_SIZE` when `None`. Past it `write()`
+    /// fails, and `FeatureCollectionToShpWriterConfig::write_chunked` starts a new shapefile.
+    pub max_file_size: Option<u64>,
+    /// Write a `.wkt` sidecar holding the WKT of every record's shape, one per line.
+    pub write_wkt_sidecar: bool,
+    /// Leave the files of a failed conversion in place, for debugging, instead of removing them.
+    pub keep_partial: bool,
+    /// Replace output files that already exist, instead of refusing to write anything.
+    pub overwrite: bool,
+    /// Create the directory the output is written to, and its parents, when missing.
+    pub create_dirs: bool,
+    /// Write an ESRI-style `.shp.xml` metadata sidecar next to the shapefile.
+    #[cfg(feature = "esri-metadata")]
+    pub esri_metadata: bool,
+    /// Only features for which the predicate returns `true` are converted.
+    pub filter: Option<FeatureFilter>,
+    /// Checked before each feature is written. Once it is set, `write()` stops with
+    /// `ConversionError::Cancelled`, and the files are removed unless `keep_partial` is set.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Timeout and redirects of `FeatureCollectionToShpWriter::from_url`.
+    #[cfg(feature = "http")]
+    pub http: crate::HttpOptions,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            null_geometry_behavior: Default::default(),
+            lat_lon_order: false,
+            normalize_coordinates: false,
+            coordinate_precision: None,
+            simplify: None,
+            include_foreign_members: false,
+            transliterate: false,
+            trim_strings: false,
+            collapse_whitespace: false,
+            encoding: None,
+            write_cpg: true,
+            write_prj: true,
+            unencodable: Default::default(),
+            on_error: Default::default(),
+            keep_going: false,
+            dedupe: false,
+            long_text: Default::default(),
+            inexact_numbers: Default::default(),
+            feature_id_field: None,
+            require_unique_id: false,
+            field_types: HashMap::new(),
+            default_string_width: None,
+            string_widths: HashMap::new(),
+            schema_sample: Default::default(),
+            strict_schema: false,
+            skip_index: false,
+            max_file_size: None,
+            write_wkt_sidecar: false,
+            keep_partial: false,
+            overwrite: false,
+            create_dirs: false,
+            #[cfg(feature = "esri-metadata")]
+            esri_metadata: false,
+            filter: None,
+            cancel: None,
+            #[cfg(feature = "http")]
+            http: Default::default(),
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// The most a .shp or .dbf holds: record offsets are stored as signed 32-bit numbers.
+    pub const MAX_FILE_SIZE: u64 = i32::MAX as u64;
+
+    /// Sets a predicate that is applied before schema inference and writing.
+    pub fn filter(mut self, predicate: impl Fn(&Feature) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(FeatureFilter(Arc::new(predicate)));
+        self
+    }
+
+    /// Keeps only the features whose `property` equals `value`, on top of any `filter` set
+    /// before. A String `value` also matches the number, boolean or null it reads as in JSON,
+    /// so that `"3"` matches `3`, as values typed on the command line are all text.
+    pub fn property_equals(
+        mut self,
+        property: impl Into<String>,
+        value: impl Into<JsonValue>,
+    ) -> Self {
+        let (property, value) = (property.into(), value.into());
+        let previous = self.filter.take();
+        self.filter = Some(FeatureFilter(Arc::new(move |feature| {
+            previous
+                .as_ref()
+                .is_none_or(|filter| filter.matches(feature))
+                && feature
+                    .property(&property)
+                    .is_some_and(|actual| property_matches(actual, &value))
+        })));
+        self
+    }
+
+    /// Sets a flag that cancels the conversion when set, from another thread for instance.
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// The contents of the .cpg, `None` unless `write_cpg` is set.
+    pub(crate) fn cpg(&self) -> Option<&'static str> {
+        self.write_cpg
+            .then(|| self.encoding.map_or("UTF-8", crate::encoding::code_page))
+    }
+
+    /// The contents of the .prj, `None` unless `write_prj` is set.
+    pub(crate) fn prj(&self) -> Option<&'static str> {
+        self.write_prj.then_some(crate::shp::WGS84_PRJ)
+    }
+
+    /// Fails with `ConversionError::Cancelled` once the `cancel` flag is set.
+    pub(crate) fn check_cancelled(&self) -> Result<(), crate::ConversionError> {
+        match &self.cancel {
+            Some(flag) if flag.load(Ordering::Relaxed) => Err(crate::ConversionError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn lat_lon_order(mut self, lat_lon_order: bool) -> Self {
+        self.lat_lon_order = lat_lon_order;
+        self
+    }
+
+    pub fn coordinate_precision(mut self, decimals: u8) -> Self {
+        self.coordinate_precision = Some(decimals);
+        self
+    }
+
+    pub fn simplify(mut self, tolerance: f64) -> Self {
+        self.simplify = Some(tolerance);
+        self
+    }
+
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    pub fn field_types(mut self, field_types: HashMap<String, DbfType>) -> Self {
+        self.field_types = field_types;
+        self
+    }
+}
+
+fn property_matches(actual: &JsonValue, expected: &JsonValue) -> bool {
+    match (actual, expected) {
+        (JsonValue::String(_), _) => actual == expected,
+        (_, JsonValue::String(text)) => text.parse().is_ok_and(|value: JsonValue| value == *actual),
+        _ => actual == expected,
+    }
+}
+
+/// A shareable predicate used to select the features to convert.
+#[derive(Clone)]
+pub struct FeatureFilter(Arc<dyn Fn(&Feature) -> bool + Send + Sync>);
+
+impl FeatureFilter {
+    pub fn matches(&self, feature: &Feature) -> bool {
+        (self.0)(feature)
+    }
+}
+
+impl fmt::Debug for FeatureFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FeatureFilter")
+    }
+}
+
+/// The features scanned to infer the schema.
+///
+/// With `First`, properties that only appear later in the collection are dropped with a
+/// warning, or rejected under `ConversionOptions::strict_schema`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaSample {
+    First(usize),
+    #[default]
+    All,
+}
+
+/// What to do with characters the chosen dbf encoding cannot represent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnencodablePolicy {
+    /// Write `?` in their place.
+    #[default]
+    Replace,
+    /// Fail the feature, so it stops the conversion or is skipped under `ErrorPolicy::Skip`.
+    Error,
+}
+
+/// What to do with a feature that cannot be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop the conversion with the feature's error.
+    #[default]
+    FailFast,
+    /// Leave the feature out of both the .shp and the .dbf, record why in the summary and go on.
+    Skip,
+}
+
+/// How Character values longer than 254 bytes are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LongTextPolicy {
+    /// Cut the value at 254 bytes and raise a warning.
+    #[default]
+    Truncate,
+    /// Spread the value over up to `max_fields` numbered fields (`descript_1`, `descript_2`, …)
+    /// that reassemble into the original value when concatenated. Anything beyond the last
+    /// field is truncated with a warning.
+    Split { max_fields: usize },
+    /// Leave properties holding over-long values out of the dbf entirely.
+    Drop,
+    /// Fail the feature holding an over-long value, naming the property and its length, so it
+    /// stops the conversion or is skipped under `ErrorPolicy::Skip`.
+    Error,
+}
+
+impl LongTextPolicy {
+    /// Number of continuation fields used by `--long-text split` unless told otherwise.
+    pub const DEFAULT_SPLIT_FIELDS: usize = 4;
+}
+
+/// How numbers that a Numeric field cannot hold exactly are written.
+///
+/// Numeric values are written from an `f64`, which holds every integer up to 2^53 exactly.
+/// Integers past it, such as 64-bit ids, are rounded to the nearest `f64`. A number with no
+/// `f64` at all would be written as null.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InexactNumberPolicy {
+    /// Write the nearest `f64`, or null when there is none.
+    #[default]
+    Nearest,
+    /// Fail the feature, so it stops the conversion or is skipped under `ErrorPolicy::Skip`.
+    Error,
+    /// Write this value in its place, such as `-9999.0`.
+    Sentinel(f64),
+}
+
+/// What to do with features whose `geometry` member is `null`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullGeometryBehavior {
+    /// Leave the feature out of both the .shp and the .dbf.
+    #[default]
+    Skip,
+    /// Write a Null Shape record (shape type 0) paired with the feature's attributes.
+    WriteNullShape,
+    /// Stop the conversion with an error.
+    Error,
+}
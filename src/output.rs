@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ConversionError;
+
+/// The files a conversion writes, kept under temporary names until it succeeds.
+///
+/// Each file is created as a hidden temporary file next to its destination and renamed into
+/// place by `commit()`, so that a directory watcher never sees a half-written shapefile. If
+/// the conversion fails the temporary files are removed instead.
+///
+/// The guard discards the files not committed when it is dropped, so that the files created
+/// before an error, or by a writer dropped without being finished, never appear under their
+/// own names. As a file cannot be renamed or removed while it is open on Windows, the guard is
+/// meant to be dropped after the writers holding the files.
+///
+/// Unless told to `overwrite`, no file is created over an existing one.
+#[derive(Debug, Default)]
+pub(crate) struct OutputFiles {
+    /// The temporary path of every file, and its destination.
+    files: Vec<(PathBuf, PathBuf)>,
+    /// Set by `discard()`, after which `commit()` moves nothing into place.
+    discarded: bool,
+    overwrite: bool,
+}
+
+impl OutputFiles {
+    pub(crate) fn new(overwrite: bool) -> Self {
+        Self {
+            files: Vec::new(),
+            discarded: false,
+            overwrite,
+        }
+    }
+
+    /// Fails with every one of `paths` that already exists, unless overwriting.
+    pub(crate) fn check(&self, paths: &[String]) -> Result<(), ConversionError> {
+        if self.overwrite {
+            return Ok(());
+        }
+        let existing: Vec<String> = paths
+            .iter()
+            .filter(|path| Path::new(path).exists())
+            .cloned()
+            .collect();
+        if existing.is_empty() {
+            Ok(())
+        } else {
+            Err(ConversionError::OutputExists { paths: existing })
+        }
+    }
+
+    /// Creates the temporary file for `path`.
+    pub(crate) fn create(&mut self, path: String) -> Result<File, ConversionError> {
+        let temp = self.reserve(path)?;
+        Ok(File::create(temp)?)
+    }
+
+    /// The temporary path to write `path` at, for writers that create the file themselves.
+    pub(crate) fn reserve(&mut self, path: String) -> Result<PathBuf, ConversionError> {
+        self.check(std::slice::from_ref(&path))?;
+        let path = PathBuf::from(path);
+        let temp = temp_path(&path);
+        self.files.push((temp.clone(), path));
+        Ok(temp)
+    }
+
+    /// Writes `contents` to the temporary file for `path`.
+    pub(crate) fn write(&mut self, path: String, contents: &str) -> Result<(), ConversionError> {
+        self.check(std::slice::from_ref(&path))?;
+        let path = PathBuf::from(path);
+        let temp = temp_path(&path);
+        std::fs::write(&temp, contents)?;
+        self.files.push((temp, path));
+        Ok(())
+    }
+
+    /// Renames every file into place. The .shp goes last, as it is the file readers look for.
+    /// Returns where the files went, in the order they were created.
+    ///
+    /// If a rename fails, the files not yet in place are removed. Once discarded, the files
+    /// are removed again instead, and nothing is returned.
+    pub(crate) fn commit(&mut self) -> io::Result<Vec<PathBuf>> {
+        if self.discarded {
+            self.discard();
+            return Ok(Vec::new());
+        }
+        let mut files = std::mem::take(&mut self.files);
+        let paths = files.iter().map(|(_, path)| path.clone()).collect();
+        files.sort_by_key(|(_, path)| path.extension().is_some_and(|ext| ext == "shp"));
+        let mut result = Ok(());
+        for (temp, path) in files {
+            if result.is_ok() {
+                result = replace(&temp, &path);
+            }
+            if result.is_err() {
+                let _ = std::fs::remove_file(&temp);
+            }
+        }
+        result.map(|_| paths)
+    }
+
+    /// Removes the files now where possible, and again when the guard is dropped.
+    pub(crate) fn discard(&mut self) {
+        self.discarded = true;
+        for (temp, _) in self.files.iter() {
+            let _ = std::fs::remove_file(temp);
+        }
+    }
+}
+
+impl Drop for OutputFiles {
+    fn drop(&mut self) {
+        self.discard();
+    }
+}
+
+/// `filepath` without the `.shp`, `.shx` or `.dbf` extension (in any case) the writer adds
+/// to it, so that `out.shp` is written as `out.shp` rather than `out.shp.shp`.
+pub(crate) fn shapefile_base(filepath: &str) -> &str {
+    let extension = Path::new(filepath)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("shp" | "shx" | "dbf") => &filepath[..filepath.len() - ".shp".len()],
+        _ => filepath,
+    }
+}
+
+/// Makes sure the directory `path` is written to exists, creating it when `create` is set.
+pub(crate) fn ensure_directory(path: &Path, create: bool) -> Result<(), ConversionError> {
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => return Ok(()),
+    };
+    if directory.is_dir() {
+        return Ok(());
+    }
+    if create {
+        std::fs::create_dir_all(directory)?;
+        return Ok(());
+    }
+    Err(ConversionError::MissingOutputDirectory {
+        directory: directory.to_path_buf(),
+        path: path.to_path_buf(),
+    })
+}
+
+/// A hidden name for `path` in the same directory, since a rename is only atomic within
+/// a file system.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), count))
+}
+
+/// Moves `temp` over `path`, replacing the file already there.
+///
+/// `std::fs::rename` replaces an existing destination on both Unix and Windows, but Windows
+/// refuses when the destination is read-only; in that case the old file is removed first.
+fn replace(temp: &Path, path: &Path) -> io::Result<()> {
+    match std::fs::rename(temp, path) {
+        Err(err) if path.is_file() => {
+            let mut permissions = std::fs::metadata(path)?.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            std::fs::set_permissions(path, permissions)
+                .and_then(|_| std::fs::remove_file(path))
+                .map_err(|_| err)?;
+            std::fs::rename(temp, path)
+        }
+        result => result,
+    }
+}
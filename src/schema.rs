@@ -0,0 +1,808 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use shapefile::dbase::{FieldName, FieldValue, Record, TableWriterBuilder};
+
+use crate::error::describe_value;
+use crate::{ConversionError, ConversionOptions, LongTextPolicy};
+
+pub(crate) const CHARACTER_MAX_WIDTH: usize = 254;
+const NUMERIC_MAX_WIDTH: usize = 20;
+const FIELD_NAME_MAX_LEN: usize = 10;
+/// Sizes in bytes of the dbf file header and of each field descriptor.
+pub(crate) const DBF_HEADER_SIZE: u64 = 32;
+pub(crate) const DBF_FIELD_DESCRIPTOR_SIZE: u64 = 32;
+
+/// The dBase field types the converter writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DbfType {
+    Character,
+    Numeric,
+}
+
+/// A single column of the .dbf, tied to the property it is read from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldDefinition {
+    pub property: String,
+    pub name: String,
+    pub field_type: DbfType,
+    pub width: u8,
+    pub decimals: u8,
+    /// For properties split by `LongTextPolicy::Split`, which 254-byte piece
+    /// of the value (starting at 1) this field holds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<usize>,
+}
+
+/// The .dbf columns written for a FeatureCollection.
+///
+/// The schema can be serialized, persisted and handed back to
+/// `FeatureCollectionToShpWriter::from_reader_with_schema` to keep the columns stable across runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub fields: Vec<FieldDefinition>,
+}
+
+/// Something the schema inference had to compromise on to fit the dbf format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaWarning {
+    /// The property name was cut down to the 10 characters a field name allows.
+    NameTruncated { property: String, name: String },
+    /// The (truncated) name was already taken, so the field was numbered.
+    NameCollision { property: String, name: String },
+    /// The property holds both numbers and strings and was written as a Character field.
+    TypeFallback { property: String },
+    /// The values are wider than the field type allows and will be cut to `width`.
+    WidthClamped { property: String, width: u8 },
+}
+
+impl fmt::Display for SchemaWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaWarning::NameTruncated { property, name } => {
+                write!(
+                    f,
+                    "Property \"{}\" was truncated to the field name \"{}\"",
+                    property, name
+                )
+            }
+            SchemaWarning::NameCollision { property, name } => write!(
+                f,
+                "Property \"{}\" collided with another field name and was renamed to \"{}\"",
+                property, name
+            ),
+            SchemaWarning::TypeFallback { property } => write!(
+                f,
+                "Property \"{}\" mixes numbers and strings and is written as a Character field",
+                property
+            ),
+            SchemaWarning::WidthClamped { property, width } => write!(
+                f,
+                "Property \"{}\" has values wider than its field and is clamped to {} characters",
+                property, width
+            ),
+        }
+    }
+}
+
+/// Running statistics for a property while its values are scanned.
+#[derive(Default)]
+struct PropertyStats {
+    has_numbers: bool,
+    has_strings: bool,
+    max_len: usize,
+    /// Widest piece at each position, for values longer than a Character field.
+    chunk_widths: Vec<usize>,
+    max_integer_digits: usize,
+    max_decimals: usize,
+}
+
+impl PropertyStats {
+    fn add(&mut self, property: &str, value: &JsonValue) -> Result<(), ConversionError> {
+        match value {
+            JsonValue::Null => {}
+            JsonValue::Number(number) => {
+                let text = number_text(number);
+                let (integer, decimals) = match text.split_once('.') {
+                    Some((integer, decimals)) => (integer.len(), decimals.len()),
+                    None => (text.len(), 0),
+                };
+                self.has_numbers = true;
+                self.max_len = self.max_len.max(text.len());
+                self.max_integer_digits = self.max_integer_digits.max(integer);
+                self.max_decimals = self.max_decimals.max(decimals);
+            }
+            JsonValue::String(text) => {
+                self.has_strings = true;
+                self.max_len = self.max_len.max(text.len());
+                if text.len() > CHARACTER_MAX_WIDTH {
+                    for (index, chunk) in text_chunks(text).enumerate() {
+                        match self.chunk_widths.get_mut(index) {
+                            Some(width) => *width = (*width).max(chunk.len()),
+                            None => self.chunk_widths.push(chunk.len()),
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(ConversionError::UnsupportedPropertyType {
+                    property: property.to_string(),
+                    value: describe_value(value),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn into_fields(
+        self,
+        property: String,
+        long_text: LongTextPolicy,
+        width_override: Option<u8>,
+        warnings: &mut Vec<SchemaWarning>,
+    ) -> Vec<FieldDefinition> {
+        if self.has_numbers && !self.has_strings {
+            let integer_digits = self.max_integer_digits.clamp(1, NUMERIC_MAX_WIDTH);
+            let mut decimals = self.max_decimals;
+            if decimals > 0 && integer_digits + 1 + decimals > NUMERIC_MAX_WIDTH {
+                decimals = NUMERIC_MAX_WIDTH.saturating_sub(integer_digits + 1);
+            }
+            let width = match decimals {
+                0 => integer_digits,
+                _ => integer_digits + 1 + decimals,
+            };
+            if self.max_integer_digits > integer_digits || self.max_decimals > decimals {
+                warnings.push(SchemaWarning::WidthClamped {
+                    property: property.clone(),
+                    width: width as u8,
+                });
+            }
+            return vec![FieldDefinition {
+                name: property.clone(),
+                property,
+                field_type: DbfType::Numeric,
+                width: width as u8,
+                decimals: decimals as u8,
+                part: None,
+            }];
+        }
+
+        if self.has_numbers && self.has_strings {
+            warnings.push(SchemaWarning::TypeFallback {
+                property: property.clone(),
+            });
+        }
+        // Values are written to a single field unless they are too long for one and the
+        // policy says otherwise.
+        let is_single_field = self.max_len <= CHARACTER_MAX_WIDTH
+            || matches!(long_text, LongTextPolicy::Truncate | LongTextPolicy::Error);
+        let single_width = width_override
+            .map_or(self.max_len, usize::from)
+            .clamp(1, CHARACTER_MAX_WIDTH);
+        if is_single_field && self.max_len > single_width {
+            warnings.push(SchemaWarning::WidthClamped {
+                property: property.clone(),
+                width: single_width as u8,
+            });
+        }
+        let character_field = |width: usize, part: Option<usize>| FieldDefinition {
+            name: property.clone(),
+            property: property.clone(),
+            field_type: DbfType::Character,
+            width: width.clamp(1, CHARACTER_MAX_WIDTH) as u8,
+            decimals: 0,
+            part,
+        };
+        match long_text {
+            _ if is_single_field => vec![character_field(single_width, None)],
+            LongTextPolicy::Truncate | LongTextPolicy::Drop | LongTextPolicy::Error => Vec::new(),
+            LongTextPolicy::Split { max_fields } => self
+                .chunk_widths
+                .iter()
+                .take(max_fields.max(1))
+                .enumerate()
+                .map(|(index, width)| character_field(*width, Some(index + 1)))
+                .collect(),
+        }
+    }
+}
+
+/// Splits text into pieces of at most 254 bytes, on character boundaries.
+pub(crate) fn text_chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = floor_char_boundary(rest, CHARACTER_MAX_WIDTH);
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// The longest prefix of `text` that fits in `max_len` bytes without splitting a character.
+pub(crate) fn truncate_to_width(text: &str, max_len: usize) -> &str {
+    &text[..floor_char_boundary(text, max_len)]
+}
+
+fn floor_char_boundary(text: &str, max_len: usize) -> usize {
+    if text.len() <= max_len {
+        return text.len();
+    }
+    (0..=max_len)
+        .rev()
+        .find(|index| text.is_char_boundary(*index))
+        .unwrap_or(0)
+}
+
+/// Fits a property name (plus an optional suffix) into the 10 characters a dbf field name
+/// allows, numbering it when the result is already taken.
+///
+/// Returns the name and whether it had to be numbered.
+fn field_name(property: &str, suffix: &str, taken: &[String]) -> (String, bool) {
+    let is_taken = |name: &str| taken.iter().any(|t| t.eq_ignore_ascii_case(name));
+    let fit = |suffix: &str| {
+        let base = truncate_to_width(property, FIELD_NAME_MAX_LEN.saturating_sub(suffix.len()));
+        format!("{}{}", base, suffix)
+    };
+    let name = fit(suffix);
+    if !is_taken(&name) {
+        return (name, false);
+    }
+    let name = (1..)
+        .map(|n| fit(&format!("{}_{}", suffix, n)))
+        .find(|name| !is_taken(name))
+        .unwrap();
+    (name, true)
+}
+
+fn field_name_of(field: &FieldDefinition) -> Result<FieldName, ConversionError> {
+    FieldName::try_from(&field.name[..]).map_err(|reason| ConversionError::InvalidFieldName {
+        name: field.name.clone(),
+        reason,
+    })
+}
+
+/// Reads a string holding a number as that number, for properties forced to Numeric.
+fn coerce_to_number(property: &str, value: &JsonValue) -> Result<JsonValue, ConversionError> {
+    match value {
+        JsonValue::String(text) => parse_number(text).map(JsonValue::Number).ok_or_else(|| {
+            ConversionError::PropertyTypeMismatch {
+                property: property.to_string(),
+                expected: DbfType::Numeric,
+                value: describe_value(value),
+            }
+        }),
+        _ => Ok(value.clone()),
+    }
+}
+
+pub(crate) fn parse_number(text: &str) -> Option<serde_json::Number> {
+    text.trim().parse().ok()
+}
+
+/// The largest integer below which an `f64` holds every integer exactly, 2^53.
+const MAX_EXACT_INTEGER: u64 = 1 << 53;
+
+/// The `f64` a Numeric field is written from, if it holds `number` exactly. Numbers with a
+/// fraction or an exponent are read as `f64` to begin with, so only integers can fail.
+pub(crate) fn exact_f64(number: &serde_json::Number) -> Option<f64> {
+    let is_exact = match (number.as_i64(), number.as_u64()) {
+        (Some(integer), _) => integer.unsigned_abs() <= MAX_EXACT_INTEGER,
+        (None, Some(integer)) => integer <= MAX_EXACT_INTEGER,
+        (None, None) => true,
+    };
+    number.as_f64().filter(|_| is_exact)
+}
+
+/// Renders a JSON number the way its digits are counted for the field width.
+fn number_text(number: &serde_json::Number) -> String {
+    match number.as_f64() {
+        Some(value) if !(number.is_i64() || number.is_u64()) => value.to_string(),
+        _ => number.to_string(),
+    }
+}
+
+impl FieldSchema {
+    /// Name of the field written when the features have no attributes at all,
+    /// since a .dbf requires at least one field. It holds the zero-based record number.
+    pub const PLACEHOLDER_FIELD: &'static str = "FID";
+    /// The most fields a dbf holds.
+    pub const MAX_FIELDS: usize = 255;
+    /// The longest record, deletion flag included, that dBASE readers accept.
+    pub const MAX_RECORD_LEN: u64 = 4000;
+    /// The most records a dbf holds, as their count is stored as a 32-bit number.
+    pub const MAX_RECORDS: u64 = u32::MAX as u64;
+
+    /// Infers the schema from the attributes of every feature.
+    ///
+    /// Fields are the union of all property names, in the order they are first seen.
+    /// Properties holding both numbers and strings fall back to Character fields.
+    /// Field names longer than 10 characters are truncated, and numbered when that
+    /// makes them collide with another field.
+    pub fn infer<'a>(
+        attributes: impl IntoIterator<Item = &'a Map<String, JsonValue>>,
+    ) -> Result<Self, ConversionError> {
+        Self::infer_with_options(attributes, &ConversionOptions::default())
+    }
+
+    /// Same as `infer`, applying the options that affect the schema such as `long_text`.
+    pub fn infer_with_options<'a>(
+        attributes: impl IntoIterator<Item = &'a Map<String, JsonValue>>,
+        options: &ConversionOptions,
+    ) -> Result<Self, ConversionError> {
+        Ok(Self::infer_with_warnings(attributes, options)?.0)
+    }
+
+    /// Same as `infer_with_options`, also returning the truncations, collisions,
+    /// type fallbacks and width clamps made along the way.
+    pub fn infer_with_warnings(
+        attributes: impl IntoIterator<Item = impl Borrow<Map<String, JsonValue>>>,
+        options: &ConversionOptions,
+    ) -> Result<(Self, Vec<SchemaWarning>), ConversionError> {
+        let mut stats: Vec<(String, PropertyStats)> = Vec::new();
+        for (feature_index, feature_attributes) in attributes.into_iter().enumerate() {
+            for (prop_name, value) in feature_attributes.borrow().iter() {
+                let index = match stats.iter().position(|(name, _)| name == prop_name) {
+                    Some(index) => index,
+                    None => {
+                        stats.push((prop_name.clone(), PropertyStats::default()));
+                        stats.len() - 1
+                    }
+                };
+                let value = match options.field_types.get(prop_name) {
+                    Some(DbfType::Numeric) => coerce_to_number(prop_name, value),
+                    _ => Ok(value.clone()),
+                };
+                value
+                    .and_then(|value| stats[index].1.add(prop_name, &value))
+                    .map_err(|err| err.at_feature(feature_index))?;
+            }
+        }
+        let mut warnings = Vec::new();
+        let mut fields: Vec<FieldDefinition> = Vec::new();
+        for (property, mut stats) in stats {
+            match options.field_types.get(&property) {
+                Some(DbfType::Character) => {
+                    stats.has_numbers = false;
+                    stats.has_strings = true;
+                }
+                Some(DbfType::Numeric) => stats.has_numbers = true,
+                None => {}
+            }
+            let width_override = options
+                .string_widths
+                .get(&property)
+                .copied()
+                .or(options.default_string_width);
+            let fields_of_property =
+                stats.into_fields(property, options.long_text, width_override, &mut warnings);
+            for mut field in fields_of_property {
+                let taken: Vec<String> = fields.iter().map(|f| f.name.clone()).collect();
+                let suffix = field
+                    .part
+                    .map(|part| format!("_{}", part))
+                    .unwrap_or_default();
+                let (name, collided) = field_name(&field.property, &suffix, &taken);
+                if collided {
+                    warnings.push(SchemaWarning::NameCollision {
+                        property: field.property.clone(),
+                        name: name.clone(),
+                    });
+                } else if name != format!("{}{}", field.property, suffix) {
+                    warnings.push(SchemaWarning::NameTruncated {
+                        property: field.property.clone(),
+                        name: name.clone(),
+                    });
+                }
+                field.name = name;
+                fields.push(field);
+            }
+        }
+        if fields.is_empty() {
+            return Ok((Self::placeholder(), warnings));
+        }
+        Ok((Self { fields }, warnings))
+    }
+
+    /// Parses a GeoJSON document and infers the schema of all of its features with the
+    /// default options, without creating any output. See `infer_schema` to pass options.
+    pub fn from_geojson_str(json: &str) -> Result<Self, ConversionError> {
+        crate::infer_schema(json, &ConversionOptions::default())
+    }
+
+    /// A schema holding only the placeholder `FID` field.
+    pub fn placeholder() -> Self {
+        Self {
+            fields: vec![FieldDefinition {
+                property: Self::PLACEHOLDER_FIELD.to_string(),
+                name: Self::PLACEHOLDER_FIELD.to_string(),
+                field_type: DbfType::Numeric,
+                width: 10,
+                decimals: 0,
+                part: None,
+            }],
+        }
+    }
+
+    /// Moves the field(s) read from `property` to the front, keeping their order.
+    pub fn move_to_front(&mut self, property: &str) {
+        self.fields.sort_by_key(|field| field.property != property);
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        *self == Self::placeholder()
+    }
+
+    /// Size in bytes of a record: its deletion flag followed by every field.
+    pub(crate) fn record_len(&self) -> u64 {
+        1 + self
+            .fields
+            .iter()
+            .map(|field| field.width as u64)
+            .sum::<u64>()
+    }
+
+    /// Size in bytes of the header: the file header, the field descriptors and their terminator.
+    pub(crate) fn header_len(&self) -> u64 {
+        DBF_HEADER_SIZE + DBF_FIELD_DESCRIPTOR_SIZE * self.fields.len() as u64 + 1
+    }
+
+    /// Checks that every field name is a valid dbf field name, and that no two fields share a
+    /// name (dbf field names are case-insensitive), as the record of the second field would
+    /// overwrite the first. Inferred schemas number colliding names; this catches the
+    /// schemas written by hand.
+    ///
+    /// Also checks the schema against the `MAX_FIELDS` and `MAX_RECORD_LEN` of dBASE, which
+    /// many wide Character fields easily exceed.
+    pub fn validate(&self) -> Result<(), ConversionError> {
+        if self.fields.len() > Self::MAX_FIELDS {
+            return Err(ConversionError::TooManyFields {
+                count: self.fields.len(),
+                limit: Self::MAX_FIELDS,
+            });
+        }
+        if self.record_len() > Self::MAX_RECORD_LEN {
+            let mut widest: Vec<&FieldDefinition> = self.fields.iter().collect();
+            widest.sort_by_key(|field| std::cmp::Reverse(field.width));
+            return Err(ConversionError::RecordTooLong {
+                len: self.record_len(),
+                limit: Self::MAX_RECORD_LEN,
+                widest: widest
+                    .iter()
+                    .take(3)
+                    .map(|field| format!("{} ({} bytes)", field.name, field.width))
+                    .collect(),
+            });
+        }
+        let mut names = HashSet::new();
+        for field in self.fields.iter() {
+            field_name_of(field)?;
+            if !names.insert(field.name.to_ascii_uppercase()) {
+                return Err(ConversionError::DuplicateFieldName {
+                    name: field.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn table_builder(&self) -> Result<TableWriterBuilder, ConversionError> {
+        self.validate()?;
+        let mut builder = TableWriterBuilder::new();
+        for field in self.fields.iter() {
+            let name = field_name_of(field)?;
+            builder = match field.field_type {
+                DbfType::Character => builder.add_character_field(name, field.width),
+                DbfType::Numeric => builder.add_numeric_field(name, field.width, field.decimals),
+            };
+        }
+        Ok(builder)
+    }
+
+    /// Builds the dbf record for a feature's attributes.
+    ///
+    /// Attributes missing from the feature are written as null values.
+    pub fn record(&self, attributes: &Map<String, JsonValue>) -> Result<Record, ConversionError> {
+        let mut record = Record::default();
+        for field in self.fields.iter() {
+            let value = match (field.field_type, attributes.get(&field.property)) {
+                (DbfType::Numeric, Some(JsonValue::Number(val))) => {
+                    FieldValue::Numeric(val.as_f64())
+                }
+                (DbfType::Numeric, None | Some(JsonValue::Null)) => FieldValue::Numeric(None),
+                (DbfType::Numeric, Some(JsonValue::String(val))) if parse_number(val).is_some() => {
+                    FieldValue::Numeric(parse_number(val).and_then(|number| number.as_f64()))
+                }
+                (DbfType::Character, Some(JsonValue::String(val))) => {
+                    let text = match field.part {
+                        Some(part) => text_chunks(val).nth(part - 1),
+                        None => Some(&val[..]),
+                    };
+                    FieldValue::Character(
+                        text.map(|text| truncate_to_width(text, field.width as usize).to_string()),
+                    )
+                }
+                (DbfType::Character, Some(JsonValue::Number(val))) => {
+                    FieldValue::Character(Some(number_text(val)))
+                }
+                (DbfType::Character, None | Some(JsonValue::Null)) => FieldValue::Character(None),
+                (expected, Some(value @ JsonValue::String(_))) => {
+                    return Err(ConversionError::PropertyTypeMismatch {
+                        property: field.property.clone(),
+                        expected,
+                        value: describe_value(value),
+                    })
+                }
+                (_, value) => {
+                    return Err(ConversionError::UnsupportedPropertyType {
+                        property: field.property.clone(),
+                        value: value.map(describe_value).unwrap_or_default(),
+                    })
+                }
+            };
+            record.insert(field.name.clone(), value);
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attributes(value: JsonValue) -> Map<String, JsonValue> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn infers_union_of_fields_with_widths() {
+        let features = [
+            attributes(json!({"name": "Rochester", "height": 12.5})),
+            attributes(json!({"name": "Ithaca", "height": 1234, "zip": "14850"})),
+            attributes(json!({"name": null, "mixed": 3})),
+            attributes(json!({"mixed": "three"})),
+        ];
+        let schema = FieldSchema::infer(features.iter()).unwrap();
+
+        let summary: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_type, f.width, f.decimals))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("height", DbfType::Numeric, 6, 1),
+                ("name", DbfType::Character, 9, 0),
+                ("zip", DbfType::Character, 5, 0),
+                ("mixed", DbfType::Character, 5, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let features = [attributes(json!({"name": "a", "ranking": 1}))];
+        let schema = FieldSchema::infer(features.iter()).unwrap();
+
+        let serialized = serde_json::to_string(&schema).unwrap();
+        let deserialized: FieldSchema = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(schema, deserialized);
+    }
+
+    #[test]
+    fn applies_the_long_text_policy() {
+        let description = "é".repeat(150);
+        let features = [attributes(json!({"description": description, "name": "a"}))];
+
+        let schema = FieldSchema::infer(features.iter()).unwrap();
+        assert_eq!(schema.fields[0].name, "descriptio");
+        assert_eq!(schema.fields[0].width, 254);
+        let record = schema.record(&features[0]).unwrap();
+        assert_eq!(
+            record.get("descriptio"),
+            Some(&FieldValue::Character(Some("é".repeat(127))))
+        );
+
+        let options = ConversionOptions {
+            long_text: LongTextPolicy::Split { max_fields: 4 },
+            ..Default::default()
+        };
+        let schema = FieldSchema::infer_with_options(features.iter(), &options).unwrap();
+        let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["descript_1", "descript_2", "name"]);
+        let record = schema.record(&features[0]).unwrap();
+        let joined: String = ["descript_1", "descript_2"]
+            .iter()
+            .map(|name| match record.get(name) {
+                Some(FieldValue::Character(Some(text))) => text.clone(),
+                _ => panic!("missing {}", name),
+            })
+            .collect();
+        assert_eq!(joined, description);
+
+        let options = ConversionOptions {
+            long_text: LongTextPolicy::Drop,
+            ..Default::default()
+        };
+        let schema = FieldSchema::infer_with_options(features.iter(), &options).unwrap();
+        let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["name"]);
+    }
+
+    #[test]
+    fn truncates_and_numbers_long_field_names() {
+        let features = [attributes(
+            json!({"population_2010": 1, "population_2020": 2, "pop": 3}),
+        )];
+        let schema = FieldSchema::infer(features.iter()).unwrap();
+        let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["pop", "population", "populati_1"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_field_names() {
+        let features = [attributes(
+            json!({"Station Name": "a", "station name": "b", "station_id": 1}),
+        )];
+        let mut schema = FieldSchema::infer(features.iter()).unwrap();
+        let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Station Na", "station _1", "station_id"]);
+        assert!(schema.validate().is_ok());
+
+        schema.fields[1].name = "STATION NA".to_string();
+        assert!(matches!(
+            schema.validate(),
+            Err(ConversionError::DuplicateFieldName { name }) if name == "STATION NA"
+        ));
+        assert!(schema.table_builder().is_err());
+    }
+
+    #[test]
+    fn rejects_schemas_past_the_dbase_limits() {
+        let wide: Map<String, JsonValue> = (0..16)
+            .map(|i| (format!("notes_{}", i), JsonValue::from("x".repeat(254))))
+            .collect();
+        let schema = FieldSchema::infer([&wide]).unwrap();
+        assert!(schema.record_len() > FieldSchema::MAX_RECORD_LEN);
+        assert!(matches!(
+            schema.validate(),
+            Err(ConversionError::RecordTooLong { widest, .. }) if widest.len() == 3
+        ));
+
+        let many: Map<String, JsonValue> = (0..256)
+            .map(|i| (format!("f{}", i), JsonValue::from(i)))
+            .collect();
+        let schema = FieldSchema::infer([&many]).unwrap();
+        assert!(matches!(
+            schema.validate(),
+            Err(ConversionError::TooManyFields {
+                count: 256,
+                limit: 255
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_truncations_collisions_and_fallbacks() {
+        let features = [
+            attributes(json!({"population_2010": 1, "population_2020": 2, "mixed": 3})),
+            attributes(json!({"mixed": "three"})),
+        ];
+        let (_, warnings) =
+            FieldSchema::infer_with_warnings(features.iter(), &ConversionOptions::default())
+                .unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                SchemaWarning::TypeFallback {
+                    property: "mixed".to_string()
+                },
+                SchemaWarning::NameTruncated {
+                    property: "population_2010".to_string(),
+                    name: "population".to_string()
+                },
+                SchemaWarning::NameCollision {
+                    property: "population_2020".to_string(),
+                    name: "populati_1".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn infers_from_a_geojson_string() {
+        let contents = std::fs::read_to_string("./fixtures/points.geojson").unwrap();
+        let schema = FieldSchema::from_geojson_str(&contents).unwrap();
+        assert!(schema.fields.iter().any(|field| field.name == "category"));
+        assert!(FieldSchema::from_geojson_str("{").is_err());
+    }
+
+    #[test]
+    fn overrides_character_field_widths() {
+        let features = [attributes(
+            json!({"name": "Main Street", "address": "1 Main Street", "code": "A1", "rank": 12345}),
+        )];
+        let options = ConversionOptions {
+            default_string_width: Some(80),
+            string_widths: [("name".to_string(), 4)].into(),
+            ..Default::default()
+        };
+        let (schema, warnings) =
+            FieldSchema::infer_with_warnings(features.iter(), &options).unwrap();
+        let widths: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.width))
+            .collect();
+        assert_eq!(
+            widths,
+            [("address", 80), ("code", 80), ("name", 4), ("rank", 5)]
+        );
+        assert_eq!(
+            warnings,
+            [SchemaWarning::WidthClamped {
+                property: "name".to_string(),
+                width: 4
+            }]
+        );
+        assert_eq!(
+            schema.record(&features[0]).unwrap().get("name"),
+            Some(&FieldValue::Character(Some("Main".to_string())))
+        );
+    }
+
+    #[test]
+    fn forces_the_requested_field_types() {
+        let features = [
+            attributes(json!({"zip": "02134", "floors": "12"})),
+            attributes(json!({"zip": 10001, "floors": 3})),
+        ];
+        let options = ConversionOptions::default().field_types(
+            [
+                ("zip".to_string(), DbfType::Character),
+                ("floors".to_string(), DbfType::Numeric),
+            ]
+            .into(),
+        );
+        let (schema, warnings) =
+            FieldSchema::infer_with_warnings(features.iter(), &options).unwrap();
+        assert!(warnings.is_empty());
+        let types: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_type, f.width))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("floors", DbfType::Numeric, 2),
+                ("zip", DbfType::Character, 5)
+            ]
+        );
+
+        let record = schema.record(&features[0]).unwrap();
+        assert_eq!(
+            record.get("zip"),
+            Some(&FieldValue::Character(Some("02134".to_string())))
+        );
+        assert_eq!(record.get("floors"), Some(&FieldValue::Numeric(Some(12.0))));
+        let record = schema.record(&features[1]).unwrap();
+        assert_eq!(
+            record.get("zip"),
+            Some(&FieldValue::Character(Some("10001".to_string())))
+        );
+
+        let features = [attributes(json!({"floors": "ground"}))];
+        assert!(matches!(
+            FieldSchema::infer_with_options(features.iter(), &options),
+            Err(ConversionError::Feature { index: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use shapefile::record::{EsriShape, WritableShape};
+use shapefile::{Error, Shape, ShapeType};
+
+const FILE_CODE: i32 = 9994;
+const VERSION: i32 = 1000;
+pub(crate) const HEADER_SIZE: i32 = 100;
+const RECORD_HEADER_SIZE: i32 = 8;
+/// The header bbox of a file without any (non-null) shape. The spec leaves it undefined;
+/// GDAL and ArcGIS write, and expect, zeros.
+const EMPTY_BBOX: [f64; 8] = [0.0; 8];
+
+/// The .prj of a shapefile in WGS 84, in the ESRI flavour of WKT that ArcGIS writes.
+pub(crate) const WGS84_PRJ: &str = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+
+/// Writes the .shp and (optionally) the .shx of a shapefile.
+///
+/// Unlike `shapefile::ShapeWriter`, which fixes the file's shape type from the first
+/// record it is given, this writer accepts any `shapefile::Shape` and allows Null Shape
+/// records (shape type 0) to be interleaved with records of the file's shape type.
+pub struct ShapeRecordWriter<T: Write + Seek> {
+    shp_dest: T,
+    shx_dest: Option<T>,
+    shape_type: ShapeType,
+    bbox: Option<[f64; 8]>,
+    file_length: i32,
+    rec_num: i32,
+    header_reserved: bool,
+    closed: bool,
+}
+
+impl<T: Write + Seek> ShapeRecordWriter<T> {
+    pub fn new(shp_dest: T) -> Self {
+        Self::with_optional_shx(shp_dest, None)
+    }
+
+    pub fn with_shx(shp_dest: T, shx_dest: T) -> Self {
+        Self::with_optional_shx(shp_dest, Some(shx_dest))
+    }
+
+    fn with_optional_shx(shp_dest: T, shx_dest: Option<T>) -> Self {
+        Self {
+            shp_dest,
+            shx_dest,
+            shape_type: ShapeType::NullShape,
+            bbox: None,
+            file_length: HEADER_SIZE / 2,
+            rec_num: 1,
+            header_reserved: false,
+            closed: false,
+        }
+    }
+
+    /// The shape type written to the file header.
+    ///
+    /// This stays `ShapeType::NullShape` until a non-null shape has been written.
+    pub fn shape_type(&self) -> ShapeType {
+        self.shape_type
+    }
+
+    /// The size in bytes of the .shp written so far, including its header.
+    pub fn file_size(&self) -> u64 {
+        self.file_length as u64 * 2
+    }
+
+    pub fn write_shape(&mut self, shape: &Shape) -> Result<(), Error> {
+        match shape {
+            Shape::NullShape => self.write_null_shape(),
+            Shape::Point(s) => self.write_esri_shape(s),
+            Shape::PointM(s) => self.write_esri_shape(s),
+            Shape::PointZ(s) => self.write_esri_shape(s),
+            Shape::Polyline(s) => self.write_esri_shape(s),
+            Shape::PolylineM(s) => self.write_esri_shape(s),
+            Shape::PolylineZ(s) => self.write_esri_shape(s),
+            Shape::Polygon(s) => self.write_esri_shape(s),
+            Shape::PolygonM(s) => self.write_esri_shape(s),
+            Shape::PolygonZ(s) => self.write_esri_shape(s),
+            Shape::Multipoint(s) => self.write_esri_shape(s),
+            Shape::MultipointM(s) => self.write_esri_shape(s),
+            Shape::MultipointZ(s) => self.write_esri_shape(s),
+            Shape::Multipatch(s) => self.write_esri_shape(s),
+        }
+    }
+
+    pub fn write_null_shape(&mut self) -> Result<(), Error> {
+        self.reserve_header()?;
+        let content_length = std::mem::size_of::<i32>() as i32 / 2;
+        self.write_record_header(content_length)?;
+        self.shp_dest
+            .write_all(&(ShapeType::NullShape as i32).to_le_bytes())?;
+        self.file_length += content_length;
+        Ok(())
+    }
+
+    pub fn write_esri_shape<S: EsriShape>(&mut self, shape: &S) -> Result<(), Error> {
+        match (self.shape_type, S::shapetype()) {
+            (ShapeType::NullShape, t) => self.shape_type = t,
+            (t1, t2) if t1 != t2 => {
+                return Err(Error::MismatchShapeType {
+                    requested: t1,
+                    actual: t2,
+                });
+            }
+            _ => {}
+        }
+        self.reserve_header()?;
+
+        let content_length = (shape.size_in_bytes() + std::mem::size_of::<i32>()) as i32 / 2;
+        self.write_record_header(content_length)?;
+        self.shp_dest
+            .write_all(&(S::shapetype() as i32).to_le_bytes())?;
+        shape.write_to(&mut self.shp_dest)?;
+        self.file_length += content_length;
+        self.grow_bbox(shape);
+        Ok(())
+    }
+
+    /// Rewrites the headers of the .shp and .shx with the final file lengths and bbox.
+    ///
+    /// Called automatically when the writer is dropped; call it directly to handle errors.
+    pub fn close(&mut self) -> Result<(), Error> {
+        if self.closed {
+            return Ok(());
+        }
+        self.reserve_header()?;
+        let bbox = self.bbox.unwrap_or(EMPTY_BBOX);
+
+        self.shp_dest.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.shp_dest, self.file_length, self.shape_type, &bbox)?;
+        self.shp_dest.seek(SeekFrom::End(0))?;
+        self.shp_dest.flush()?;
+        if let Some(shx_dest) = &mut self.shx_dest {
+            let shx_length = HEADER_SIZE / 2 + (self.rec_num - 1) * 4;
+            shx_dest.seek(SeekFrom::Start(0))?;
+            write_header(shx_dest, shx_length, self.shape_type, &bbox)?;
+            shx_dest.seek(SeekFrom::End(0))?;
+            shx_dest.flush()?;
+        }
+        self.closed = true;
+        Ok(())
+    }
+
+    fn reserve_header(&mut self) -> Result<(), Error> {
+        if !self.header_reserved {
+            let placeholder = [0u8; HEADER_SIZE as usize];
+            self.shp_dest.write_all(&placeholder)?;
+            if let Some(shx_dest) = &mut self.shx_dest {
+                shx_dest.write_all(&placeholder)?;
+            }
+            self.header_reserved = true;
+        }
+        Ok(())
+    }
+
+    fn write_record_header(&mut self, content_length: i32) -> Result<(), Error> {
+        self.shp_dest.write_all(&self.rec_num.to_be_bytes())?;
+        self.shp_dest.write_all(&content_length.to_be_bytes())?;
+        if let Some(shx_dest) = &mut self.shx_dest {
+            shx_dest.write_all(&self.file_length.to_be_bytes())?;
+            shx_dest.write_all(&content_length.to_be_bytes())?;
+        }
+        self.file_length += RECORD_HEADER_SIZE / 2;
+        self.rec_num += 1;
+        Ok(())
+    }
+
+    fn grow_bbox<S: EsriShape>(&mut self, shape: &S) {
+        let [x_min, x_max] = shape.x_range();
+        let [y_min, y_max] = shape.y_range();
+        let [z_min, z_max] = shape.z_range();
+        let [m_min, m_max] = shape.m_range();
+        let bbox = self
+            .bbox
+            .get_or_insert([x_min, y_min, x_max, y_max, z_min, z_max, m_min, m_max]);
+        bbox[0] = bbox[0].min(x_min);
+        bbox[1] = bbox[1].min(y_min);
+        bbox[2] = bbox[2].max(x_max);
+        bbox[3] = bbox[3].max(y_max);
+        bbox[4] = bbox[4].min(z_min);
+        bbox[5] = bbox[5].max(z_max);
+        bbox[6] = bbox[6].min(m_min);
+        bbox[7] = bbox[7].max(m_max);
+    }
+}
+
+/// The sizes in bytes of the .shp and .shx written for `shapes`.
+pub(crate) fn file_sizes<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> (u64, u64) {
+    let (mut shp, mut shx) = (HEADER_SIZE as u64, HEADER_SIZE as u64);
+    for shape in shapes {
+        shp += record_size(shape);
+        shx += RECORD_HEADER_SIZE as u64;
+    }
+    (shp, shx)
+}
+
+/// The size in bytes of the .shp record written for `shape`.
+pub(crate) fn record_size(shape: &Shape) -> u64 {
+    let content = match shape {
+        Shape::NullShape => 0,
+        Shape::Point(s) => s.size_in_bytes(),
+        Shape::PointM(s) => s.size_in_bytes(),
+        Shape::PointZ(s) => s.size_in_bytes(),
+        Shape::Polyline(s) => s.size_in_bytes(),
+        Shape::PolylineM(s) => s.size_in_bytes(),
+        Shape::PolylineZ(s) => s.size_in_bytes(),
+        Shape::Polygon(s) => s.size_in_bytes(),
+        Shape::PolygonM(s) => s.size_in_bytes(),
+        Shape::PolygonZ(s) => s.size_in_bytes(),
+        Shape::Multipoint(s) => s.size_in_bytes(),
+        Shape::MultipointM(s) => s.size_in_bytes(),
+        Shape::MultipointZ(s) => s.size_in_bytes(),
+        Shape::Multipatch(s) => s.size_in_bytes(),
+    };
+    // Each record holds its shape type before the shape itself.
+    (RECORD_HEADER_SIZE as usize + std::mem::size_of::<i32>() + content) as u64
+}
+
+impl<T: Write + Seek> Drop for ShapeRecordWriter<T> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn write_header<W: Write>(
+    dest: &mut W,
+    file_length: i32,
+    shape_type: ShapeType,
+    bbox: &[f64; 8],
+) -> Result<(), Error> {
+    dest.write_all(&FILE_CODE.to_be_bytes())?;
+    dest.write_all(&[0u8; 20])?;
+    dest.write_all(&file_length.to_be_bytes())?;
+    dest.write_all(&VERSION.to_le_bytes())?;
+    dest.write_all(&(shape_type as i32).to_le_bytes())?;
+    for value in bbox {
+        dest.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn interleaves_null_shapes_with_points() {
+        let mut shp = Cursor::new(Vec::new());
+        let mut shx = Cursor::new(Vec::new());
+        {
+            let mut writer = ShapeRecordWriter::with_shx(&mut shp, &mut shx);
+            writer.write_shape(&Shape::NullShape).unwrap();
+            writer
+                .write_shape(&Shape::Point(shapefile::Point::new(1.0, 2.0)))
+                .unwrap();
+            writer.write_shape(&Shape::NullShape).unwrap();
+        }
+
+        shp.set_position(0);
+        shx.set_position(0);
+        let shapes = shapefile::ShapeReader::with_shx(shp, shx)
+            .unwrap()
+            .read()
+            .unwrap();
+        assert_eq!(shapes.len(), 3);
+        assert!(matches!(shapes[0], Shape::NullShape));
+        assert!(matches!(shapes[1], Shape::Point(p) if p.x == 1.0 && p.y == 2.0));
+        assert!(matches!(shapes[2], Shape::NullShape));
+    }
+
+    #[test]
+    fn writes_a_zeroed_bbox_when_empty() {
+        let mut shp = Cursor::new(Vec::new());
+        let mut shx = Cursor::new(Vec::new());
+        ShapeRecordWriter::with_shx(&mut shp, &mut shx)
+            .close()
+            .unwrap();
+
+        for file in [shp.get_ref(), shx.get_ref()] {
+            assert_eq!(file.len(), HEADER_SIZE as usize);
+            assert_eq!(file[..4], FILE_CODE.to_be_bytes());
+            assert_eq!(file[24..28], (HEADER_SIZE / 2).to_be_bytes());
+            assert_eq!(file[28..32], VERSION.to_le_bytes());
+            assert_eq!(file[32..36], (ShapeType::NullShape as i32).to_le_bytes());
+            assert!(file[36..].iter().all(|&byte| byte == 0));
+        }
+        shp.set_position(0);
+        let header = *shapefile::ShapeReader::new(shp).unwrap().header();
+        assert_eq!(header.bbox.min.x, 0.0);
+        assert_eq!(header.bbox.max.y, 0.0);
+    }
+}
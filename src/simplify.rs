@@ -0,0 +1,107 @@
+//! Ramer-Douglas-Peucker simplification of lines and rings.
+
+use shapefile::Point;
+
+/// Drops the vertices of `points` that lie within `epsilon` of the simplified line, keeping
+/// the first and last. A closed ring stays closed, as its first and last points are kept.
+pub(crate) fn simplify_points(points: &[Point], epsilon: f64) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // Ranges still to simplify, by the index of their first and last point.
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|index| {
+                let distance = segment_distance(&points[index], &points[first], &points[last]);
+                (index, distance)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((index, distance)) = farthest {
+            if distance > epsilon {
+                keep[index] = true;
+                ranges.push((first, index));
+                ranges.push((index, last));
+            }
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+/// Distance from `point` to the segment from `start` to `end`.
+fn segment_distance(point: &Point, start: &Point, end: &Point) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.x - start.x) * dx + (point.y - start.y) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (start.x + t * dx, start.y + t * dy);
+    (point.x - x).hypot(point.y - y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coordinates: &[(f64, f64)]) -> Vec<Point> {
+        coordinates.iter().map(|&(x, y)| Point::new(x, y)).collect()
+    }
+
+    #[test]
+    fn keeps_the_vertices_farther_than_epsilon() {
+        let line = points(&[
+            (0.0, 0.0),
+            (1.0, 0.1),
+            (2.0, -0.1),
+            (3.0, 5.0),
+            (4.0, 6.0),
+            (5.0, 7.0),
+            (6.0, 8.1),
+            (7.0, 9.0),
+            (8.0, 9.0),
+            (9.0, 9.0),
+        ]);
+        let simplified = simplify_points(&line, 0.5);
+        assert_eq!(
+            simplified,
+            points(&[(0.0, 0.0), (2.0, -0.1), (3.0, 5.0), (7.0, 9.0), (9.0, 9.0)])
+        );
+        assert!(simplified.len() < line.len());
+        assert_eq!(simplify_points(&line[..2], 10.0), line[..2]);
+    }
+
+    #[test]
+    fn keeps_rings_closed() {
+        let ring = points(&[
+            (0.0, 0.0),
+            (0.0, 5.0),
+            (0.0, 10.0),
+            (5.0, 10.01),
+            (10.0, 10.0),
+            (10.0, 0.0),
+            (5.0, 0.0),
+            (0.0, 0.0),
+        ]);
+        let simplified = simplify_points(&ring, 0.1);
+        assert_eq!(
+            simplified,
+            points(&[
+                (0.0, 0.0),
+                (0.0, 10.0),
+                (10.0, 10.0),
+                (10.0, 0.0),
+                (0.0, 0.0)
+            ])
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde_json::{Map, Value as JsonValue};
+
+use crate::schema::text_chunks;
+use crate::{DbfType, FieldSchema};
+
+/// The distribution of every dbf field's values, keyed by field name.
+pub type FieldStats = BTreeMap<String, FieldStat>;
+
+/// The distribution of a single field's values, as computed by
+/// `FeatureCollectionToShpWriter::compute_stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldStat {
+    /// Smallest and largest number, for Numeric fields.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Features where the property is missing or `null`.
+    pub null_count: usize,
+    /// Distinct non-null values.
+    pub unique_count: usize,
+    /// Longest value in bytes, before any truncation to the field width, for Character fields.
+    pub max_str_len: Option<usize>,
+}
+
+/// Computes the statistics of the values each field of `schema` is written from.
+pub(crate) fn field_stats(
+    attributes: impl IntoIterator<Item = Map<String, JsonValue>>,
+    schema: &FieldSchema,
+) -> FieldStats {
+    let mut stats: Vec<(FieldStat, HashSet<String>)> =
+        vec![Default::default(); schema.fields.len()];
+    for attributes in attributes {
+        for (field, (stat, unique)) in schema.fields.iter().zip(stats.iter_mut()) {
+            let value = match attributes.get(&field.property) {
+                None | Some(JsonValue::Null) => {
+                    stat.null_count += 1;
+                    continue;
+                }
+                Some(value) => value,
+            };
+            let number = match value {
+                JsonValue::Number(number) => number.as_f64(),
+                JsonValue::String(text) if field.field_type == DbfType::Numeric => {
+                    text.trim().parse().ok()
+                }
+                _ => None,
+            };
+            match (field.field_type, number, value) {
+                (DbfType::Numeric, Some(number), _) => {
+                    stat.min = Some(stat.min.map_or(number, |min| min.min(number)));
+                    stat.max = Some(stat.max.map_or(number, |max| max.max(number)));
+                    unique.insert(number.to_string());
+                }
+                (_, _, value) => {
+                    let text = match value {
+                        JsonValue::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    let text = match field.part {
+                        Some(part) => match text_chunks(&text).nth(part - 1) {
+                            Some(chunk) => chunk.to_string(),
+                            None => {
+                                stat.null_count += 1;
+                                continue;
+                            }
+                        },
+                        None => text,
+                    };
+                    stat.max_str_len = Some(stat.max_str_len.unwrap_or(0).max(text.len()));
+                    unique.insert(text);
+                }
+            }
+        }
+    }
+    schema
+        .fields
+        .iter()
+        .zip(stats)
+        .map(|(field, (mut stat, unique))| {
+            stat.unique_count = unique.len();
+            (field.name.clone(), stat)
+        })
+        .collect()
+}
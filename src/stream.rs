@@ -0,0 +1,486 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::path::Path;
+
+use geojson::{Feature, FeatureCollection};
+use serde::de::Error as _;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::convert::feature_attributes;
+use crate::text::prepare_text_values;
+use crate::{
+    fill_missing_properties, open_input_file, CollectionMetadata, ConversionError,
+    ConversionOptions, ConversionSummary, FeatureCollectionToShpWriter, FeatureIdCheck,
+    FieldSchema, PreparedCollection, SchemaSample,
+};
+
+/// Bytes of the start of the input kept to show in parse errors.
+const CONTEXT_BYTES: usize = 1024;
+
+/// Reads the features of a FeatureCollection (or of a JSON array of Features) one at a time,
+/// without holding the whole document in memory.
+///
+/// Only one feature is parsed at a time. The other members of the collection, such as its
+/// `bbox`, are collected along the way into `metadata()`, which is complete once the reader
+/// is exhausted. Unlike `FeatureCollectionToShpWriter::new`, a single Feature, a bare
+/// Geometry or a TopoJSON Topology cannot be read.
+pub struct FeatureReader<R> {
+    reader: R,
+    state: State,
+    /// Number of features read so far.
+    index: usize,
+    /// Number of bytes read so far.
+    offset: u64,
+    /// The start of the input, for parse errors.
+    context: Vec<u8>,
+    /// The `type` member of the top-level object.
+    collection_type: Option<String>,
+    metadata: CollectionMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    /// Between the members of the top-level object.
+    Members {
+        first: bool,
+    },
+    /// Between the features; `bare` for a top-level array.
+    Features {
+        first: bool,
+        bare: bool,
+    },
+    Done,
+}
+
+impl FeatureReader<Box<dyn BufRead + Send>> {
+    /// Opens the GeoJSON file at `path`, naming it when it does not exist. A gzipped file is
+    /// decompressed as it is read.
+    pub fn open(path: &Path) -> Result<Self, ConversionError> {
+        Ok(Self::new(open_input_file(path)?))
+    }
+}
+
+impl<R: BufRead> FeatureReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: State::Start,
+            index: 0,
+            offset: 0,
+            context: Vec::new(),
+            collection_type: None,
+            metadata: CollectionMetadata::default(),
+        }
+    }
+
+    /// The `bbox` and foreign members of the collection read so far.
+    pub fn metadata(&self) -> &CollectionMetadata {
+        &self.metadata
+    }
+
+    fn read_next(&mut self) -> Result<Option<Feature>, ConversionError> {
+        loop {
+            match self.state {
+                State::Start => {
+                    self.skip_whitespace()?;
+                    match self.peek()? {
+                        Some(b'{') => self.state = State::Members { first: true },
+                        Some(b'[') => {
+                            self.state = State::Features {
+                                first: true,
+                                bare: true,
+                            }
+                        }
+                        _ => {
+                            return Err(self.error("expected `{` or `[` at the start of the input"))
+                        }
+                    }
+                    self.consume();
+                }
+                State::Members { first } => {
+                    self.skip_whitespace()?;
+                    if self.peek()? == Some(b'}') {
+                        self.consume();
+                        self.state = State::Done;
+                        return self.end_of_collection().map(|_| None);
+                    }
+                    if !first {
+                        self.expect(b',', "expected `,` or `}` after a member")?;
+                        self.skip_whitespace()?;
+                    }
+                    if self.peek()? != Some(b'"') {
+                        return Err(self.error("expected a member name"));
+                    }
+                    let key: String = serde_json::from_slice(&self.read_value()?)
+                        .map_err(|err| self.json_error(err))?;
+                    self.skip_whitespace()?;
+                    self.expect(b':', "expected `:` after a member name")?;
+                    self.skip_whitespace()?;
+                    if key == "features" {
+                        self.expect(b'[', "expected the features to be an array")?;
+                        self.state = State::Features {
+                            first: true,
+                            bare: false,
+                        };
+                        continue;
+                    }
+                    let value: JsonValue = serde_json::from_slice(&self.read_value()?)
+                        .map_err(|err| self.json_error(err))?;
+                    self.add_member(key, value)?;
+                    self.state = State::Members { first: false };
+                }
+                State::Features { first, bare } => {
+                    self.skip_whitespace()?;
+                    if self.peek()? == Some(b']') {
+                        self.consume();
+                        self.state = match bare {
+                            true => State::Done,
+                            false => State::Members { first: false },
+                        };
+                        continue;
+                    }
+                    if !first {
+                        self.expect(b',', "expected `,` or `]` after a feature")?;
+                        self.skip_whitespace()?;
+                    }
+                    let raw = self.read_value()?;
+                    let index = self.index;
+                    self.index += 1;
+                    self.state = State::Features { first: false, bare };
+                    return parse_feature(&raw)
+                        .map(Some)
+                        .map_err(|err| err.at_feature(index));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn add_member(&mut self, key: String, value: JsonValue) -> Result<(), ConversionError> {
+        match key.as_str() {
+            "type" => self.collection_type = value.as_str().map(str::to_string),
+            "bbox" => match serde_json::from_value(value) {
+                Ok(bbox) => self.metadata.bbox = Some(bbox),
+                Err(err) => return Err(self.json_error(err)),
+            },
+            _ => {
+                self.metadata.foreign_members.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn end_of_collection(&self) -> Result<(), ConversionError> {
+        match self.collection_type.as_deref() {
+            Some("FeatureCollection") => Ok(()),
+            Some(other) => Err(self.error(&format!(
+                "only a FeatureCollection or an array of Features can be streamed, not a {}",
+                other
+            ))),
+            None => Err(self.error("the top-level object has no `type`")),
+        }
+    }
+
+    /// The bytes of the JSON value starting at the current position.
+    ///
+    /// Only the nesting of brackets and strings is followed here; the value itself is
+    /// checked when it is parsed.
+    fn read_value(&mut self) -> Result<Vec<u8>, ConversionError> {
+        let mut value = Vec::new();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let byte = match self.peek()? {
+                Some(byte) => byte,
+                None if depth == 0 && !in_string && !value.is_empty() => return Ok(value),
+                None => return Err(self.error("unexpected end of the input")),
+            };
+            if in_string {
+                self.consume();
+                value.push(byte);
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                    if depth == 0 {
+                        return Ok(value);
+                    }
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => return Ok(value),
+                b'}' | b']' => depth -= 1,
+                b',' if depth == 0 => return Ok(value),
+                byte if depth == 0 && byte.is_ascii_whitespace() => return Ok(value),
+                _ => {}
+            }
+            self.consume();
+            value.push(byte);
+            if depth == 0 && matches!(byte, b'}' | b']') {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Skips whitespace, and the byte order mark some (mostly Windows) tools start files with.
+    fn skip_whitespace(&mut self) -> Result<(), ConversionError> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if self.offset == 0 && buf.starts_with("\u{FEFF}".as_bytes()) {
+                self.reader.consume(3);
+                continue;
+            }
+            match buf.first() {
+                Some(byte) if byte.is_ascii_whitespace() => self.consume(),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &str) -> Result<(), ConversionError> {
+        if self.peek()? == Some(byte) {
+            self.consume();
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ConversionError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    /// Moves past the byte returned by `peek()`.
+    fn consume(&mut self) {
+        if self.context.len() < CONTEXT_BYTES {
+            if let Ok(buf) = self.reader.fill_buf() {
+                self.context.extend(buf.first());
+            }
+        }
+        self.reader.consume(1);
+        self.offset += 1;
+    }
+
+    fn error(&self, message: &str) -> ConversionError {
+        let message = format!("{} at byte {}", message, self.offset);
+        self.json_error(serde_json::Error::custom(message))
+    }
+
+    fn json_error(&self, err: serde_json::Error) -> ConversionError {
+        ConversionError::parse(
+            geojson::Error::MalformedJson(err),
+            &String::from_utf8_lossy(&self.context),
+        )
+    }
+}
+
+impl<R: BufRead> Iterator for FeatureReader<R> {
+    type Item = Result<Feature, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.read_next();
+        if result.is_err() {
+            self.state = State::Done;
+        }
+        result.transpose()
+    }
+}
+
+fn parse_feature(raw: &[u8]) -> Result<Feature, ConversionError> {
+    let text = String::from_utf8_lossy(raw);
+    let mut value: JsonValue = serde_json::from_slice(raw)
+        .map_err(|err| ConversionError::parse(geojson::Error::MalformedJson(err), &text))?;
+    fill_missing_properties(&mut value);
+    Feature::from_json_value(value).map_err(|err| ConversionError::parse(err, &text))
+}
+
+/// Converts the GeoJSON file at `path` like `convert_with_options`, reading the features one
+/// at a time instead of loading the whole file, for files too large to fit in memory.
+///
+/// The file is read twice: once to infer the schema, and once to write the features. It must
+/// hold a FeatureCollection or a JSON array of Features (see `FeatureReader`).
+pub fn convert_file_streaming(
+    path: &Path,
+    output_base: &str,
+    options: ConversionOptions,
+) -> Result<ConversionSummary, ConversionError> {
+    let in_file = |err: ConversionError| err.in_file(Some(path));
+    let mut reader = FeatureReader::open(path)?;
+    let prepared = prepare(&mut reader, &options).map_err(in_file)?;
+
+    let filter = options.filter.clone();
+    let features = FeatureReader::open(path)?
+        .filter(move |feature| match (feature, &filter) {
+            (Ok(feature), Some(filter)) => filter.matches(feature),
+            _ => true,
+        })
+        .map(move |feature| feature.map_err(in_file));
+    let mut writer =
+        FeatureCollectionToShpWriter::from_prepared_collection(prepared, output_base, options)?;
+    if let Err(err) = writer.write_from(features) {
+        // As in `convert_contents`, the files write_from() leaves behind are put into place.
+        writer.finish()?;
+        return Err(err);
+    }
+    writer.write_metadata_sidecar()?;
+    #[cfg(feature = "esri-metadata")]
+    if writer.options.esri_metadata {
+        writer.write_esri_metadata()?;
+    }
+    writer.finish()
+}
+
+/// Streams through the features to infer the schema, as `PreparedCollection::new` does for a
+/// collection in memory. The collection returned holds the metadata, but no features.
+fn prepare<R: BufRead>(
+    reader: &mut FeatureReader<R>,
+    options: &ConversionOptions,
+) -> Result<PreparedCollection, ConversionError> {
+    let sample_size = match options.schema_sample {
+        SchemaSample::First(count) => count,
+        SchemaSample::All => usize::MAX,
+    };
+    let mut ids = FeatureIdCheck::default();
+    let mut count = 0;
+    let mut features_filtered = 0;
+    let mut sampled_properties = HashSet::new();
+    let mut read_error = None;
+    let attributes = std::iter::from_fn(|| loop {
+        let feature = match reader.next()? {
+            Ok(feature) => feature,
+            Err(err) => {
+                read_error = Some(err);
+                return None;
+            }
+        };
+        if let Some(filter) = &options.filter {
+            if !filter.matches(&feature) {
+                features_filtered += 1;
+                continue;
+            }
+        }
+        if let Some(field) = &options.feature_id_field {
+            ids.add(count, &feature, field, options);
+        }
+        count += 1;
+        // The rest of the features are still read, for their ids and the metadata after them.
+        if count <= sample_size {
+            let mut attributes: Map<String, JsonValue> =
+                feature_attributes(&feature, options, &mut ConversionSummary::default());
+            prepare_text_values(&mut attributes, options);
+            sampled_properties.extend(attributes.keys().cloned());
+            return Some(attributes);
+        }
+    });
+    let inferred = FieldSchema::infer_with_warnings(attributes, options);
+    if let Some(err) = read_error {
+        return Err(err);
+    }
+    let (mut schema, schema_warnings) = inferred?;
+    if let Some(field) = &options.feature_id_field {
+        ids.finish(field)?;
+        schema.move_to_front(field);
+    }
+    let metadata = reader.metadata();
+    Ok(PreparedCollection {
+        feature_collection: FeatureCollection {
+            bbox: metadata.bbox.clone(),
+            features: Vec::new(),
+            foreign_members: Some(metadata.foreign_members.clone())
+                .filter(|members| !members.is_empty()),
+        },
+        schema,
+        sampled_properties: (count > sample_size).then_some(sampled_properties),
+        summary: ConversionSummary {
+            features_filtered,
+            schema_warnings,
+            ..ConversionSummary::default()
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_features_and_metadata_one_at_a_time() {
+        let contents = "\u{FEFF} {\"bbox\": [0, 0, 1, 1], \"features\": [
+            {\"type\": \"Feature\", \"geometry\": {\"type\": \"Point\", \"coordinates\": [0.5, 0.5]}, \"properties\": {\"name\": \"a]\\\"b\"}},
+            {\"type\": \"Feature\", \"geometry\": null}
+        ], \"type\": \"FeatureCollection\", \"source\": \"survey\"}";
+        let mut reader = FeatureReader::new(contents.as_bytes());
+        let features: Vec<Feature> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(
+            features[0].property("name"),
+            Some(&JsonValue::from("a]\"b"))
+        );
+        assert!(features[1].geometry.is_none());
+        assert_eq!(reader.metadata().bbox, Some(vec![0.0, 0.0, 1.0, 1.0]));
+        assert_eq!(
+            reader.metadata().foreign_members.get("source"),
+            Some(&JsonValue::from("survey"))
+        );
+
+        let bare = r#"[{"type": "Feature", "geometry": null, "properties": {"n": 1}}]"#;
+        assert_eq!(FeatureReader::new(bare.as_bytes()).count(), 1);
+
+        let feature = r#"{"type": "Feature", "geometry": null, "properties": null}"#;
+        let results: Vec<_> = FeatureReader::new(feature.as_bytes()).collect();
+        assert!(matches!(&results[..], [Err(ConversionError::Parse { .. })]));
+
+        let truncated = r#"{"type": "FeatureCollection", "features": [{"type": "Feat"#;
+        let results: Vec<_> = FeatureReader::new(truncated.as_bytes()).collect();
+        assert!(matches!(&results[..], [Err(ConversionError::Parse { .. })]));
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn streams_the_same_shapefile_as_reading_the_whole_file() {
+        let path = "./fixtures/countries.geojson";
+        let whole = crate::tests::temp_output("countries_whole");
+        let convert = |path: &str, extra: &[&str], output: &str| {
+            let args: Vec<String> = ["geojson_to_shp", path, output, "--schema-sample", "50"]
+                .iter()
+                .chain(extra)
+                .map(|arg| arg.to_string())
+                .collect();
+            crate::Cli::new(args.into_iter())
+                .unwrap()
+                .convert()
+                .unwrap()
+        };
+        let expected = convert(path, &[], &whole);
+        // The gzipped fixture is decompressed as it is read, whole or streamed.
+        for (path, extra, output) in [
+            (path, &["--stream"][..], "countries_streamed"),
+            (
+                "./fixtures/countries.geojson.gz",
+                &[][..],
+                "countries_gzipped",
+            ),
+            (
+                "./fixtures/countries.geojson.gz",
+                &["--stream"][..],
+                "countries_gzipped_streamed",
+            ),
+        ] {
+            let output = crate::tests::temp_output(output);
+            let summary = convert(path, extra, &output);
+            assert_eq!(summary.features_written, expected.features_written);
+            assert_eq!(summary.schema_warnings, expected.schema_warnings);
+            for extension in ["shp", "shx", "dbf"] {
+                let read = |base: &str| std::fs::read(format!("{}.{}", base, extension)).unwrap();
+                assert!(read(&whole) == read(&output), "the .{} differ", extension);
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+use crate::{ConversionError, SchemaWarning};
+
+/// What happened during a conversion.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionSummary {
+    pub features_written: usize,
+    /// Number of features `ConversionOptions::filter` left out.
+    pub features_filtered: usize,
+    /// Number of features skipped by `ConversionOptions::dedupe`.
+    pub duplicates_skipped: usize,
+    /// Number of attribute values changed by `ConversionOptions::transliterate`.
+    pub transliterated_values: usize,
+    pub warnings: Vec<String>,
+    /// Features left out under `ErrorPolicy::Skip`.
+    pub skipped_features: Vec<SkippedFeature>,
+    /// The mean centroid of the features written, per GeoJSON geometry type, to check that
+    /// the data landed in the right part of the world. Each feature's centroid is the mean
+    /// of its vertices.
+    pub centroids: HashMap<String, [f64; 2]>,
+    /// Compromises made while inferring the schema, such as truncated field names.
+    pub schema_warnings: Vec<SchemaWarning>,
+    /// Data of individual features that could not be written as is.
+    pub conversion_warnings: Vec<ConversionWarning>,
+    /// The files written, in the order they were created, once the writer is finished.
+    pub files: Vec<PathBuf>,
+}
+
+/// Something a feature held that the shapefile cannot, and was left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionWarning {
+    /// Positions of the feature had `count` values past longitude, latitude and elevation,
+    /// such as a measure or application-specific extras.
+    ExtraCoordinatesDropped { feature_index: usize, count: usize },
+    /// The feature matched one already written, and `ConversionOptions::dedupe` left it out.
+    DuplicateFeatureSkipped { feature_index: usize },
+}
+
+impl fmt::Display for ConversionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionWarning::ExtraCoordinatesDropped {
+                feature_index,
+                count,
+            } => write!(
+                f,
+                "Feature {} has {} coordinate value(s) past longitude, latitude and elevation; they were dropped",
+                feature_index, count
+            ),
+            ConversionWarning::DuplicateFeatureSkipped { feature_index } => write!(
+                f,
+                "Feature {} duplicates a feature already written; it was skipped",
+                feature_index
+            ),
+        }
+    }
+}
+
+/// A feature left out of the shapefile because it could not be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFeature {
+    /// Zero-based index of the feature in the (filtered) collection.
+    pub index: usize,
+    pub reason: String,
+}
+
+impl ConversionSummary {
+    /// The outcome as a JSON object, for `--error-format json`: the number of features
+    /// written, every warning as text, and the features skipped with why.
+    pub fn to_json(&self) -> JsonValue {
+        let warnings: Vec<String> = self
+            .schema_warnings
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.warnings.iter().cloned())
+            .chain(self.conversion_warnings.iter().map(ToString::to_string))
+            .collect();
+        let skipped_features: Vec<JsonValue> = self
+            .skipped_features
+            .iter()
+            .map(|skipped| json!({"index": skipped.index, "reason": skipped.reason}))
+            .collect();
+        json!({
+            "features_written": self.features_written,
+            "warnings": warnings,
+            "skipped_features": skipped_features,
+        })
+    }
+
+    /// Writes a JSON manifest of the `files` written to `path`, with the size in bytes and
+    /// SHA-256 of each, so that a pipeline can check that its outputs did not change:
+    ///
+    /// ```json
+    /// {"files": [{"path": "out/roads.shx", "bytes": 108, "sha256": "9f86d0…"}]}
+    /// ```
+    pub fn write_manifest(&self, path: impl AsRef<Path>) -> Result<(), ConversionError> {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut hasher = Sha256::new();
+                let bytes = io::copy(&mut File::open(file)?, &mut hasher)?;
+                let sha256 = format!("{:x}", hasher.finalize());
+                Ok(json!({
+                    "path": file.display().to_string(),
+                    "bytes": bytes,
+                    "sha256": sha256,
+                }))
+            })
+            .collect::<Result<Vec<JsonValue>, ConversionError>>()?;
+        let manifest = serde_json::to_string_pretty(&json!({ "files": files }))?;
+        std::fs::write(path, manifest + "\n")?;
+        Ok(())
+    }
+
+    /// Whether there is anything to warn the user about.
+    pub fn has_warnings(&self) -> bool {
+        !(self.warnings.is_empty()
+            && self.skipped_features.is_empty()
+            && self.schema_warnings.is_empty()
+            && self.conversion_warnings.is_empty())
+    }
+
+    /// Shifts the feature indices by `offset`, for the summary of features written from the
+    /// `offset`th on, such as a chunk of `write_chunked`.
+    pub(crate) fn offset_feature_indices(&mut self, offset: usize) {
+        for skipped in self.skipped_features.iter_mut() {
+            skipped.index += offset;
+        }
+        for warning in self.conversion_warnings.iter_mut() {
+            match warning {
+                ConversionWarning::ExtraCoordinatesDropped { feature_index, .. }
+                | ConversionWarning::DuplicateFeatureSkipped { feature_index } => {
+                    *feature_index += offset
+                }
+            }
+        }
+    }
+
+    pub(crate) fn warn(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+
+use serde_json::{Map, Value as JsonValue};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::ConversionOptions;
+
+/// Applies the text options to every String attribute, returning how many values were transliterated.
+///
+/// This runs before both schema inference and record writing so that field widths
+/// are measured on the values that are actually written.
+pub(crate) fn prepare_text_values(
+    attributes: &mut Map<String, JsonValue>,
+    options: &ConversionOptions,
+) -> usize {
+    let mut altered = 0;
+    for value in attributes.values_mut() {
+        if let JsonValue::String(text) = value {
+            if options.collapse_whitespace {
+                if let Cow::Owned(collapsed) = collapse_whitespace(text) {
+                    *text = collapsed;
+                }
+            }
+            if options.trim_strings && text.trim() != text {
+                *text = text.trim().to_string();
+            }
+            if options.transliterate {
+                if let Cow::Owned(ascii) = transliterate(text) {
+                    *text = ascii;
+                    altered += 1;
+                }
+            }
+        }
+    }
+    altered
+}
+
+/// Replaces every run of whitespace with a single space.
+fn collapse_whitespace(text: &str) -> Cow<'_, str> {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    if collapsed == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(collapsed)
+    }
+}
+
+/// Transliterates text to ASCII where possible (é → e, ß → ss).
+///
+/// Characters are NFKD-decomposed and stripped of combining marks; a small table covers the
+/// letters that do not decompose. Anything else is left in place for the encoder to deal with.
+pub fn transliterate(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.nfkd().filter(|c| !is_combining_mark(*c)) {
+        match special_case(c) {
+            Some(replacement) => ascii.push_str(replacement),
+            None => ascii.push(c),
+        }
+    }
+    if ascii == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(ascii)
+    }
+}
+
+fn special_case(c: char) -> Option<&'static str> {
+    let replacement = match c {
+        'ß' => "ss",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ø' => "O",
+        'ø' => "o",
+        'Đ' | 'Ð' => "D",
+        'đ' | 'ð' => "d",
+        'Ł' => "L",
+        'ł' => "l",
+        'Þ' => "TH",
+        'þ' => "th",
+        'ı' => "i",
+        '‘' | '’' => "'",
+        '“' | '”' => "\"",
+        '–' | '—' => "-",
+        _ => return None,
+    };
+    Some(replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_to_ascii() {
+        assert_eq!(transliterate("Café Straße"), "Cafe Strasse");
+        assert_eq!(transliterate("Łódź"), "Lodz");
+        assert!(matches!(transliterate("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn trims_and_collapses_whitespace() {
+        let mut attributes = Map::new();
+        attributes.insert("city".to_string(), JsonValue::from("  Rochester \t NY "));
+        let options = ConversionOptions {
+            trim_strings: true,
+            ..Default::default()
+        };
+        let mut trimmed = attributes.clone();
+        prepare_text_values(&mut trimmed, &options);
+        assert_eq!(trimmed["city"], "Rochester \t NY");
+
+        let options = ConversionOptions {
+            trim_strings: true,
+            collapse_whitespace: true,
+            ..Default::default()
+        };
+        prepare_text_values(&mut attributes, &options);
+        assert_eq!(attributes["city"], "Rochester NY");
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use geojson::{Feature, FeatureCollection, Geometry};
+use serde_json::{Map, Number, Value as JsonValue};
+use shapefile::dbase::{FieldValue, Record};
+use shapefile::Shape;
+
+use crate::ConversionError;
+
+/// How the GeoJSON is laid out when written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeoJsonFormat {
+    /// A FeatureCollection on a single line.
+    #[default]
+    Compact,
+    /// An indented FeatureCollection.
+    Pretty,
+    /// Newline-delimited GeoJSON: one Feature per line, without the enclosing collection.
+    NewlineDelimited,
+}
+
+/// Reads a shapefile (.shp and its .dbf) back into GeoJSON features.
+pub struct ShapefileToGeoJsonConverter {
+    features: Vec<Feature>,
+}
+
+impl ShapefileToGeoJsonConverter {
+    /// Reads every shape and record of the shapefile at `shp_path`.
+    ///
+    /// Null shapes become features with a `null` geometry.
+    pub fn new(shp_path: &str) -> Result<Self, ConversionError> {
+        let mut reader = shapefile::Reader::from_path(shp_path)?;
+        let mut features = Vec::new();
+        for shape_record in reader.iter_shapes_and_records() {
+            let (shape, record) = shape_record?;
+            features.push(Feature {
+                bbox: None,
+                geometry: shape_to_geometry(shape)?,
+                id: None,
+                properties: Some(record_to_properties(record)),
+                foreign_members: None,
+            });
+        }
+        Ok(Self { features })
+    }
+
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    pub fn into_feature_collection(self) -> FeatureCollection {
+        FeatureCollection {
+            bbox: None,
+            features: self.features,
+            foreign_members: None,
+        }
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        mut dest: W,
+        format: GeoJsonFormat,
+    ) -> Result<(), ConversionError> {
+        match format {
+            GeoJsonFormat::NewlineDelimited => {
+                for feature in self.features.iter() {
+                    serde_json::to_writer(&mut dest, feature)?;
+                    dest.write_all(b"\n")?;
+                }
+            }
+            GeoJsonFormat::Compact | GeoJsonFormat::Pretty => {
+                let collection = FeatureCollection {
+                    bbox: None,
+                    features: self.features.clone(),
+                    foreign_members: None,
+                };
+                if format == GeoJsonFormat::Pretty {
+                    serde_json::to_writer_pretty(&mut dest, &collection)?;
+                } else {
+                    serde_json::to_writer(&mut dest, &collection)?;
+                }
+                dest.write_all(b"\n")?;
+            }
+        }
+        dest.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to_path(&self, path: &str, format: GeoJsonFormat) -> Result<(), ConversionError> {
+        self.write(BufWriter::new(File::create(path)?), format)
+    }
+}
+
+/// Converts a shape into a GeoJSON geometry, `None` for Null Shapes.
+///
+/// Shapefiles store every line and polygon as a multi-part shape; single-part
+/// shapes are written as a LineString or Polygon.
+fn shape_to_geometry(shape: Shape) -> Result<Option<Geometry>, ConversionError> {
+    if let Shape::NullShape = shape {
+        return Ok(None);
+    }
+    let geometry = geo_types::Geometry::<f64>::try_from(shape).map_err(|reason| {
+        ConversionError::UnsupportedGeometry {
+            geometry_type: reason.to_string(),
+        }
+    })?;
+    let geometry = match geometry {
+        geo_types::Geometry::MultiLineString(mut lines) if lines.0.len() == 1 => {
+            geo_types::Geometry::LineString(lines.0.remove(0))
+        }
+        geo_types::Geometry::MultiPolygon(mut polygons) if polygons.0.len() == 1 => {
+            geo_types::Geometry::Polygon(polygons.0.remove(0))
+        }
+        other => other,
+    };
+    Ok(Some(Geometry::new(geojson::Value::from(&geometry))))
+}
+
+fn record_to_properties(record: Record) -> Map<String, JsonValue> {
+    record
+        .into_iter()
+        .map(|(name, value)| (name, field_value_to_json(value)))
+        .collect()
+}
+
+fn field_value_to_json(value: FieldValue) -> JsonValue {
+    match value {
+        FieldValue::Character(Some(text)) | FieldValue::Memo(text) => JsonValue::String(text),
+        FieldValue::Numeric(Some(number)) => number_to_json(number),
+        FieldValue::Float(Some(number)) => number_to_json(number as f64),
+        FieldValue::Double(number) | FieldValue::Currency(number) => number_to_json(number),
+        FieldValue::Integer(number) => JsonValue::from(number),
+        FieldValue::Logical(Some(flag)) => JsonValue::Bool(flag),
+        FieldValue::Date(Some(date)) => JsonValue::String(format!(
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month(),
+            date.day()
+        )),
+        FieldValue::DateTime(date_time) => {
+            let (date, time) = (date_time.date(), date_time.time());
+            JsonValue::String(format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                date.year(),
+                date.month(),
+                date.day(),
+                time.hours(),
+                time.minutes(),
+                time.seconds()
+            ))
+        }
+        FieldValue::Character(None)
+        | FieldValue::Numeric(None)
+        | FieldValue::Float(None)
+        | FieldValue::Logical(None)
+        | FieldValue::Date(None) => JsonValue::Null,
+    }
+}
+
+/// Numeric fields are read as floats; whole numbers are written back as JSON integers.
+fn number_to_json(number: f64) -> JsonValue {
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        JsonValue::from(number as i64)
+    } else {
+        Number::from_f64(number).map_or(JsonValue::Null, JsonValue::Number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_converted_shapefile_back_into_features() {
+        let output = crate::tests::temp_output("round_trip_points");
+        let output = output.as_str();
+        let options = crate::ConversionOptions::default();
+        crate::convert_with_options("./fixtures/points.geojson", output, options).unwrap();
+
+        let converter = ShapefileToGeoJsonConverter::new(&format!("{}.shp", output)).unwrap();
+        let feature = &converter.features()[0];
+        let geometry = feature.geometry.as_ref().unwrap();
+        assert_eq!(geometry.value, geojson::Value::Point(vec![102.0, 0.5]));
+        let properties = feature.properties.as_ref().unwrap();
+        assert_eq!(properties["category"], "high");
+        assert_eq!(properties["ranking"], 1);
+
+        let mut lines = Vec::new();
+        converter
+            .write(&mut lines, GeoJsonFormat::NewlineDelimited)
+            .unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        assert_eq!(lines.lines().count(), converter.features().len());
+        assert!(lines
+            .lines()
+            .all(|line| matches!(line.parse(), Ok(geojson::GeoJson::Feature(_)))));
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::{Map, Value as JsonValue};
+use topojson::Topology;
+
+use crate::ConversionError;
+
+/// Whether the parsed input is a TopoJSON Topology rather than GeoJSON.
+pub(crate) fn is_topology(value: &JsonValue) -> bool {
+    value.get("type").and_then(JsonValue::as_str) == Some("Topology")
+}
+
+/// Decodes every object of a TopoJSON Topology into a single GeoJSON FeatureCollection.
+///
+/// The features of each object follow each other in the order of the object names.
+pub(crate) fn topology_to_feature_collection(
+    topology: JsonValue,
+) -> Result<JsonValue, ConversionError> {
+    let topology = match topology {
+        JsonValue::Object(object) => object,
+        _ => Map::new(),
+    };
+    let topology = Topology::from_json_object(topology)?;
+    let mut features = Vec::new();
+    for name in topology.list_names() {
+        // topojson builds on its own version of the geojson crate; going through JSON
+        // keeps the two apart.
+        let collection = serde_json::to_value(topojson::to_geojson(&topology, &name)?)?;
+        if let Some(JsonValue::Array(object_features)) = collection.get("features") {
+            features.extend(object_features.iter().cloned());
+        }
+    }
+    Ok(serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_arcs_into_features() {
+        let contents = std::fs::read_to_string("./fixtures/roads.topojson").unwrap();
+        let value: JsonValue = serde_json::from_str(&contents).unwrap();
+        assert!(is_topology(&value));
+
+        let collection = topology_to_feature_collection(value).unwrap();
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            serde_json::json!([[102.0, 0.0], [103.0, 1.0], [104.0, 0.0]])
+        );
+        assert_eq!(features[1]["properties"]["name"], "Oak Ave");
+    }
+}
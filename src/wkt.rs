@@ -0,0 +1,87 @@
+use shapefile::{Point, Shape};
+
+use crate::geom_convert::polygon_parts;
+
+/// Renders `shape` as Well-Known Text, with the points in the order of the shapefile record.
+///
+/// A polyline with several parts is a MULTILINESTRING, and a polygon with several exterior
+/// rings a MULTIPOLYGON. A Null Shape is `GEOMETRYCOLLECTION EMPTY`.
+pub(crate) fn shape_to_wkt(shape: &Shape) -> String {
+    match shape {
+        Shape::Point(point) => format!("POINT ({})", xy(point)),
+        Shape::Multipoint(multipoint) => {
+            let points: Vec<String> = multipoint
+                .points()
+                .iter()
+                .map(|point| format!("({})", xy(point)))
+                .collect();
+            format!("MULTIPOINT ({})", points.join(", "))
+        }
+        Shape::Polyline(line) => match &line.parts()[..] {
+            [part] => format!("LINESTRING {}", sequence(part)),
+            parts => {
+                let parts: Vec<String> = parts.iter().map(|part| sequence(part)).collect();
+                format!("MULTILINESTRING ({})", parts.join(", "))
+            }
+        },
+        Shape::Polygon(polygon) => match &polygon_parts(polygon)[..] {
+            [rings] => format!("POLYGON {}", rings_text(rings)),
+            polygons => {
+                let polygons: Vec<String> =
+                    polygons.iter().map(|rings| rings_text(rings)).collect();
+                format!("MULTIPOLYGON ({})", polygons.join(", "))
+            }
+        },
+        _ => "GEOMETRYCOLLECTION EMPTY".to_string(),
+    }
+}
+
+fn xy(point: &Point) -> String {
+    format!("{} {}", point.x, point.y)
+}
+
+fn sequence(points: &[Point]) -> String {
+    let points: Vec<String> = points.iter().map(xy).collect();
+    format!("({})", points.join(", "))
+}
+
+fn rings_text(rings: &[&[Point]]) -> String {
+    let rings: Vec<String> = rings.iter().map(|ring| sequence(ring)).collect();
+    format!("({})", rings.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom_convert::geometry_to_shp;
+    use crate::ConversionOptions;
+    use geojson::Value;
+
+    #[test]
+    fn renders_shapes_as_wkt() {
+        let options = ConversionOptions::default();
+        let wkt = |value: Value| shape_to_wkt(&geometry_to_shp(&value, &options).unwrap());
+        assert_eq!(wkt(Value::Point(vec![1.5, -2.0])), "POINT (1.5 -2)");
+        assert_eq!(
+            wkt(Value::MultiPoint(vec![vec![1.0, 2.0], vec![3.0, 4.0]])),
+            "MULTIPOINT ((1 2), (3 4))"
+        );
+        assert_eq!(
+            wkt(Value::MultiLineString(vec![
+                vec![vec![0.0, 0.0], vec![1.0, 1.0]],
+                vec![vec![2.0, 2.0], vec![3.0, 3.0]],
+            ])),
+            "MULTILINESTRING ((0 0, 1 1), (2 2, 3 3))"
+        );
+        assert_eq!(
+            wkt(Value::Polygon(vec![vec![
+                vec![0.0, 0.0],
+                vec![0.0, 1.0],
+                vec![1.0, 1.0],
+                vec![0.0, 0.0],
+            ]])),
+            "POLYGON ((0 0, 0 1, 1 1, 0 0))"
+        );
+        assert_eq!(shape_to_wkt(&Shape::NullShape), "GEOMETRYCOLLECTION EMPTY");
+    }
+}